@@ -1,10 +1,15 @@
 use lib_benchmark::run_benchmark;
-use lib_game_cli::GameCLI;
+use lib_game_cli::{GameCLI, Side};
+use lib_game_board::{sequence_position::SequencePosition, bitboard_position_with_ordering::BitboardPositionWithOrdering};
+use lib_game_board::player::{RandomPlayer, ScriptedPlayer};
+use lib_alpha_beta_solver::{ai_player::Difficulty, final_alpha_beta::FinalAlphaBeta};
+use lib_book_cli::{generate_book, book_info, book_lookup, book_merge};
+use lib_tournament::{run_tournament, default_book_path, PLAYER_NAMES};
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
     if args.len() < 2 {
-        println!("\n\n`cargo run`: invalid arguments. Run either `cargo run benchmark ...` or `cargo run game`");
+        println!("\n\n`cargo run`: invalid arguments. Run either `cargo run benchmark ...`, `cargo run book ...`, `cargo run game ...` or `cargo run tournament ...`");
         return;
     }
 
@@ -14,7 +19,7 @@ fn main() {
             println!("\n\ncargo run benchmark: invalid arguments list. The argument list should be as follow:");
             println!("\tcargo run benchmark [solver] [weak] [position] [move_ordering] [L] [R] [games_number]");
             println!("where:");
-            println!("\t- 'solver': the solver type. Choose between 'min_max', 'alpha_beta', 'alpha_beta_with_transposition', 'alpha_beta_with_iterative_deepening', 'anticipating_alpha_beta', 'alpha_beta_with_ordering', 'alpha_beta_with_optimised_transposition', and 'alpha_beta_with_lower_bound_transposition'.");
+            println!("\t- 'solver': the solver type. Choose between 'min_max', 'alpha_beta', 'alpha_beta_with_transposition', 'alpha_beta_with_iterative_deepening', 'anticipating_alpha_beta', 'alpha_beta_with_ordering', 'alpha_beta_with_optimised_transposition', 'alpha_beta_with_lower_bound_transposition', 'alpha_beta_with_opening_book', 'final_alpha_beta' and 'parallel_alpha_beta' (followed by '_[threads]', such as 'parallel_alpha_beta_8', to choose the number of threads, all the cores by default).");
             println!("\t- 'weak': compute the numbers of move until the end (strong) or only the winner (weak). Choose between 'strong' and 'weak'.");
             println!("\t- 'position': the representation of the board. Choose between 'grid', 'stack' and 'bitboard'.");
             println!("\t- 'move_ordering': the order of the moves. Impactful only for Alpha-Beta-based solvers. Choose between 'left_to_right', and 'center_first'.");
@@ -24,7 +29,7 @@ fn main() {
             return;
         }
     
-        let solver_string = &args[2+0];
+        let solver_string = &args[2];
         let weak_string = &args[2+1];
         let position_string = &args[2+2];
         let move_ordering_string = &args[2+3];
@@ -39,14 +44,118 @@ fn main() {
             games_number = None;
         }
     
-        run_benchmark(&solver_string, &weak_string, &position_string, &move_ordering_string, length, rating, games_number);
+        run_benchmark(solver_string, weak_string, position_string, move_ordering_string, length, rating, games_number);
+    } else if args[1] == "book" {
+        match (args.get(2).map(String::as_str), args.len()) {
+            // /target/... book generate width height depth path
+            (Some("generate"), 7) => {
+                let width: usize = args[3].trim().parse().expect("2nd argument, 'width', is not a number.");
+                let height: usize = args[4].trim().parse().expect("3rd argument, 'height', is not a number.");
+                let depth: usize = args[5].trim().parse().expect("4th argument, 'depth', is not a number.");
+                generate_book(width, height, depth, &args[6]);
+            },
+            // /target/... book info path
+            (Some("info"), 4) => book_info(&args[3]),
+            // /target/... book lookup path sequence
+            (Some("lookup"), 5) => book_lookup(&args[3], &args[4]),
+            // /target/... book merge output inputs...
+            (Some("merge"), length) if length >= 6 => book_merge(&args[3], &args[4..]),
+            _ => {
+                println!("\n\ncargo run book: invalid arguments list. The argument list should be one of:");
+                println!("\tcargo run book generate [width] [height] [depth] [path]");
+                println!("\tcargo run book info [path]");
+                println!("\tcargo run book lookup [path] [sequence]");
+                println!("\tcargo run book merge [output] [input] [input] ...");
+                println!("where:");
+                println!("\t- 'width' and 'height': the dimensions of the board.");
+                println!("\t- 'depth': the number of moves up to which positions are stored in the book.");
+                println!("\t- 'path': the book file. If a generation is interrupted, run the same command again to resume it.");
+                println!("\t- 'sequence': the moves leading to the position, such as '4453'.");
+                println!("\t- 'output' and 'input': the merged book, and the books to merge, of the same board but possibly of different depths.");
+            }
+        }
     } else if args[1] == "game" {
+        // /target/... game mode difficulty width height sequence
+        let mode = args.get(2).map_or("human", String::as_str);
+        let difficulty = args.get(3).map_or("perfect", String::as_str);
+        let width = args.get(4).map_or(Ok(7), |width| width.trim().parse());
+        let height = args.get(5).map_or(Ok(6), |height| height.trim().parse());
+        let sequence = SequencePosition::parse(args.get(6).map_or("", String::as_str));
+
+        match (width, height, sequence) {
+            (Ok(width), Ok(height), Ok(sequence)) if args.len() <= 7
+                && ["human", "ai", "hotseat", "watch", "replay"].contains(&mode)
+                && ["random", "easy", "medium", "hard", "perfect"].contains(&difficulty)
+                && FinalAlphaBeta::<u64>::supports(width, height) => {
+                let computer = || match difficulty {
+                    "random" => Side::Computer(Box::new(RandomPlayer::new())),
+                    "easy" => Side::Computer(Box::new(GameCLI::ai_player(width, height, Difficulty::Easy))),
+                    "medium" => Side::Computer(Box::new(GameCLI::ai_player(width, height, Difficulty::Medium))),
+                    "hard" => Side::Computer(Box::new(GameCLI::ai_player(width, height, Difficulty::Hard))),
+                    _ => Side::Computer(Box::new(GameCLI::ai_player(width, height, Difficulty::Perfect)))
+                };
+                let replay = || Side::Computer(Box::new(ScriptedPlayer::new(&sequence)));
 
-        let mut game_cli = GameCLI::new(7, 6);
-        game_cli.run_game();
+                let game_cli = match mode {
+                    // The recorded game is replayed from the empty board
+                    "replay" => sequence.play_on(&mut BitboardPositionWithOrdering::new(width, height))
+                        .map(|()| GameCLI::new(width, height, replay(), replay())),
+                    _ => {
+                        let (red, yellow) = match mode {
+                            "human" => (Side::Human, computer()),
+                            "ai" => (computer(), Side::Human),
+                            "hotseat" => (Side::Human, Side::Human),
+                            _ => (computer(), computer())
+                        };
+                        GameCLI::from_seq(width, height, red, yellow, &sequence)
+                    }
+                };
+                match game_cli {
+                    Ok(mut game_cli) => game_cli.run_game(),
+                    Err(error) => println!("\n\ncargo run game: invalid sequence: {}.", error)
+                }
+            },
+            _ => {
+                println!("\n\ncargo run game: invalid arguments list. The argument list should be as follow:");
+                println!("\tcargo run game [mode] [difficulty] [width] [height] [sequence]");
+                println!("where:");
+                println!("\t- 'mode': who plays. Choose between 'human' (you move first against the AI), 'ai' (the AI moves first), 'hotseat' (two humans), 'watch' (the AI against itself) and 'replay' (steps through the recorded 'sequence'). Defaults to 'human'.");
+                println!("\t- 'difficulty': the strength of the AI. Choose between 'random', 'easy', 'medium', 'hard' and 'perfect'. Defaults to 'perfect'.");
+                println!("\t- 'width' and 'height': the dimensions of the board, of at least two cells, whose columns plus one spare cell each must fit in 55 cells. Defaults to 7 and 6.");
+                println!("\t- 'sequence': the moves leading to the starting position, such as '4453', or the game to replay. Defaults to the empty board.");
+            }
+        }
+    } else if args[1] == "tournament" {
+        // /target/... tournament width height depth csv [--book=path] players...
+        let width = args.get(2).map(|width| width.trim().parse::<usize>());
+        let height = args.get(3).map(|height| height.trim().parse::<usize>());
+        let depth = args.get(4).map(|depth| depth.trim().parse::<usize>());
+        let book_path = args.iter().skip(6).find_map(|arg| arg.strip_prefix("--book="));
+        let mut names: Vec<String> = args.iter().skip(6).filter(|arg| !arg.starts_with("--book=")).cloned().collect();
+        if names.is_empty() {
+            names = ["random", "easy", "medium", "hard", "perfect"].iter().map(|name| name.to_string()).collect();
+        }
 
+        match (width, height, depth, args.get(5)) {
+            (Some(Ok(width)), Some(Ok(height)), Some(Ok(depth)), Some(csv_path)) if FinalAlphaBeta::<u64>::supports(width, height)
+                && depth < width * height && names.len() >= 2
+                && names.iter().all(|name| PLAYER_NAMES.contains(&name.as_str()))
+                && names.iter().enumerate().all(|(index, name)| !names[..index].contains(name)) => {
+                let book_path = book_path.map_or_else(|| default_book_path(width, height), str::to_string);
+                run_tournament(width, height, depth, csv_path, &book_path, &names);
+            },
+            _ => {
+                println!("\n\ncargo run tournament: invalid arguments list. The argument list should be as follow:");
+                println!("\tcargo run tournament [width] [height] [depth] [csv] [--book=path] [player] [player] ...");
+                println!("where:");
+                println!("\t- 'width' and 'height': the dimensions of the board, of at least two cells, whose columns plus one spare cell each must fit in 55 cells.");
+                println!("\t- 'depth': the number of moves of the openings. Every pair of players meets from each opening that does not end the game, once with each colour.");
+                println!("\t- 'csv': the file where the standings are written.");
+                println!("\t- 'path': the opening book of the AI players. Defaults to 'libs/alpha-beta-solver/opening-books/[width]x[height]_small.book'.");
+                println!("\t- 'player': at least two different players. Choose between 'random', 'easy', 'medium', 'hard', 'perfect', 'alpha_beta' and 'min_max'. Defaults to 'random', 'easy', 'medium', 'hard' and 'perfect'. 'min_max' only plays on boards of up to 16 cells, 'alpha_beta' of up to 20 cells, and 'perfect' of up to 30 cells without an opening book.");
+            }
+        }
     } else {
-        println!("\n\n`cargo run`: invalid argument. Run either `cargo run benchmark ...` or `cargo run game ...`");
-        return;
+        println!("\n\n`cargo run`: invalid argument. Run either `cargo run benchmark ...`, `cargo run book ...`, `cargo run game ...` or `cargo run tournament ...`");
     }
 }
\ No newline at end of file
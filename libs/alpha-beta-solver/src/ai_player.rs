@@ -0,0 +1,200 @@
+use lib_game_board::{BitboardMovePosition, Player, Analyser};
+use lib_game_board::bitboard::Bitboard;
+use lib_game_board::player::Random;
+use std::time::Duration;
+
+use crate::final_alpha_beta::FinalAlphaBeta;
+use crate::search_limit::SearchLimit;
+
+/// How far from perfect the moves of an `AiPlayer` are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    /// Draws the moves with a softmax over their scores, which often lets slip a win.
+    Easy,
+    /// Softmax sharper than `Easy`: the best moves are favoured, but mistakes still happen.
+    Medium,
+    /// Plays any move that does not lose, or the longest defence when every move loses.
+    Hard,
+    /// Always plays an optimal move.
+    Perfect
+}
+
+impl Difficulty {
+    /// Temperature of the softmax over the scores of the moves, `None` when the moves are not drawn this way.
+    fn temperature(&self) -> Option<f64> {
+        match self {
+            Difficulty::Easy => Some(4.),
+            Difficulty::Medium => Some(1.),
+            Difficulty::Hard | Difficulty::Perfect => None
+        }
+    }
+}
+
+/// Time given by default to the weaker levels to score the moves.
+pub const DEFAULT_LIMIT: SearchLimit = SearchLimit::Time(Duration::from_secs(1));
+
+/// Opponent of configurable strength, choosing among the moves by their score. Below `Perfect`,
+/// the moves are searched within a limit: the scores not found in time are estimated from their bounds.
+pub struct AiPlayer<B: Bitboard = u64> {
+    solver: FinalAlphaBeta<B>,
+    difficulty: Difficulty,
+    limit: SearchLimit,
+    random: Random
+}
+
+impl<B: Bitboard> AiPlayer<B> {
+    pub fn new(solver: FinalAlphaBeta<B>, difficulty: Difficulty) -> Self {
+        Self { solver, difficulty, limit: DEFAULT_LIMIT, random: Random::from_time() }
+    }
+
+    /// Player whose random choices are always the same for a given seed.
+    pub fn with_seed(solver: FinalAlphaBeta<B>, difficulty: Difficulty, seed: u64) -> Self {
+        Self { solver, difficulty, limit: DEFAULT_LIMIT, random: Random::new(seed) }
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    /// Limit shared by the searches of the moves, below `Perfect`.
+    pub fn set_limit(&mut self, limit: SearchLimit) {
+        self.limit = limit;
+    }
+
+    /// Solver of the player, whose transposition table is filled by the previous moves.
+    pub fn solver(&mut self) -> &mut FinalAlphaBeta<B> {
+        &mut self.solver
+    }
+
+    /// Draws a column given the score of each one, `None` for the full ones.
+    fn pick(&mut self, scores: &[Option<i32>]) -> Option<usize> {
+        let playable: Vec<(usize, i32)> = scores.iter().enumerate()
+            .filter_map(|(column, score)| score.map(|score| (column, score)))
+            .collect();
+        let best = playable.iter().map(|&(_, score)| score).max()?;
+
+        let weights: Vec<f64> = match self.difficulty.temperature() {
+            Some(temperature) => playable.iter()
+                .map(|&(_, score)| ((score - best) as f64 / temperature).exp())
+                .collect(),
+            None => playable.iter()
+                .map(|&(_, score)| if score >= best.min(0) { 1. } else { 0. })
+                .collect()
+        };
+
+        let mut target = self.random.uniform() * weights.iter().sum::<f64>();
+        for (&(column, _), weight) in playable.iter().zip(weights) {
+            if target < weight {
+                return Some(column);
+            }
+            target -= weight;
+        }
+        playable.iter().rev().find(|&&(_, score)| score == best).map(|&(column, _)| column)
+    }
+}
+
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> Player<P> for AiPlayer<B> {
+    fn choose_move(&mut self, position: &P) -> Option<usize> {
+        match self.difficulty {
+            Difficulty::Perfect => self.solver.best_move(position).1,
+            _ => {
+                let nb_playable = (0..position.width()).filter(|&column| position.can_play(column)).count();
+                let scores: Vec<Option<i32>> = self.solver.analyze_all_moves_with_limit(position, self.limit.divided(nb_playable))
+                    .iter()
+                    .map(|result| result.map(|result| result.estimate()))
+                    .collect();
+                self.pick(&scores)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod ai_player_tests {
+    use super::*;
+    use lib_game_board::{Position, Solver, sequence_position::SequencePosition};
+    use lib_game_board::bitboard_position_with_ordering::BitboardPositionWithOrdering;
+
+    fn player(difficulty: Difficulty, seed: u64) -> AiPlayer {
+        AiPlayer::with_seed(FinalAlphaBeta::new(7, 6, lib_game_board::center_first(7)), difficulty, seed)
+    }
+
+    fn draws(difficulty: Difficulty, scores: &[Option<i32>]) -> Vec<usize> {
+        let mut player = player(difficulty, 42);
+        let mut counts = vec![0; scores.len()];
+        for _ in 0..1000 {
+            counts[player.pick(scores).unwrap()] += 1;
+        }
+        counts
+    }
+
+    #[test]
+    fn hard_avoids_losing_moves() {
+        let counts = draws(Difficulty::Hard, &[Some(-3), None, Some(0), Some(2), Some(-1)]);
+        assert_eq!(counts[0] + counts[1] + counts[4], 0);
+        assert!(counts[2] > 0 && counts[3] > 0);
+
+        // Only the longest defence remains
+        let counts = draws(Difficulty::Hard, &[Some(-3), Some(-1), Some(-1), Some(-2)]);
+        assert_eq!(counts[0] + counts[3], 0);
+    }
+
+    #[test]
+    fn softmax_favours_best_moves() {
+        let scores = [Some(-5), Some(1), Some(-1), None];
+        for difficulty in [Difficulty::Easy, Difficulty::Medium] {
+            let counts = draws(difficulty, &scores);
+            assert!(counts[1] > counts[2] && counts[2] > counts[0], "{:?}: {:?}", difficulty, counts);
+            assert_eq!(counts[3], 0);
+        }
+        // Easy makes more mistakes
+        assert!(draws(Difficulty::Easy, &scores)[1] < draws(Difficulty::Medium, &scores)[1]);
+    }
+
+    #[test]
+    fn no_move_left() {
+        assert_eq!(player(Difficulty::Easy, 1).pick(&[None, None]), None);
+    }
+
+    #[test]
+    fn same_seed_same_moves() {
+        let scores = [Some(0), Some(1), Some(-1), Some(1)];
+        let moves = |seed| {
+            let mut player = player(Difficulty::Easy, seed);
+            (0..20).map(|_| player.pick(&scores).unwrap()).collect::<Vec<_>>()
+        };
+        assert_eq!(moves(7), moves(7));
+    }
+
+    #[test]
+    fn limited_moves() {
+        // Nothing is solved in the first moves: the searches must stop anyway
+        let position = BitboardPositionWithOrdering::new(7, 6);
+        for difficulty in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard] {
+            let mut player = player(difficulty, 5);
+            player.set_limit(SearchLimit::Nodes(7_000));
+            assert!(position.can_play(player.choose_move(&position).unwrap()));
+            assert!(Solver::<BitboardPositionWithOrdering>::explored_positions(player.solver()) <= 7_000);
+        }
+    }
+
+    #[test]
+    fn legal_moves() {
+        let position = BitboardPositionWithOrdering::try_from(
+            &SequencePosition::parse("2252576253462244111563365343671351441").unwrap()
+        ).unwrap();
+        let score = FinalAlphaBeta::new(7, 6, lib_game_board::center_first(7)).solve(&position);
+
+        for difficulty in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard, Difficulty::Perfect] {
+            let mut player = player(difficulty, 3);
+            let column = player.choose_move(&position).unwrap();
+            assert!(position.can_play(column));
+
+            if difficulty == Difficulty::Perfect {
+                let mut position2 = position.clone();
+                position2.play(column);
+                assert!(position.is_winning_move(column) || -player.solver().solve(&position2) == score);
+            }
+        }
+    }
+}
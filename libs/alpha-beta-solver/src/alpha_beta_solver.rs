@@ -1,17 +1,23 @@
-use lib_game_board::{Solver, WeakSolver};
+use lib_game_board::{Position, Solver, WeakSolver, Analyser};
+use crate::search_control::{SearchControl, Controllable};
 
 pub struct AlphaBetaSolver {
     move_order: Vec<usize>,
-    explored_positions: usize
+    explored_positions: usize,
+    control: SearchControl
 }
 
 impl AlphaBetaSolver {
     pub fn new(move_order: Vec<usize>) -> Self {
-        Self { move_order, explored_positions: 0 }
+        Self { move_order, explored_positions: 0, control: SearchControl::default() }
     }
 
-    fn solve_range(&mut self, position: &(impl lib_game_board::Position + Clone), mut alpha: i32, mut beta: i32) -> i32 {
+    fn solve_range(&mut self, position: &(impl Position + Clone), mut alpha: i32, mut beta: i32) -> i32 {
         self.explored_positions += 1;
+        if self.control.is_cancelled() {
+            return 0;
+        }
+
         // Draw
         if position.nb_moves() == position.width() * position.height() {
             return 0;
@@ -39,7 +45,10 @@ impl AlphaBetaSolver {
                 let mut position2 = position.clone();
                 position2.play(*column);
 
-                let score = - self.solve_range(&mut position2, -beta, -alpha);
+                let score = - self.solve_range(&position2, -beta, -alpha);
+                if self.control.is_cancelled() {
+                    return 0;
+                }
                 
                 if score >= beta {
                     return score;
@@ -52,13 +61,23 @@ impl AlphaBetaSolver {
 
         alpha
     }
+
+    /// Searches the whole window at once, so that the progress is only reported with the score.
+    fn search(&mut self, position: &(impl Position + Clone), alpha: i32, beta: i32) -> i32 {
+        self.control.start();
+        let score = self.solve_range(position, alpha, beta);
+        if !self.control.is_cancelled() {
+            self.control.report(score, score, self.explored_positions);
+        }
+        score
+    }
 }
 
-impl Solver for AlphaBetaSolver {
+impl<P: Position + Clone> Solver<P> for AlphaBetaSolver {
     /// Uses negamax to solve the position.
-    fn solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+    fn solve(&mut self, position: &P) -> i32 {
         let best_score = (position.width() * position.height()) as i32;
-        self.solve_range(position, -best_score, best_score)
+        self.search(position, -best_score, best_score)
     }
 
     fn explored_positions(&self) -> usize {
@@ -70,9 +89,21 @@ impl Solver for AlphaBetaSolver {
     }
 }
 
-impl WeakSolver for AlphaBetaSolver {
-    fn weak_solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
-        self.solve_range(position, -1, 1)
+impl<P: Position + Clone> Analyser<P> for AlphaBetaSolver {
+    fn is_cancelled(&mut self) -> bool {
+        self.control.is_cancelled()
+    }
+}
+
+impl Controllable for AlphaBetaSolver {
+    fn control(&mut self) -> &mut SearchControl {
+        &mut self.control
+    }
+}
+
+impl<P: Position + Clone> WeakSolver<P> for AlphaBetaSolver {
+    fn weak_solve(&mut self, position: &P) -> i32 {
+        self.search(position, -1, 1)
     }
 
     fn explored_positions(&self) -> usize {
@@ -87,17 +118,19 @@ impl WeakSolver for AlphaBetaSolver {
 #[cfg(test)]
 mod alpha_beta_tests {
     use super::*;
-    use lib_game_board::{grid_position::GridPosition, sequence_position::SequencePosition, stack_position::StackPosition};
+    use lib_game_board::{Position, grid_position::GridPosition, sequence_position::SequencePosition, stack_position::StackPosition};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
 
     #[test]
     fn grid_correctness() {
         let mut alpha_beta_solver = AlphaBetaSolver::new((0..7).collect());
 
         assert_eq!(alpha_beta_solver.solve(
-        &mut GridPosition::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
+        &GridPosition::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
 
@@ -106,10 +139,60 @@ mod alpha_beta_tests {
         let mut alpha_beta_solver = AlphaBetaSolver::new((0..7).collect());
 
         assert_eq!(alpha_beta_solver.solve(
-        &mut StackPosition::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
+        &StackPosition::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
+
+    #[test]
+    fn grid_small_boards() {
+        let mut alpha_beta_solver = AlphaBetaSolver::new((0..4).collect());
+        assert_eq!(alpha_beta_solver.solve(
+            &GridPosition::from_seq_with_dims(4, 4, &SequencePosition::parse("").unwrap()).unwrap()),
+            0);
+
+        let mut alpha_beta_solver = AlphaBetaSolver::new(vec![2, 1, 3, 0, 4]);
+        assert_eq!(alpha_beta_solver.solve(
+            &GridPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("1").unwrap()).unwrap()),
+            1);
+    }
+
+    #[test]
+    fn grid_principal_variation() {
+        let mut alpha_beta_solver = AlphaBetaSolver::new((0..4).collect());
+        let position = GridPosition::from_seq_with_dims(4, 4, &SequencePosition::parse("").unwrap()).unwrap();
+        let analysis = alpha_beta_solver.analyse(&position);
+        assert_eq!(analysis.score, 0);
+
+        // A draw: the principal variation fills the board without any alignment.
+        let mut position2 = position.clone();
+        for &column in &analysis.principal_variation {
+            assert!(position2.can_play(column) && !position2.is_winning_move(column));
+            position2.play(column);
+        }
+        assert_eq!(position2.nb_moves(), 16);
+    }
+
+    #[test]
+    fn cancelled_search() {
+        let token = Arc::new(AtomicBool::new(true));
+        let mut alpha_beta_solver = AlphaBetaSolver::new((0..4).collect());
+        alpha_beta_solver.control().set_cancel_token(Arc::clone(&token));
+        let position = GridPosition::from_seq_with_dims(4, 4, &SequencePosition::parse("").unwrap()).unwrap();
+        assert_eq!(alpha_beta_solver.try_solve(&position), None);
+        assert_eq!(alpha_beta_solver.best_move(&position).1, None);
+
+        token.store(false, Ordering::Relaxed);
+        assert_eq!(alpha_beta_solver.try_solve(&position), Some(0));
+    }
+
+    #[test]
+    fn stack_small_boards() {
+        let mut alpha_beta_solver = AlphaBetaSolver::new((0..4).collect());
+        assert_eq!(alpha_beta_solver.solve(
+            &StackPosition::from_seq_with_dims(4, 4, &SequencePosition::parse("").unwrap()).unwrap()),
+            0);
+    }
 }
\ No newline at end of file
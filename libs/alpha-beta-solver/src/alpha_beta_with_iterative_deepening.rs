@@ -1,19 +1,25 @@
-use lib_game_board::{Solver, WeakSolver};
+use lib_game_board::{KeyedPosition, Solver, WeakSolver, Analyser};
+use lib_game_board::bitboard::Bitboard;
 use crate::simple_transposition_table::{SimpleTranspositionTable, TABLE_SIZE};
+use crate::search_control::{SearchControl, Controllable};
 
-pub struct AlphaBetaWithIterativeDeepening {
+pub struct AlphaBetaWithIterativeDeepening<B: Bitboard = u64> {
     move_order: Vec<usize>,
     explored_positions: usize,
-    transposition_table: SimpleTranspositionTable
+    transposition_table: SimpleTranspositionTable<B>,
+    control: SearchControl
 }
 
-impl AlphaBetaWithIterativeDeepening {
+impl<B: Bitboard> AlphaBetaWithIterativeDeepening<B> {
     pub fn new(move_order: Vec<usize>) -> Self {
-        Self { move_order, explored_positions: 0, transposition_table: SimpleTranspositionTable::new(TABLE_SIZE) }
+        Self { move_order, explored_positions: 0, transposition_table: SimpleTranspositionTable::new(TABLE_SIZE), control: SearchControl::default() }
     }
 
-    fn solve_range(&mut self, position: &(impl lib_game_board::Position + Clone), mut alpha: i32, mut beta: i32) -> i32 {
+    fn solve_range(&mut self, position: &(impl KeyedPosition<Bitboard = B> + Clone), mut alpha: i32, mut beta: i32) -> i32 {
         self.explored_positions += 1;
+        if self.control.is_cancelled() {
+            return 0;
+        }
         // Draw
         if position.nb_moves() == position.width() * position.height() {
             return 0;
@@ -26,8 +32,8 @@ impl AlphaBetaWithIterativeDeepening {
             }
         }
 
-        let position_min_score = - ((position.width()*position.height()) as i32) /2 + 3;
-        let max_score = match self.transposition_table.get(position.key()) {
+        let position_min_score = position.min_score();
+        let max_score = match self.transposition_table.get(position.canonical_key()) {
             None => ((position.width()*position.height() - 1 - position.nb_moves()) / 2) as i32,
             Some(val) => val as i32 + position_min_score // val + min_score
         };
@@ -46,7 +52,10 @@ impl AlphaBetaWithIterativeDeepening {
                 let mut position2 = position.clone();
                 position2.play(*column);
 
-                let score = - self.solve_range(&mut position2, -beta, -alpha);
+                let score = - self.solve_range(&position2, -beta, -alpha);
+                if self.control.is_cancelled() {
+                    return 0;
+                }
                 
                 if score >= beta {
                     return score;
@@ -57,15 +66,16 @@ impl AlphaBetaWithIterativeDeepening {
             }
         }
 
-        self.transposition_table.insert(position.key(), (alpha - position_min_score + 1) as u8);
+        self.transposition_table.insert(position.canonical_key(), (alpha - position_min_score + 1) as u8);
         alpha
     }
 }
 
-impl Solver for AlphaBetaWithIterativeDeepening {
-    fn solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+impl<B: Bitboard, P: KeyedPosition<Bitboard = B> + Clone> Solver<P> for AlphaBetaWithIterativeDeepening<B> {
+    fn solve(&mut self, position: &P) -> i32 {
         let mut min = - ((position.width()*position.height()) as i32 - position.nb_moves() as i32)/2;
         let mut max = ((position.width()*position.height() + 1) as i32 - position.nb_moves() as i32)/2;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -74,11 +84,15 @@ impl Solver for AlphaBetaWithIterativeDeepening {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let r = self.solve_range(position, med, med+1);
+            if self.control.is_cancelled() {
+                break;
+            }
             if r <= med {
                 max = r;
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
         }
 
         min
@@ -93,10 +107,23 @@ impl Solver for AlphaBetaWithIterativeDeepening {
     }
 }
 
-impl WeakSolver for AlphaBetaWithIterativeDeepening {
-    fn weak_solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+impl<B: Bitboard, P: KeyedPosition<Bitboard = B> + Clone> Analyser<P> for AlphaBetaWithIterativeDeepening<B> {
+    fn is_cancelled(&mut self) -> bool {
+        self.control.is_cancelled()
+    }
+}
+
+impl<B: Bitboard> Controllable for AlphaBetaWithIterativeDeepening<B> {
+    fn control(&mut self) -> &mut SearchControl {
+        &mut self.control
+    }
+}
+
+impl<B: Bitboard, P: KeyedPosition<Bitboard = B> + Clone> WeakSolver<P> for AlphaBetaWithIterativeDeepening<B> {
+    fn weak_solve(&mut self, position: &P) -> i32 {
         let mut min = -1;
         let mut max = 1;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -105,11 +132,15 @@ impl WeakSolver for AlphaBetaWithIterativeDeepening {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let r = self.solve_range(position, med, med+1);
+            if self.control.is_cancelled() {
+                break;
+            }
             if r <= med {
                 max = r;
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
         }
 
         min
@@ -135,10 +166,10 @@ mod alpha_beta_with_iterative_deepening_tests {
         let mut alpha_beta_with_transposition = AlphaBetaWithIterativeDeepening::new((0..7).collect());
 
         assert_eq!(alpha_beta_with_transposition.solve(
-        &mut BitboardPosition::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
+        &BitboardPosition::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
 }
\ No newline at end of file
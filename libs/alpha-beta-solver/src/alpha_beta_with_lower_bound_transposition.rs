@@ -1,24 +1,30 @@
-use lib_game_board::{Solver, WeakSolver};
+use lib_game_board::{BitboardMovePosition, Solver, WeakSolver, Analyser};
+use lib_game_board::bitboard::Bitboard;
 use crate::lower_bound_transposition_table::{LowerBoundTranspositionTable, TABLE_SIZE};
 use crate::move_sorter::MoveSorter;
+use crate::search_control::{SearchControl, Controllable};
 
-pub struct AlphaBetaWithLowerBoundTransposition {
+pub struct AlphaBetaWithLowerBoundTransposition<B: Bitboard = u64> {
     move_order: Vec<usize>,
     explored_positions: usize,
-    transposition_table: LowerBoundTranspositionTable
+    transposition_table: LowerBoundTranspositionTable<B>,
+    control: SearchControl
 }
 
-impl AlphaBetaWithLowerBoundTransposition {
+impl<B: Bitboard> AlphaBetaWithLowerBoundTransposition<B> {
     pub fn new(move_order: Vec<usize>) -> Self {
-        Self { move_order, explored_positions: 0, transposition_table: LowerBoundTranspositionTable::new(TABLE_SIZE) }
+        Self { move_order, explored_positions: 0, transposition_table: LowerBoundTranspositionTable::new(TABLE_SIZE), control: SearchControl::default() }
     }
 
-    fn solve_range(&mut self, position: &(impl lib_game_board::Position + Clone), mut alpha: i32, mut beta: i32) -> i32 {
+    fn solve_range(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone), mut alpha: i32, mut beta: i32) -> i32 {
         self.explored_positions += 1;
+        if self.control.is_cancelled() {
+            return 0;
+        }
 
         // Anticipate loosing move
         let next = position.possible_non_loosing_moves();
-        if next == 0 {
+        if next == B::ZERO {
             // if no possible non losing move, opponent wins next move
             return -((position.width()*position.height() - position.nb_moves()) as i32)/2;
         }
@@ -44,11 +50,11 @@ impl AlphaBetaWithLowerBoundTransposition {
             } 
         }
 
-        let position_min_score = -((position.width()*position.height()) as i32)/2 + 3;
-        let position_max_score = (position.width()*position.height()+1) as i32/2 - 3;
+        let position_min_score = position.min_score();
+        let position_max_score = position.max_score();
 
         // Compare lower and upper bound to the transposition table content
-        if let Some(val) = self.transposition_table.get(position.key()) {
+        if let Some(val) = self.transposition_table.get(position.canonical_key()) {
             if val > (position_max_score - position_min_score + 1) as u16 { // we have a lower bound
                 min = val as i32 + 2*position_min_score - position_max_score - 2;
                 if alpha < min {
@@ -71,9 +77,9 @@ impl AlphaBetaWithLowerBoundTransposition {
         // Sort the moves by score
         let mut move_sorter = MoveSorter::new(position.width());
         for column in self.move_order.clone().iter().rev() {
-            let column_mask = ((1 << position.height()) - 1) << (column * (position.height() + 1));
+            let column_mask = B::column_mask(position.height(), *column);
             let move_bit = next & column_mask;
-            if move_bit != 0 {
+            if move_bit != B::ZERO {
                 move_sorter.add(move_bit, position.move_score(move_bit));
             }
         }
@@ -82,15 +88,18 @@ impl AlphaBetaWithLowerBoundTransposition {
         // For each move, apply the basic negamax principle
         loop {
             let next = move_sorter.get_next();
-            if next == 0 { break; }
+            if next == B::ZERO { break; }
 
             let mut position2 = position.clone();
             position2.play_move(next);
 
-            let score = - self.solve_range(&mut position2, -beta, -alpha);
+            let score = - self.solve_range(&position2, -beta, -alpha);
+            if self.control.is_cancelled() {
+                return 0;
+            }
             
             if score >= beta {
-                self.transposition_table.insert(position.key(), (score + position_max_score - 2*position_min_score + 2) as u16);
+                self.transposition_table.insert(position.canonical_key(), (score + position_max_score - 2*position_min_score + 2) as u16);
                 return score;
             }
             if score > alpha {
@@ -98,15 +107,16 @@ impl AlphaBetaWithLowerBoundTransposition {
             }
         }
 
-        self.transposition_table.insert(position.key(), (alpha - position_min_score + 1) as u16);
+        self.transposition_table.insert(position.canonical_key(), (alpha - position_min_score + 1) as u16);
         alpha
     }
 }
 
-impl Solver for AlphaBetaWithLowerBoundTransposition {
-    fn solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> Solver<P> for AlphaBetaWithLowerBoundTransposition<B> {
+    fn solve(&mut self, position: &P) -> i32 {
         let mut min = - ((position.width()*position.height() - position.nb_moves()) as i32)/2;
         let mut max = ((position.width()*position.height() + 1 - position.nb_moves()) as i32)/2;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -115,11 +125,15 @@ impl Solver for AlphaBetaWithLowerBoundTransposition {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let r = self.solve_range(position, med, med+1);
+            if self.control.is_cancelled() {
+                break;
+            }
             if r <= med {
                 max = r;
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
         }
 
         min
@@ -135,10 +149,23 @@ impl Solver for AlphaBetaWithLowerBoundTransposition {
     }
 }
 
-impl WeakSolver for AlphaBetaWithLowerBoundTransposition {
-    fn weak_solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> Analyser<P> for AlphaBetaWithLowerBoundTransposition<B> {
+    fn is_cancelled(&mut self) -> bool {
+        self.control.is_cancelled()
+    }
+}
+
+impl<B: Bitboard> Controllable for AlphaBetaWithLowerBoundTransposition<B> {
+    fn control(&mut self) -> &mut SearchControl {
+        &mut self.control
+    }
+}
+
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> WeakSolver<P> for AlphaBetaWithLowerBoundTransposition<B> {
+    fn weak_solve(&mut self, position: &P) -> i32 {
         let mut min = -1;
         let mut max = 1;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -147,11 +174,15 @@ impl WeakSolver for AlphaBetaWithLowerBoundTransposition {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let r = self.solve_range(position, med, med+1);
+            if self.control.is_cancelled() {
+                break;
+            }
             if r <= med {
                 max = r;
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
         }
 
         min
@@ -169,18 +200,72 @@ impl WeakSolver for AlphaBetaWithLowerBoundTransposition {
 #[cfg(test)]
 mod alpha_beta_with_lower_bound_transposition_tests {
     use super::*;
+    use lib_game_board::Position;
     use lib_game_board::bitboard_position_with_ordering::BitboardPositionWithOrdering;
     use lib_game_board::sequence_position::SequencePosition;
+    use lib_game_board::{grid_position::GridPosition, stack_position::StackPosition};
 
     #[test]
     fn bitboard_correctness() {
         let mut solver = AlphaBetaWithLowerBoundTransposition::new((0..7).collect());
 
         assert_eq!(solver.solve(
-        &mut BitboardPositionWithOrdering::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
+        &BitboardPositionWithOrdering::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
+
+    #[test]
+    fn grid_correctness() {
+        let mut solver = AlphaBetaWithLowerBoundTransposition::new((0..7).collect());
+
+        assert_eq!(solver.solve(
+        &GridPosition::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
+                -1);
+    }
+
+    #[test]
+    fn stack_correctness() {
+        let mut solver = AlphaBetaWithLowerBoundTransposition::new((0..7).collect());
+
+        assert_eq!(solver.solve(
+        &StackPosition::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
+                -1);
+    }
+
+    #[test]
+    fn small_boards() {
+        let mut solver = AlphaBetaWithLowerBoundTransposition::new(vec![1, 2, 0, 3]);
+        assert_eq!(solver.solve(
+            &BitboardPositionWithOrdering::from_seq_with_dims(4, 4, &SequencePosition::parse("").unwrap()).unwrap()),
+            0);
+
+        let mut solver = AlphaBetaWithLowerBoundTransposition::new(vec![2, 1, 3, 0, 4]);
+        assert_eq!(solver.solve(
+            &BitboardPositionWithOrdering::from_seq_with_dims(5, 4, &SequencePosition::parse("").unwrap()).unwrap()),
+            0);
+
+        let mut solver = AlphaBetaWithLowerBoundTransposition::new(vec![2, 1, 3, 0, 4]);
+        assert_eq!(solver.solve(
+            &BitboardPositionWithOrdering::from_seq_with_dims(5, 4, &SequencePosition::parse("1").unwrap()).unwrap()),
+            1);
+
+        let mut solver = AlphaBetaWithLowerBoundTransposition::new(vec![2, 3, 1, 4, 0, 5]);
+        assert_eq!(solver.solve(
+            &BitboardPositionWithOrdering::from_seq_with_dims(6, 5, &SequencePosition::parse("3344256").unwrap()).unwrap()),
+            3);
+
+        let mut solver = AlphaBetaWithLowerBoundTransposition::new(vec![2, 3, 1, 4, 0, 5]);
+        assert_eq!(solver.solve(
+            &BitboardPositionWithOrdering::from_seq_with_dims(6, 5, &SequencePosition::parse("33442561").unwrap()).unwrap()),
+            -3);
+    }
 }
\ No newline at end of file
@@ -1,35 +1,63 @@
-use lib_game_board::{Solver, WeakSolver};
+use lib_game_board::{BitboardMovePosition, Solver, WeakSolver, Analyser};
+use lib_game_board::bitboard::Bitboard;
 use crate::lower_bound_transposition_table::{LowerBoundTranspositionTable, TABLE_SIZE};
 use crate::move_sorter::MoveSorter;
-use crate::opening_book::OpeningBook;
+use crate::opening_book::{OpeningBook, BookError};
+use crate::search_control::{SearchControl, Controllable};
 
-pub struct AlphaBetaWithOpeningBook {
+use std::sync::Arc;
+
+pub struct AlphaBetaWithOpeningBook<B: Bitboard = u64> {
     move_order: Vec<usize>,
     explored_positions: usize,
-    transposition_table: LowerBoundTranspositionTable,
-    opening_book: OpeningBook
+    transposition_table: LowerBoundTranspositionTable<B>,
+    opening_book: Arc<OpeningBook>,
+    control: SearchControl
 }
 
-impl AlphaBetaWithOpeningBook {
+impl<B: Bitboard> AlphaBetaWithOpeningBook<B> {
     pub fn new(width: usize, height: usize, move_order: Vec<usize>) -> Self {
         Self { 
             move_order, 
             explored_positions: 0, 
             transposition_table: LowerBoundTranspositionTable::new(TABLE_SIZE),
-            opening_book: OpeningBook::new(width, height)
+            opening_book: Arc::new(OpeningBook::new(width, height)),
+            control: SearchControl::default()
+        }
+    }
+
+    /// Loads the opening book stored in `book_path`. If it is invalid,
+    /// the solver keeps searching without a book.
+    pub fn load_opening_book(&mut self, book_path: &str) -> Result<(), BookError> {
+        let mut book = OpeningBook::new(self.opening_book.width(), self.opening_book.height());
+        book.load(book_path)?;
+        self.opening_book = Arc::new(book);
+        Ok(())
+    }
+
+    /// Uses an opening book already loaded, for instance by another solver.
+    pub fn set_opening_book(&mut self, book: Arc<OpeningBook>) -> Result<(), BookError> {
+        let expected = (self.opening_book.width(), self.opening_book.height());
+        if (book.width(), book.height()) != expected {
+            return Err(BookError::DimensionMismatch { expected, found: (book.width(), book.height()) });
         }
+        self.opening_book = book;
+        Ok(())
     }
 
-    pub fn load_opening_book(&mut self, book_path: &str) {
-        self.opening_book.load(book_path);
+    pub fn opening_book(&self) -> Arc<OpeningBook> {
+        Arc::clone(&self.opening_book)
     }
 
-    fn solve_range(&mut self, position: &(impl lib_game_board::Position + Clone), mut alpha: i32, mut beta: i32) -> i32 {
+    fn solve_range(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone), mut alpha: i32, mut beta: i32) -> i32 {
         self.explored_positions += 1;
+        if self.control.is_cancelled() {
+            return 0;
+        }
 
         // Anticipate loosing move
         let next = position.possible_non_loosing_moves();
-        if next == 0 {
+        if next == B::ZERO {
             // if no possible non losing move, opponent wins next move
             return -((position.width()*position.height() - position.nb_moves()) as i32)/2;
         }
@@ -55,11 +83,11 @@ impl AlphaBetaWithOpeningBook {
             } 
         }
 
-        let position_min_score = -((position.width()*position.height()) as i32)/2 + 3;
-        let position_max_score = (position.width()*position.height()+1) as i32/2 - 3;
+        let position_min_score = position.min_score();
+        let position_max_score = position.max_score();
 
         // Compare lower and upper bound to the transposition table content
-        if let Some(val) = self.transposition_table.get(position.key()) {
+        if let Some(val) = self.transposition_table.get(position.canonical_key()) {
             if val > (position_max_score - position_min_score + 1) as u16 { // we have a lower bound
                 min = val as i32 + 2*position_min_score - position_max_score - 2;
                 if alpha < min {
@@ -80,15 +108,15 @@ impl AlphaBetaWithOpeningBook {
         };
 
         if let Some(val) = self.opening_book.get(position) {
-            return (val + position_min_score as u16 - 1) as i32;
+            return val as i32 + position_min_score - 1;
         }
 
         // Sort the moves by score
         let mut move_sorter = MoveSorter::new(position.width());
         for column in self.move_order.clone().iter().rev() {
-            let column_mask = ((1 << position.height()) - 1) << (column * (position.height() + 1));
+            let column_mask = B::column_mask(position.height(), *column);
             let move_bit = next & column_mask;
-            if move_bit != 0 {
+            if move_bit != B::ZERO {
                 move_sorter.add(move_bit, position.move_score(move_bit));
             }
         }
@@ -97,15 +125,18 @@ impl AlphaBetaWithOpeningBook {
         // For each move, apply the basic negamax principle
         loop {
             let next = move_sorter.get_next();
-            if next == 0 { break; }
+            if next == B::ZERO { break; }
 
             let mut position2 = position.clone();
             position2.play_move(next);
 
-            let score = - self.solve_range(&mut position2, -beta, -alpha);
+            let score = - self.solve_range(&position2, -beta, -alpha);
+            if self.control.is_cancelled() {
+                return 0;
+            }
             
             if score >= beta {
-                self.transposition_table.insert(position.key(), (score + position_max_score - 2*position_min_score + 2) as u16);
+                self.transposition_table.insert(position.canonical_key(), (score + position_max_score - 2*position_min_score + 2) as u16);
                 return score;
             }
             if score > alpha {
@@ -113,15 +144,16 @@ impl AlphaBetaWithOpeningBook {
             }
         }
 
-        self.transposition_table.insert(position.key(), (alpha - position_min_score + 1) as u16);
+        self.transposition_table.insert(position.canonical_key(), (alpha - position_min_score + 1) as u16);
         alpha
     }
 }
 
-impl Solver for AlphaBetaWithOpeningBook {
-    fn solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> Solver<P> for AlphaBetaWithOpeningBook<B> {
+    fn solve(&mut self, position: &P) -> i32 {
         let mut min = - ((position.width()*position.height() - position.nb_moves()) as i32)/2;
         let mut max = ((position.width()*position.height() + 1 - position.nb_moves()) as i32)/2;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -130,11 +162,15 @@ impl Solver for AlphaBetaWithOpeningBook {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let r = self.solve_range(position, med, med+1);
+            if self.control.is_cancelled() {
+                break;
+            }
             if r <= med {
                 max = r;
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
         }
 
         min
@@ -150,10 +186,23 @@ impl Solver for AlphaBetaWithOpeningBook {
     }
 }
 
-impl WeakSolver for AlphaBetaWithOpeningBook {
-    fn weak_solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> Analyser<P> for AlphaBetaWithOpeningBook<B> {
+    fn is_cancelled(&mut self) -> bool {
+        self.control.is_cancelled()
+    }
+}
+
+impl<B: Bitboard> Controllable for AlphaBetaWithOpeningBook<B> {
+    fn control(&mut self) -> &mut SearchControl {
+        &mut self.control
+    }
+}
+
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> WeakSolver<P> for AlphaBetaWithOpeningBook<B> {
+    fn weak_solve(&mut self, position: &P) -> i32 {
         let mut min = -1;
         let mut max = 1;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -162,11 +211,15 @@ impl WeakSolver for AlphaBetaWithOpeningBook {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let r = self.solve_range(position, med, med+1);
+            if self.control.is_cancelled() {
+                break;
+            }
             if r <= med {
                 max = r;
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
         }
 
         min
@@ -186,43 +239,63 @@ mod alpha_beta_with_opening_book_tests {
     use super::*;
     use lib_game_board::bitboard_position_with_ordering::BitboardPositionWithOrdering;
     use lib_game_board::sequence_position::SequencePosition;
+    use crate::alpha_beta_solver::AlphaBetaSolver;
+    use lib_game_board::Position;
 
     #[test]
     fn bitboard_correctness() {
         let mut solver = AlphaBetaWithOpeningBook::new(7, 6, (0..7).collect());
 
         assert_eq!(solver.solve(
-        &mut BitboardPositionWithOrdering::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
+        &BitboardPositionWithOrdering::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
 
     #[test]
-    fn load_small_opening_book() {
+    fn cancellation() {
+        let token = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
         let mut solver = AlphaBetaWithOpeningBook::new(7, 6, (0..7).collect());
-        solver.load_opening_book("./opening-books/7x6_small.book");
+        solver.control().set_cancel_token(token.clone());
+
+        // Cancelled before any iteration: the lower bound is the worst score
+        let position = BitboardPositionWithOrdering::try_from(
+            &SequencePosition::parse("2252576253462244111563365343671351441").unwrap()
+        ).unwrap();
+        assert_eq!(solver.solve(&position), -2);
+        token.store(false, std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(solver.solve(&position), -1);
+    }
 
-        assert_eq!(solver.solve(
-        &mut BitboardPositionWithOrdering::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
-                -1);
+    #[test]
+    fn load_small_opening_book() {
+        let path = crate::opening_book_generator::fixture_book("alpha_beta_with_opening_book_small_opening_book", 4);
+        let mut solver = AlphaBetaWithOpeningBook::new(4, 4, vec![1, 2, 0, 3]);
+        solver.load_opening_book(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // Positions in the book and deeper ones are solved like without it
+        let mut reference = AlphaBetaSolver::new(vec![1, 2, 0, 3]);
+        for sequence in ["", "2", "23", "2332", "233214", "23321411"] {
+            let position = BitboardPositionWithOrdering::from_seq_with_dims(4, 4, &SequencePosition::parse(sequence).unwrap()).unwrap();
+            assert_eq!(solver.solve(&position), reference.solve(&position), "{}", sequence);
+        }
     }
 
     #[test]
-    #[ignore]
     fn load_large_opening_book() {
-        let mut solver = AlphaBetaWithOpeningBook::new(7, 6, (0..7).collect());
-        solver.load_opening_book("./opening-books/7x6.book");
-
-        assert_eq!(solver.solve(
-        &mut BitboardPositionWithOrdering::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
-                -1);
+        let path = crate::opening_book_generator::fixture_book("alpha_beta_with_opening_book_large_opening_book", 6);
+        let mut solver = AlphaBetaWithOpeningBook::new(4, 4, vec![1, 2, 0, 3]);
+        solver.load_opening_book(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // Positions in the book and deeper ones are solved like without it
+        let mut reference = AlphaBetaSolver::new(vec![1, 2, 0, 3]);
+        for sequence in ["", "2", "23", "2332", "233214", "23321411"] {
+            let position = BitboardPositionWithOrdering::from_seq_with_dims(4, 4, &SequencePosition::parse(sequence).unwrap()).unwrap();
+            assert_eq!(solver.solve(&position), reference.solve(&position), "{}", sequence);
+        }
     }
 }
\ No newline at end of file
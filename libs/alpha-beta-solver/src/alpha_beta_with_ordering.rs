@@ -1,23 +1,29 @@
-use lib_game_board::{Solver, WeakSolver};
+use lib_game_board::{BitboardMovePosition, Solver, WeakSolver, Analyser};
+use lib_game_board::bitboard::Bitboard;
 use crate::simple_transposition_table::{SimpleTranspositionTable, TABLE_SIZE};
 use crate::move_sorter::MoveSorter;
+use crate::search_control::{SearchControl, Controllable};
 
-pub struct AlphaBetaWithOrdering {
+pub struct AlphaBetaWithOrdering<B: Bitboard = u64> {
     move_order: Vec<usize>,
     explored_positions: usize,
-    transposition_table: SimpleTranspositionTable
+    transposition_table: SimpleTranspositionTable<B>,
+    control: SearchControl
 }
 
-impl AlphaBetaWithOrdering {
+impl<B: Bitboard> AlphaBetaWithOrdering<B> {
     pub fn new(move_order: Vec<usize>) -> Self {
-        Self { move_order, explored_positions: 0, transposition_table: SimpleTranspositionTable::new(TABLE_SIZE) }
+        Self { move_order, explored_positions: 0, transposition_table: SimpleTranspositionTable::new(TABLE_SIZE), control: SearchControl::default() }
     }
 
-    fn solve_range(&mut self, position: &(impl lib_game_board::Position + Clone), mut alpha: i32, mut beta: i32) -> i32 {
+    fn solve_range(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone), mut alpha: i32, mut beta: i32) -> i32 {
         self.explored_positions += 1;
+        if self.control.is_cancelled() {
+            return 0;
+        }
 
         let next = position.possible_non_loosing_moves();
-        if next == 0 {
+        if next == B::ZERO {
             // if no possible non losing move, opponent wins next move
             return -((position.width()*position.height()) as i32 - position.nb_moves() as i32)/2;
         }
@@ -35,7 +41,7 @@ impl AlphaBetaWithOrdering {
             } 
         }
 
-        let max_score = match self.transposition_table.get(position.key()) {
+        let max_score = match self.transposition_table.get(position.canonical_key()) {
             None => ((position.width()*position.height() - 1 - position.nb_moves()) / 2) as i32,
             Some(val) => val as i32 + position_min_score - 1
         };
@@ -51,9 +57,9 @@ impl AlphaBetaWithOrdering {
         let mut move_sorter = MoveSorter::new(position.width());
         // For each possible move
         for column in self.move_order.clone().iter().rev() {
-            let column_mask = ((1 << position.height()) - 1) << (column * (position.height() + 1));
+            let column_mask = B::column_mask(position.height(), *column);
             let move_bit = next & column_mask;
-            if move_bit != 0 {
+            if move_bit != B::ZERO {
                 move_sorter.add(move_bit, position.move_score(move_bit));
             }
         }
@@ -62,12 +68,15 @@ impl AlphaBetaWithOrdering {
 
         loop {
             let next = move_sorter.get_next();
-            if next == 0 { break; }
+            if next == B::ZERO { break; }
 
             let mut position2 = position.clone();
             position2.play_move(next);
 
-            let score = - self.solve_range(&mut position2, -beta, -alpha);
+            let score = - self.solve_range(&position2, -beta, -alpha);
+            if self.control.is_cancelled() {
+                return 0;
+            }
             
             if score >= beta {
                 return score;
@@ -77,15 +86,16 @@ impl AlphaBetaWithOrdering {
             }
         }
 
-        self.transposition_table.insert(position.key(), (alpha - position_min_score + 1) as u8);
+        self.transposition_table.insert(position.canonical_key(), (alpha - position_min_score + 1) as u8);
         alpha
     }
 }
 
-impl Solver for AlphaBetaWithOrdering {
-    fn solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> Solver<P> for AlphaBetaWithOrdering<B> {
+    fn solve(&mut self, position: &P) -> i32 {
         let mut min = - ((position.width()*position.height()) as i32 - position.nb_moves() as i32)/2;
         let mut max = ((position.width()*position.height() + 1) as i32 - position.nb_moves() as i32)/2;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -94,11 +104,15 @@ impl Solver for AlphaBetaWithOrdering {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let r = self.solve_range(position, med, med+1);
+            if self.control.is_cancelled() {
+                break;
+            }
             if r <= med {
                 max = r;
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
         }
 
         min
@@ -113,10 +127,23 @@ impl Solver for AlphaBetaWithOrdering {
     }
 }
 
-impl WeakSolver for AlphaBetaWithOrdering {
-    fn weak_solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> Analyser<P> for AlphaBetaWithOrdering<B> {
+    fn is_cancelled(&mut self) -> bool {
+        self.control.is_cancelled()
+    }
+}
+
+impl<B: Bitboard> Controllable for AlphaBetaWithOrdering<B> {
+    fn control(&mut self) -> &mut SearchControl {
+        &mut self.control
+    }
+}
+
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> WeakSolver<P> for AlphaBetaWithOrdering<B> {
+    fn weak_solve(&mut self, position: &P) -> i32 {
         let mut min = -1;
         let mut max = 1;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -125,11 +152,15 @@ impl WeakSolver for AlphaBetaWithOrdering {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let r = self.solve_range(position, med, med+1);
+            if self.control.is_cancelled() {
+                break;
+            }
             if r <= med {
                 max = r;
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
         }
 
         min
@@ -155,10 +186,10 @@ mod alpha_beta_with_ordering_tests {
         let mut alpha_beta_with_transposition = AlphaBetaWithOrdering::new((0..7).collect());
 
         assert_eq!(alpha_beta_with_transposition.solve(
-        &mut BitboardPositionWithOrdering::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
+        &BitboardPositionWithOrdering::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
 }
\ No newline at end of file
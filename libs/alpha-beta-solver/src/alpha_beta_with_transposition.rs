@@ -1,19 +1,26 @@
-use lib_game_board::{Solver, WeakSolver};
+use lib_game_board::{KeyedPosition, Solver, WeakSolver, Analyser};
+use lib_game_board::bitboard::Bitboard;
 use crate::simple_transposition_table::{SimpleTranspositionTable, TABLE_SIZE};
+use crate::search_control::{SearchControl, Controllable};
 
-pub struct AlphaBetaWithTransposition {
+pub struct AlphaBetaWithTransposition<B: Bitboard = u64> {
     move_order: Vec<usize>,
     explored_positions: usize,
-    transposition_table: SimpleTranspositionTable
+    transposition_table: SimpleTranspositionTable<B>,
+    control: SearchControl
 }
 
-impl AlphaBetaWithTransposition {
+impl<B: Bitboard> AlphaBetaWithTransposition<B> {
     pub fn new(move_order: Vec<usize>) -> Self {
-        Self { move_order, explored_positions: 0, transposition_table: SimpleTranspositionTable::new(TABLE_SIZE) }
+        Self { move_order, explored_positions: 0, transposition_table: SimpleTranspositionTable::new(TABLE_SIZE), control: SearchControl::default() }
     }
 
-    fn solve_range(&mut self, position: &(impl lib_game_board::Position + Clone), mut alpha: i32, mut beta: i32) -> i32 {
+    fn solve_range(&mut self, position: &(impl KeyedPosition<Bitboard = B> + Clone), mut alpha: i32, mut beta: i32) -> i32 {
         self.explored_positions += 1;
+        if self.control.is_cancelled() {
+            return 0;
+        }
+
         // Draw
         if position.nb_moves() == position.width() * position.height() {
             return 0;
@@ -26,8 +33,8 @@ impl AlphaBetaWithTransposition {
             }
         }
 
-        let position_min_score = - ((position.width()*position.height()) as i32) /2 + 3;
-        let max_score = match self.transposition_table.get(position.key()) {
+        let position_min_score = position.min_score();
+        let max_score = match self.transposition_table.get(position.canonical_key()) {
             None => ((position.width()*position.height() - 1 - position.nb_moves()) / 2) as i32,
             Some(val) => val as i32 + position_min_score // val + min_score
         };
@@ -46,7 +53,10 @@ impl AlphaBetaWithTransposition {
                 let mut position2 = position.clone();
                 position2.play(*column);
 
-                let score = - self.solve_range(&mut position2, -beta, -alpha);
+                let score = - self.solve_range(&position2, -beta, -alpha);
+                if self.control.is_cancelled() {
+                    return 0;
+                }
                 
                 if score >= beta {
                     return score;
@@ -57,16 +67,26 @@ impl AlphaBetaWithTransposition {
             }
         }
 
-        self.transposition_table.insert(position.key(), (alpha - position_min_score + 1) as u8);
+        self.transposition_table.insert(position.canonical_key(), (alpha - position_min_score + 1) as u8);
         alpha
     }
+
+    /// Searches the whole window at once, so that the progress is only reported with the score.
+    fn search(&mut self, position: &(impl KeyedPosition<Bitboard = B> + Clone), alpha: i32, beta: i32) -> i32 {
+        self.control.start();
+        let score = self.solve_range(position, alpha, beta);
+        if !self.control.is_cancelled() {
+            self.control.report(score, score, self.explored_positions);
+        }
+        score
+    }
 }
 
-impl Solver for AlphaBetaWithTransposition {
+impl<B: Bitboard, P: KeyedPosition<Bitboard = B> + Clone> Solver<P> for AlphaBetaWithTransposition<B> {
     /// Uses negamax to solve the position.
-    fn solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+    fn solve(&mut self, position: &P) -> i32 {
         let best_score = (position.width() * position.height()) as i32;
-        self.solve_range(position, -best_score, best_score)
+        self.search(position, -best_score, best_score)
     }
 
     fn explored_positions(&self) -> usize {
@@ -78,9 +98,21 @@ impl Solver for AlphaBetaWithTransposition {
     }
 }
 
-impl WeakSolver for AlphaBetaWithTransposition {
-    fn weak_solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
-        self.solve_range(position, -1, 1)
+impl<B: Bitboard, P: KeyedPosition<Bitboard = B> + Clone> Analyser<P> for AlphaBetaWithTransposition<B> {
+    fn is_cancelled(&mut self) -> bool {
+        self.control.is_cancelled()
+    }
+}
+
+impl<B: Bitboard> Controllable for AlphaBetaWithTransposition<B> {
+    fn control(&mut self) -> &mut SearchControl {
+        &mut self.control
+    }
+}
+
+impl<B: Bitboard, P: KeyedPosition<Bitboard = B> + Clone> WeakSolver<P> for AlphaBetaWithTransposition<B> {
+    fn weak_solve(&mut self, position: &P) -> i32 {
+        self.search(position, -1, 1)
     }
 
     fn explored_positions(&self) -> usize {
@@ -95,18 +127,47 @@ impl WeakSolver for AlphaBetaWithTransposition {
 #[cfg(test)]
 mod alpha_beta_with_transposition_tests {
     use super::*;
+    use lib_game_board::Position;
     use lib_game_board::bitboard_position::BitboardPosition;
     use lib_game_board::sequence_position::SequencePosition;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
 
     #[test]
     fn bitboard_correctness() {
         let mut alpha_beta_with_transposition = AlphaBetaWithTransposition::new((0..7).collect());
 
         assert_eq!(alpha_beta_with_transposition.solve(
-        &mut BitboardPosition::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
+        &BitboardPosition::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
+
+    #[test]
+    fn small_boards() {
+        let mut solver = AlphaBetaWithTransposition::new(vec![1, 2, 0, 3]);
+        assert_eq!(solver.solve(
+            &BitboardPosition::from_seq_with_dims(4, 4, &SequencePosition::parse("").unwrap()).unwrap()),
+            0);
+
+        let mut solver = AlphaBetaWithTransposition::new(vec![2, 1, 3, 0, 4]);
+        assert_eq!(solver.solve(
+            &BitboardPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("1").unwrap()).unwrap()),
+            1);
+    }
+
+    #[test]
+    fn cancelled_search() {
+        let token = Arc::new(AtomicBool::new(true));
+        let mut solver = AlphaBetaWithTransposition::new((0..4).collect());
+        solver.control().set_cancel_token(Arc::clone(&token));
+        let position = BitboardPosition::from_seq_with_dims(4, 4, &SequencePosition::parse("1").unwrap()).unwrap();
+        assert_eq!(solver.try_solve(&position), None);
+
+        // The table holds no result of the cancelled search
+        token.store(false, Ordering::Relaxed);
+        assert_eq!(solver.try_solve(&position), Some(0));
+    }
 }
\ No newline at end of file
@@ -1,22 +1,28 @@
-use lib_game_board::{Solver, WeakSolver};
+use lib_game_board::{AnticipatingPosition, Solver, WeakSolver, Analyser};
+use lib_game_board::bitboard::Bitboard;
 use crate::simple_transposition_table::{SimpleTranspositionTable, TABLE_SIZE};
+use crate::search_control::{SearchControl, Controllable};
 
-pub struct AnticipatingAlphaBeta {
+pub struct AnticipatingAlphaBeta<B: Bitboard = u64> {
     move_order: Vec<usize>,
     explored_positions: usize,
-    transposition_table: SimpleTranspositionTable
+    transposition_table: SimpleTranspositionTable<B>,
+    control: SearchControl
 }
 
-impl AnticipatingAlphaBeta {
+impl<B: Bitboard> AnticipatingAlphaBeta<B> {
     pub fn new(move_order: Vec<usize>) -> Self {
-        Self { move_order, explored_positions: 0, transposition_table: SimpleTranspositionTable::new(TABLE_SIZE) }
+        Self { move_order, explored_positions: 0, transposition_table: SimpleTranspositionTable::new(TABLE_SIZE), control: SearchControl::default() }
     }
 
-    fn solve_range(&mut self, position: &(impl lib_game_board::Position + Clone), mut alpha: i32, mut beta: i32) -> i32 {
+    fn solve_range(&mut self, position: &(impl AnticipatingPosition<Bitboard = B> + Clone), mut alpha: i32, mut beta: i32) -> i32 {
         self.explored_positions += 1;
+        if self.control.is_cancelled() {
+            return 0;
+        }
 
         let next = position.possible_non_loosing_moves();
-        if next == 0 {
+        if next == B::ZERO {
             // if no possible non losing move, opponent wins next move
             return -((position.width()*position.height()) as i32 - position.nb_moves() as i32)/2;
         }
@@ -34,7 +40,7 @@ impl AnticipatingAlphaBeta {
             } 
         }
 
-        let max_score = match self.transposition_table.get(position.key()) {
+        let max_score = match self.transposition_table.get(position.canonical_key()) {
             None => ((position.width()*position.height() - 1 - position.nb_moves()) / 2) as i32,
             Some(val) => val as i32 + position_min_score - 1
         };
@@ -49,12 +55,15 @@ impl AnticipatingAlphaBeta {
 
         // For each possible move
         for column in self.move_order.clone().iter() {
-            let column_mask = ((1 << position.height()) - 1) << (column * (position.height() + 1));
-            if next & column_mask != 0 {
+            let column_mask = B::column_mask(position.height(), *column);
+            if next & column_mask != B::ZERO {
                 let mut position2 = position.clone();
                 position2.play(*column);
 
-                let score = - self.solve_range(&mut position2, -beta, -alpha);
+                let score = - self.solve_range(&position2, -beta, -alpha);
+                if self.control.is_cancelled() {
+                    return 0;
+                }
                 
                 if score >= beta {
                     return score;
@@ -65,15 +74,16 @@ impl AnticipatingAlphaBeta {
             }
         }
 
-        self.transposition_table.insert(position.key(), (alpha - position_min_score + 1) as u8);
+        self.transposition_table.insert(position.canonical_key(), (alpha - position_min_score + 1) as u8);
         alpha
     }
 }
 
-impl Solver for AnticipatingAlphaBeta {
-    fn solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+impl<B: Bitboard, P: AnticipatingPosition<Bitboard = B> + Clone> Solver<P> for AnticipatingAlphaBeta<B> {
+    fn solve(&mut self, position: &P) -> i32 {
         let mut min = - ((position.width()*position.height()) as i32 - position.nb_moves() as i32)/2;
         let mut max = ((position.width()*position.height() + 1) as i32 - position.nb_moves() as i32)/2;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -82,11 +92,15 @@ impl Solver for AnticipatingAlphaBeta {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let r = self.solve_range(position, med, med+1);
+            if self.control.is_cancelled() {
+                break;
+            }
             if r <= med {
                 max = r;
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
         }
 
         min
@@ -101,10 +115,23 @@ impl Solver for AnticipatingAlphaBeta {
     }
 }
 
-impl WeakSolver for AnticipatingAlphaBeta {
-    fn weak_solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+impl<B: Bitboard, P: AnticipatingPosition<Bitboard = B> + Clone> Analyser<P> for AnticipatingAlphaBeta<B> {
+    fn is_cancelled(&mut self) -> bool {
+        self.control.is_cancelled()
+    }
+}
+
+impl<B: Bitboard> Controllable for AnticipatingAlphaBeta<B> {
+    fn control(&mut self) -> &mut SearchControl {
+        &mut self.control
+    }
+}
+
+impl<B: Bitboard, P: AnticipatingPosition<Bitboard = B> + Clone> WeakSolver<P> for AnticipatingAlphaBeta<B> {
+    fn weak_solve(&mut self, position: &P) -> i32 {
         let mut min = -1;
         let mut max = 1;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -113,11 +140,15 @@ impl WeakSolver for AnticipatingAlphaBeta {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let r = self.solve_range(position, med, med+1);
+            if self.control.is_cancelled() {
+                break;
+            }
             if r <= med {
                 max = r;
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
         }
 
         min
@@ -135,6 +166,7 @@ impl WeakSolver for AnticipatingAlphaBeta {
 #[cfg(test)]
 mod anticipating_alpha_beta_tests {
     use super::*;
+    use lib_game_board::Position;
     use lib_game_board::anticipating_bitboard_position::AnticipatingBitboardPosition;
     use lib_game_board::sequence_position::SequencePosition;
 
@@ -143,10 +175,51 @@ mod anticipating_alpha_beta_tests {
         let mut alpha_beta_with_transposition = AnticipatingAlphaBeta::new((0..7).collect());
 
         assert_eq!(alpha_beta_with_transposition.solve(
-        &mut AnticipatingBitboardPosition::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
+        &AnticipatingBitboardPosition::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
+
+    #[test]
+    fn analyse_immediate_win() {
+        let mut solver = AnticipatingAlphaBeta::new((0..7).collect());
+        let analysis = solver.analyse(
+            &AnticipatingBitboardPosition::try_from(&SequencePosition::parse("121212").unwrap()).unwrap());
+
+        assert_eq!(analysis.score, 18);
+        assert_eq!(analysis.best_move, Some(0));
+        assert_eq!(analysis.principal_variation, vec![0]);
+    }
+
+    #[test]
+    fn analyse_forced_defence() {
+        // Yellow must block the first column, then Red wins on the second one.
+        let mut solver = AnticipatingAlphaBeta::new(vec![1, 2, 0, 3]);
+        let analysis = solver.analyse(
+            &AnticipatingBitboardPosition::from_seq_with_dims(4, 4, &SequencePosition::parse("12121").unwrap()).unwrap());
+
+        assert_eq!(analysis.best_move, Some(0));
+        assert_eq!(analysis.score, -solver.solve(
+            &AnticipatingBitboardPosition::from_seq_with_dims(4, 4, &SequencePosition::parse("121211").unwrap()).unwrap()));
+    }
+
+    #[test]
+    fn small_boards() {
+        let mut solver = AnticipatingAlphaBeta::new(vec![1, 2, 0, 3]);
+        assert_eq!(solver.solve(
+            &AnticipatingBitboardPosition::from_seq_with_dims(4, 4, &SequencePosition::parse("").unwrap()).unwrap()),
+            0);
+
+        let mut solver = AnticipatingAlphaBeta::new(vec![2, 1, 3, 0, 4]);
+        assert_eq!(solver.solve(
+            &AnticipatingBitboardPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("1").unwrap()).unwrap()),
+            1);
+
+        let mut solver = AnticipatingAlphaBeta::new(vec![2, 3, 1, 4, 0, 5]);
+        assert_eq!(solver.solve(
+            &AnticipatingBitboardPosition::from_seq_with_dims(6, 5, &SequencePosition::parse("3344256").unwrap()).unwrap()),
+            3);
+    }
 }
\ No newline at end of file
@@ -1,38 +1,89 @@
+use lib_game_board::{KeyedPosition, BitboardMovePosition, Solver, WeakSolver, Analyser};
+use lib_game_board::bitboard::Bitboard;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use crate::lower_bound_transposition_table::{LowerBoundTranspositionTable, TABLE_SIZE};
 use crate::move_sorter::MoveSorter;
-use crate::opening_book::OpeningBook;
+use crate::opening_book::{OpeningBook, BookError};
+use crate::search_limit::{SearchLimit, LimitedSearch, Budget};
+use crate::search_control::{SearchControl, Controllable};
 
-pub struct FinalAlphaBeta {
+/// Value of a position won in the heuristic search, above any heuristic evaluation.
+/// The score of the win is added to it.
+const HEURISTIC_WIN: i32 = 1000;
+
+pub struct FinalAlphaBeta<B: Bitboard = u64> {
     move_order: Vec<usize>,
     explored_positions: usize,
-    transposition_table: LowerBoundTranspositionTable,
-    move_table: HashMap<u32, Option<u8>>,
-    opening_book: OpeningBook
+    transposition_table: LowerBoundTranspositionTable<B>,
+    opening_book: Arc<OpeningBook>,
+    /// Set during a limited search, which is aborted once the budget is spent.
+    budget: Option<Budget>,
+    aborted: bool,
+    control: SearchControl
 }
 
-impl FinalAlphaBeta {
+impl<B: Bitboard> FinalAlphaBeta<B> {
     pub fn new(width: usize, height: usize, move_order: Vec<usize>) -> Self {
+        assert!(Self::supports(width, height), "A {}x{} board is not supported by a {}bits solver.", width, height, B::BITS);
         Self { 
             move_order, 
             explored_positions: 0, 
             transposition_table: LowerBoundTranspositionTable::new(TABLE_SIZE),
-            move_table: HashMap::with_capacity(TABLE_SIZE),
-            opening_book: OpeningBook::new(width, height)
+            opening_book: Arc::new(OpeningBook::new(width, height)),
+            budget: None,
+            aborted: false,
+            control: SearchControl::default()
+        }
+    }
+
+    /// Whether `width`x`height` boards can be solved: they need at least two cells,
+    /// and their keys must be told apart by the transposition table.
+    pub fn supports(width: usize, height: usize) -> bool {
+        width * height >= 2 && width * (height + 1) <= LowerBoundTranspositionTable::<B>::exact_key_bits(TABLE_SIZE)
+    }
+
+    /// Loads the opening book stored in `book_path`. If it is invalid,
+    /// the solver keeps searching without a book.
+    pub fn load_opening_book(&mut self, book_path: &str) -> Result<(), BookError> {
+        let mut book = OpeningBook::new(self.opening_book.width(), self.opening_book.height());
+        book.load(book_path)?;
+        self.opening_book = Arc::new(book);
+        Ok(())
+    }
+
+    /// Uses an opening book already loaded, for instance by another solver.
+    pub fn set_opening_book(&mut self, book: Arc<OpeningBook>) -> Result<(), BookError> {
+        let expected = (self.opening_book.width(), self.opening_book.height());
+        if (book.width(), book.height()) != expected {
+            return Err(BookError::DimensionMismatch { expected, found: (book.width(), book.height()) });
         }
+        self.opening_book = book;
+        Ok(())
     }
 
-    pub fn load_opening_book(&mut self, book_path: &str) {
-        self.opening_book.load(book_path);
+    pub fn opening_book(&self) -> Arc<OpeningBook> {
+        Arc::clone(&self.opening_book)
     }
 
-    fn solve_range(&mut self, position: &(impl lib_game_board::Position + Clone), mut alpha: i32, mut beta: i32) -> (i32, Option<u8>) {
+    /// Whether the current search is cancelled or its budget spent. Once it is, every
+    /// result is meaningless, and must be neither stored nor used.
+    fn is_aborted(&mut self) -> bool {
+        let exhausted = self.budget.is_some_and(|budget| budget.is_exhausted(self.explored_positions));
+        self.aborted = self.aborted || exhausted || self.control.is_cancelled();
+        self.aborted
+    }
+
+    fn solve_range(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone), mut alpha: i32, mut beta: i32) -> (i32, Option<u8>) {
         self.explored_positions += 1;
+        if self.is_aborted() {
+            return (0, None);
+        }
 
         // Anticipate loosing move
         let next = position.possible_non_loosing_moves();
-        if next == 0 {
+        if next == B::ZERO {
             // if no possible non losing move, opponent wins next move
             return (
                 -((position.width()*position.height() - position.nb_moves()) as i32)/2,
@@ -61,17 +112,17 @@ impl FinalAlphaBeta {
             } 
         }
 
-        let position_min_score = -((position.width()*position.height()) as i32)/2 + 3;
-        let position_max_score = (position.width()*position.height()+1) as i32/2 - 3;
+        let position_min_score = position.min_score();
+        let position_max_score = position.max_score();
 
         // Compare lower and upper bound to the transposition table content
-        if let Some(val) = self.transposition_table.get(position.key()) {
+        if let Some(val) = self.transposition_table.get(position.canonical_key()) {
             if val > (position_max_score - position_min_score + 1) as u16 { // we have a lower bound
                 min = val as i32 + 2*position_min_score - position_max_score - 2;
                 if alpha < min {
                     alpha = min;
                     if alpha >= beta {
-                        return (alpha, *self.move_table.get(&(position.key() as u32)).unwrap());
+                        return (alpha, self.stored_move(position));
                     }
                 }
             } else { // we have an upper bound
@@ -79,14 +130,15 @@ impl FinalAlphaBeta {
                 if beta > max {
                     beta = max;
                     if alpha >= beta { 
-                        return (beta, *self.move_table.get(&(position.key() as u32)).unwrap());
+                        return (beta, self.stored_move(position));
                     }  
                 }
             }
         };
 
         if let Some(val) = self.opening_book.get(position) {
-            return (val as i32 + position_min_score - 1, None); // TODO: retrieve move from opening book
+            let best_move = self.opening_book.best_move(position).map(|column| column as u8);
+            return (val as i32 + position_min_score - 1, best_move);
         }
 
         // Hash Map to retrieve the column from a given move bitboard
@@ -94,9 +146,9 @@ impl FinalAlphaBeta {
         // Sort the moves by score
         let mut move_sorter = MoveSorter::new(position.width());
         for column in self.move_order.clone().iter().rev() {
-            let column_mask = ((1 << position.height()) - 1) << (column * (position.height() + 1));
+            let column_mask = B::column_mask(position.height(), *column);
             let move_bit = next & column_mask;
-            if move_bit != 0 {
+            if move_bit != B::ZERO {
                 move_sorter.add(move_bit, position.move_score(move_bit));
                 move_to_column_map.insert(move_bit, *column as u8);
             }
@@ -107,17 +159,19 @@ impl FinalAlphaBeta {
         let mut best_move: Option<u8> = None;
         loop {
             let next = move_sorter.get_next();
-            if next == 0 { break; }
+            if next == B::ZERO { break; }
 
             let mut position2 = position.clone();
             position2.play_move(next);
 
-            let (score, _) = self.solve_range(&mut position2, -beta, -alpha);
+            let (score, _) = self.solve_range(&position2, -beta, -alpha);
+            if self.aborted {
+                return (0, None);
+            }
             let score = -score;
             
             if score >= beta {
-                self.transposition_table.insert(position.key(), (score + position_max_score - 2*position_min_score + 2) as u16);
-                self.move_table.insert(position.key() as u32, Some(*move_to_column_map.get(&next).unwrap()));
+                self.store(position, (score + position_max_score - 2*position_min_score + 2) as u16, Some(*move_to_column_map.get(&next).unwrap()));
                 return (score, Some(*move_to_column_map.get(&next).unwrap()));
             }
             if score > alpha {
@@ -126,23 +180,38 @@ impl FinalAlphaBeta {
             }
         }
 
-        self.transposition_table.insert(position.key(), (alpha - position_min_score + 1) as u16);
-        self.move_table.insert(position.key() as u32, best_move);
+        // A null window search failing low proves no move: the one of a previous search is kept
+        let stored_move = best_move.or_else(|| self.stored_move(position));
+        self.store(position, (alpha - position_min_score + 1) as u16, stored_move);
         (alpha, best_move)
     }
 
-    pub fn solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> (i32, usize) {
-        if position.can_win_next() {
-            for column in 0..position.width() {
-                if position.is_winning_move(column) {
-                    return (((position.width()*position.height() + 1 - position.nb_moves()) as i32)/2, column);
-                }
-            }
-        }
+    /// Moves are stored in the orientation of the canonical key, so that mirrored positions share them.
+    fn store(&mut self, position: &impl KeyedPosition<Bitboard = B>, value: u16, best_move: Option<u8>) {
+        let best_move = best_move.map(|column| position.canonical_column(column as usize) as u8);
+        self.transposition_table.insert_with_move(position.canonical_key(), value, best_move);
+    }
+
+    /// Move stored for `position` by a previous search, in the orientation of `position`.
+    fn stored_move(&self, position: &impl KeyedPosition<Bitboard = B>) -> Option<u8> {
+        self.transposition_table
+            .get_move(position.canonical_key())
+            .map(|column| position.canonical_column(column as usize) as u8)
+    }
+
+    /// Null window search of the score between `min` and `max`,
+    /// also returning the best move found by the last search, if any.
+    fn search(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone), min: i32, max: i32) -> (i32, Option<usize>) {
+        let (min, _, best_move) = self.search_bounds(position, min, max);
+        (min, best_move)
+    }
 
-        let mut min = - ((position.width()*position.height() - position.nb_moves()) as i32)/2;
-        let mut max = ((position.width()*position.height() + 1 - position.nb_moves()) as i32)/2;
+    /// Narrows the bounds `min` and `max` of the score by null window searches, until
+    /// they meet or the search is aborted. The best move found, if any, scores at least `min`.
+    fn search_bounds(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone), mut min: i32, mut max: i32) -> (i32, i32, Option<usize>) {
         let mut best_move = None;
+        self.aborted = false;
+        self.control.start();
 
         while min < max {
             let mut med = min + (max - min)/2;
@@ -151,6 +220,9 @@ impl FinalAlphaBeta {
             else if med >= 0 && max/2 > med { med = max/2; }
 
             let (r, column_played) = self.solve_range(position, med, med+1);
+            if self.aborted {
+                break;
+            }
             best_move = match column_played {
                 None => best_move,
                 Some(val) => Some(val as usize)
@@ -160,100 +232,728 @@ impl FinalAlphaBeta {
             } else {
                 min = r;
             }
+            self.control.report(min, max, self.explored_positions);
+        }
+
+        (min, max, best_move)
+    }
+
+    /// Depth-limited negamax, where the positions left at depth 0 are evaluated by
+    /// the number of alignments their best move would threaten.
+    /// Positions solved within the depth are worth `HEURISTIC_WIN` plus their score.
+    fn heuristic_range(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone), depth: usize, mut alpha: i32, beta: i32) -> (i32, Option<u8>) {
+        self.explored_positions += 1;
+        if self.is_aborted() {
+            return (0, None);
+        }
+
+        let next = position.possible_non_loosing_moves();
+        if next == B::ZERO {
+            return (-HEURISTIC_WIN - ((position.width()*position.height() - position.nb_moves()) as i32)/2, None);
+        }
+        if position.nb_moves() >= position.width() * position.height() - 2 {
+            return (0, None);
+        }
+        if depth == 0 {
+            let threats = (0..position.width())
+                .map(|column| next & B::column_mask(position.height(), column))
+                .filter(|&move_bit| move_bit != B::ZERO)
+                .map(|move_bit| position.move_score(move_bit))
+                .max()
+                .unwrap_or(0);
+            return (threats as i32, None);
+        }
+
+        let mut move_to_column_map = HashMap::with_capacity(position.width());
+        let mut move_sorter = MoveSorter::new(position.width());
+        for column in self.move_order.clone().iter().rev() {
+            let move_bit = next & B::column_mask(position.height(), *column);
+            if move_bit != B::ZERO {
+                move_sorter.add(move_bit, position.move_score(move_bit));
+                move_to_column_map.insert(move_bit, *column as u8);
+            }
         }
 
-        if let Some(column) = best_move {
-            return (min, column);
-        } else {
-            println!("[WARNING] `best_move` is `None`: a random move was instead replaced.");
+        let mut best = (i32::MIN, None);
+        loop {
+            let next = move_sorter.get_next();
+            if next == B::ZERO { break; }
+
+            let mut position2 = position.clone();
+            position2.play_move(next);
+            let (score, _) = self.heuristic_range(&position2, depth - 1, -beta, -alpha);
+            if self.aborted {
+                return (0, None);
+            }
+
+            let score = -score;
+            if score > best.0 {
+                best = (score, Some(*move_to_column_map.get(&next).unwrap()));
+            }
+            if score > alpha {
+                alpha = score;
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    /// Searches `position` until it is solved or `limit` is reached. Half of the budget
+    /// is given to the exact search, which narrows the bounds of the score. If it does
+    /// not complete, iterative deepening with a heuristic evaluation chooses the move.
+    pub fn solve_with_limit<P: BitboardMovePosition<Bitboard = B> + Clone>(&mut self, position: &P, limit: SearchLimit) -> LimitedSearch {
+        let nb_cells = position.width() * position.height();
+        if position.can_win_next() {
+            let score = ((nb_cells + 1 - position.nb_moves()) as i32)/2;
+            let column = (0..position.width()).find(|&column| position.can_play(column) && position.is_winning_move(column));
+            return LimitedSearch { best_move: column, min: score, max: score, depth: None, cancelled: false };
+        }
+
+        let budget = Budget::new(limit, self.explored_positions);
+        self.budget = Some(budget.with_limit(limit.halved()));
+        let min = -((nb_cells - position.nb_moves()) as i32)/2;
+        let max = ((nb_cells + 1 - position.nb_moves()) as i32)/2;
+        let (mut min, mut max, exact_move) = self.search_bounds(position, min, max);
+
+        let playable = (0..position.width()).find(|&column| position.can_play(column));
+        let mut result = LimitedSearch { best_move: exact_move, min, max, depth: None, cancelled: false };
+        if !self.aborted {
+            // Losing positions fail low: their move is found among the children, mostly in the table.
+            // The children are searched within the rest of the budget, whose end leaves the move unproven
+            self.budget = Some(budget);
+            if exact_move.is_none() {
+                for column in (0..position.width()).filter(|&column| position.can_play(column)) {
+                    let mut position2 = position.clone();
+                    position2.play(column);
+                    let score = -self.solve(&position2);
+                    if self.aborted {
+                        result.cancelled = self.control.is_cancelled();
+                        result.depth = Some(0);
+                        break;
+                    }
+                    if score == min {
+                        result.best_move = Some(column);
+                        break;
+                    }
+                }
+            }
+            self.budget = None;
+            self.aborted = false;
+            result.best_move = result.best_move.or(playable);
+            return result;
+        }
+
+        self.budget = Some(budget);
+        self.aborted = false;
+        result.best_move = exact_move.or(playable);
+        for depth in 1..=nb_cells - position.nb_moves() {
+            let (value, column) = self.heuristic_range(position, depth, -i32::MAX, i32::MAX);
+            if self.aborted {
+                break;
+            }
+
+            if value >= HEURISTIC_WIN {
+                min = min.max(value - HEURISTIC_WIN);
+            } else if value <= -HEURISTIC_WIN {
+                max = max.min(value + HEURISTIC_WIN);
+            }
+            // The moves proven to win are kept over the heuristic ones
+            if exact_move.is_none() || min <= 0 {
+                result.best_move = column.map(|column| column as usize).or(result.best_move);
+            }
+            result = LimitedSearch { min, max, depth: Some(depth), ..result };
+            if value.abs() >= HEURISTIC_WIN {
+                break;
+            }
+        }
+
+        // Running out of budget is expected, while a cancellation leaves the move unproven
+        result.cancelled = self.control.is_cancelled();
+        self.budget = None;
+        self.aborted = false;
+        result
+    }
+
+    /// Returns the exact score of every column of the position, `None` for the full ones.
+    /// All the columns share the transposition table, so each search benefits from the previous ones.
+    pub fn analyze_all_moves(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone)) -> Vec<Option<i32>> {
+        (0..position.width())
+            .map(|column| {
+                if !position.can_play(column) {
+                    None
+                } else if position.is_winning_move(column) {
+                    Some(((position.width()*position.height() + 1 - position.nb_moves()) as i32)/2)
+                } else {
+                    let mut position2 = position.clone();
+                    position2.play(column);
+                    Some(-self.solve(&position2))
+                }
+            })
+            .collect()
+    }
+
+    /// Searches every column of the position within `limit` each, `None` for the full ones.
+    /// The results are given from the point of view of the player to move, playing the column.
+    pub fn analyze_all_moves_with_limit<P: BitboardMovePosition<Bitboard = B> + Clone>(&mut self, position: &P, limit: SearchLimit) -> Vec<Option<LimitedSearch>> {
+        (0..position.width())
+            .map(|column| {
+                if !position.can_play(column) {
+                    None
+                } else if position.is_winning_move(column) {
+                    let score = ((position.width()*position.height() + 1 - position.nb_moves()) as i32)/2;
+                    Some(LimitedSearch { best_move: Some(column), min: score, max: score, depth: None, cancelled: false })
+                } else {
+                    let mut position2 = position.clone();
+                    position2.play(column);
+                    let result = self.solve_with_limit(&position2, limit);
+                    Some(LimitedSearch { best_move: Some(column), min: -result.max, max: -result.min, ..result })
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the sign of the score of the position, along with the column to play if one was found.
+    pub fn weak_best_move(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone)) -> (i32, Option<usize>) {
+        if position.can_win_next() {
             for column in 0..position.width() {
-                if position.can_play(column) {
-                    return (min, column);
+                if position.can_play(column) && position.is_winning_move(column) {
+                    return (1, Some(column));
                 }
             }
-            panic!("Impossible to play anywhere. The game should trigger a draw.")
         }
+
+        self.search(position, -1, 1)
+    }
+}
+
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> Solver<P> for FinalAlphaBeta<B> {
+    fn solve(&mut self, position: &P) -> i32 {
+        if position.can_win_next() {
+            return ((position.width()*position.height() + 1 - position.nb_moves()) as i32)/2;
+        }
+
+        let min = - ((position.width()*position.height() - position.nb_moves()) as i32)/2;
+        let max = ((position.width()*position.height() + 1 - position.nb_moves()) as i32)/2;
+        self.search(position, min, max).0
     }
 
-    pub fn explored_positions(&self) -> usize {
+    fn explored_positions(&self) -> usize {
         self.explored_positions
     }
 
-    pub fn reset_explored_positions(&mut self) {
+    fn reset_explored_positions(&mut self) {
         self.explored_positions = 0;
         self.transposition_table = LowerBoundTranspositionTable::new(TABLE_SIZE);
     }
+}
 
-    pub fn weak_solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> (i32, Option<usize>) {
-        let mut min = -1;
-        let mut max = 1;
-        let mut best_move = None;
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> Analyser<P> for FinalAlphaBeta<B> {
+    /// Move stored along with the position by the last search, or in the opening book.
+    fn hint(&self, position: &P) -> Option<usize> {
+        self.stored_move(position).map(|column| column as usize)
+            .or_else(|| self.opening_book.best_move(position))
+    }
 
-        while min < max {
-            let mut med = min + (max - min)/2;
-            
-            if med <= 0 && min/2 < med { med = min/2; }
-            else if med >= 0 && max/2 > med { med = max/2; }
+    fn is_cancelled(&mut self) -> bool {
+        self.control.is_cancelled()
+    }
 
-            let (r, column_played) = self.solve_range(position, med, med+1);
-            best_move = match column_played {
-                None => best_move,
-                Some(val) => Some(val as usize)
-            };
+    /// Walks the moves stored by the searches. Each of them is only checked by the score of the
+    /// position it leads to, which the next ply needs anyway: the children are only searched
+    /// when the stored move is missing or does not reach the score.
+    fn principal_variation(&mut self, position: &P, score: i32) -> Vec<usize> {
+        let mut principal_variation = Vec::new();
+        let mut position = position.clone();
+        let mut position_score = score;
 
-            if r <= med {
-                max = r;
-            } else {
-                min = r;
+        loop {
+            let stored_move = self.stored_move(&position)
+                .map(|column| column as usize)
+                .filter(|&column| position.can_play(column) && self.move_score(&position, column) == position_score);
+            if self.control.is_cancelled() {
+                break;
+            }
+            let Some(column) = stored_move.or_else(|| self.optimal_move(&position, position_score)) else {
+                break;
+            };
+            principal_variation.push(column);
+            if position.is_winning_move(column) {
+                break;
             }
+            position.play(column);
+            position_score = -position_score;
         }
+        principal_variation
+    }
+}
 
-        (min, best_move)
+impl<B: Bitboard> Controllable for FinalAlphaBeta<B> {
+    fn control(&mut self) -> &mut SearchControl {
+        &mut self.control
+    }
+}
+
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone> WeakSolver<P> for FinalAlphaBeta<B> {
+    fn weak_solve(&mut self, position: &P) -> i32 {
+        if position.can_win_next() {
+            return 1;
+        }
+
+        self.search(position, -1, 1).0
+    }
+
+    fn explored_positions(&self) -> usize {
+        self.explored_positions
+    }
+
+    fn reset_explored_positions(&mut self) {
+        self.explored_positions = 0;
+        self.transposition_table = LowerBoundTranspositionTable::new(TABLE_SIZE);
     }
 }
 
 #[cfg(test)]
 mod final_alpha_beta_tests {
     use super::*;
-    use lib_game_board::bitboard_position_with_ordering::BitboardPositionWithOrdering;
+    use lib_game_board::Position;
+    use lib_game_board::{center_first, grid_position::GridPosition};
+    use lib_game_board::bitboard_position_with_ordering::{BitboardPositionWithOrdering, WideBitboardPosition};
     use lib_game_board::sequence_position::SequencePosition;
+    use crate::alpha_beta_solver::AlphaBetaSolver;
+    use crate::search_limit::SearchLimit;
+    use std::time::Duration;
+    use std::sync::{Mutex, atomic::{AtomicBool, Ordering}};
+    use crate::search_control::Controllable;
+
+    #[test]
+    fn large_boards() {
+        // 8x7 fits in 64 bits, but its 56 bits keys are only told apart by the wide table.
+        // The sequences leave no immediate win
+        assert!(!FinalAlphaBeta::<u64>::supports(8, 7) && FinalAlphaBeta::<u128>::supports(8, 7));
+        let sequence = SequencePosition::parse("6242664135278533311187888232565327").unwrap();
+        let score = AlphaBetaSolver::new((0..8).collect()).solve(&GridPosition::from_seq_with_dims(8, 7, &sequence).unwrap());
+        let mut solver = FinalAlphaBeta::new(8, 7, center_first(8));
+        assert_eq!(solver.solve(&WideBitboardPosition::from_seq_with_dims(8, 7, &sequence).unwrap()), score);
+
+        // 9x7 needs 72 bits
+        let sequence = SequencePosition::parse("168972888788892426222261635119969541466433314").unwrap();
+        let score = AlphaBetaSolver::new((0..9).collect()).solve(&GridPosition::from_seq_with_dims(9, 7, &sequence).unwrap());
+        let mut solver = FinalAlphaBeta::new(9, 7, center_first(9));
+        assert_eq!(solver.solve(&WideBitboardPosition::from_seq_with_dims(9, 7, &sequence).unwrap()), score);
+    }
+
+    #[test]
+    #[should_panic]
+    fn unsupported_board() {
+        let _: FinalAlphaBeta = FinalAlphaBeta::new(8, 7, center_first(8));
+    }
+
+    #[test]
+    fn other_win_lengths() {
+        for (width, height, win_length, sequence) in [(4, 4, 3, ""), (5, 4, 3, "33"), (4, 3, 2, ""), (5, 3, 5, "")] {
+            let sequence = SequencePosition::parse(sequence).unwrap();
+            let grid = GridPosition::from_seq_with_win_length(width, height, win_length, &sequence).unwrap();
+            let score = AlphaBetaSolver::new((0..width).collect()).solve(&grid);
+
+            let position = BitboardPositionWithOrdering::from_seq_with_win_length(width, height, win_length, &sequence).unwrap();
+            let mut solver = FinalAlphaBeta::new(width, height, center_first(width));
+            assert_eq!(solver.score(&position), score, "{}x{} connect-{}", width, height, win_length);
+            assert!(position.min_score() <= score && score <= position.max_score());
+        }
+    }
 
     #[test]
     fn bitboard_correctness() {
         let mut solver = FinalAlphaBeta::new(7, 6, (0..7).collect());
 
         assert_eq!(solver.solve(
-        &mut BitboardPositionWithOrdering::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))).0,
+        &BitboardPositionWithOrdering::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
 
     #[test]
-    fn load_small_opening_book() {
+    fn best_move() {
+        let mut solver = FinalAlphaBeta::new(7, 6, (0..7).collect());
+        let position = BitboardPositionWithOrdering::try_from(
+            &SequencePosition::parse("2252576253462244111563365343671351441").unwrap()
+        ).unwrap();
+
+        let (score, column) = solver.best_move(&position);
+        let column = column.unwrap();
+        assert_eq!(score, -1);
+
+        let mut position2 = position.clone();
+        position2.play(column);
+        assert_eq!(-solver.solve(&position2), score);
+    }
+
+    #[test]
+    fn principal_variation() {
+        let mut solver = FinalAlphaBeta::new(7, 6, (0..7).collect());
+        let position = BitboardPositionWithOrdering::try_from(
+            &SequencePosition::parse("2252576253462244111563365343671351441").unwrap()
+        ).unwrap();
+
+        let analysis = solver.analyse(&position);
+        assert_eq!(analysis.score, -1);
+        assert_eq!(analysis.best_move, analysis.principal_variation.first().copied());
+
+        // The opponent wins with its last stone, the 41st of the board.
+        let mut position2 = position.clone();
+        let (last, moves) = analysis.principal_variation.split_last().unwrap();
+        for &column in moves {
+            assert!(position2.can_play(column) && !position2.is_winning_move(column));
+            position2.play(column);
+        }
+        assert_eq!(position2.nb_moves(), 40);
+        assert!(position2.is_winning_move(*last));
+
+        // No move reaches a wrong score
+        assert_eq!(solver.optimal_move(&position, 5), None);
+    }
+
+    #[test]
+    fn analyze_all_moves() {
+        let mut solver = FinalAlphaBeta::new(7, 6, (0..7).collect());
+        let position = BitboardPositionWithOrdering::try_from(
+            &SequencePosition::parse("2252576253462244111563365343671351441").unwrap()
+        ).unwrap();
+
+        let scores = solver.analyze_all_moves(&position);
+        let mut reference = FinalAlphaBeta::new(7, 6, (0..7).collect());
+        assert_eq!(scores.len(), 7);
+        for (column, score) in scores.iter().enumerate() {
+            assert_eq!(score.is_some(), position.can_play(column));
+            if let Some(score) = score {
+                let mut position2 = position.clone();
+                position2.play(column);
+                assert_eq!(*score, -reference.solve(&position2));
+            }
+        }
+        assert_eq!(scores.iter().flatten().max(), Some(&solver.solve(&position)));
+    }
+
+    #[test]
+    fn analyze_all_moves_with_limit() {
+        let mut solver = FinalAlphaBeta::new(7, 6, center_first(7));
+        let position = BitboardPositionWithOrdering::try_from(
+            &SequencePosition::parse("2252576253462244111563365343671351441").unwrap()
+        ).unwrap();
+
+        // Late in the game, every column is solved within the limit
+        let exact = FinalAlphaBeta::new(7, 6, center_first(7)).analyze_all_moves(&position);
+        let results = solver.analyze_all_moves_with_limit(&position, SearchLimit::Time(Duration::from_secs(10)));
+        for (column, (result, score)) in results.iter().zip(exact).enumerate() {
+            assert_eq!(result.map(|result| (result.is_exact(), result.min, result.best_move)), score.map(|score| (true, score, Some(column))));
+        }
+
+        // Early in the game, the bounds hold the exact scores
+        let position = BitboardPositionWithOrdering::try_from(&SequencePosition::parse("44").unwrap()).unwrap();
+        let results = solver.analyze_all_moves_with_limit(&position, SearchLimit::Nodes(1000));
+        assert!(results.iter().all(|result| result.is_some_and(|result| result.min <= result.estimate() && result.estimate() <= result.max)));
+        assert!(results[3].is_some_and(|result| result.min <= 1 && 1 <= result.max));
+    }
+
+    #[test]
+    fn immediate_win() {
+        let mut solver = FinalAlphaBeta::new(7, 6, (0..7).collect());
+        let position = BitboardPositionWithOrdering::try_from(
+            &SequencePosition::parse("121212").unwrap()
+        ).unwrap();
+
+        assert_eq!(solver.best_move(&position), (18, Some(0)));
+        assert_eq!(solver.weak_best_move(&position), (1, Some(0)));
+        assert_eq!(solver.solve(&position), 18);
+        assert_eq!(solver.weak_solve(&position), 1);
+    }
+
+    #[test]
+    fn small_boards() {
+        let mut solver = FinalAlphaBeta::new(5, 4, vec![2, 1, 3, 0, 4]);
+        let position = BitboardPositionWithOrdering::from_seq_with_dims(5, 4, &SequencePosition::parse("1").unwrap()).unwrap();
+        assert_eq!(solver.solve(&position), 1);
+        assert_eq!(solver.weak_solve(&position), 1);
+    }
+
+    #[test]
+    fn reset_clears_table() {
+        let mut solver = FinalAlphaBeta::new(5, 4, vec![2, 1, 3, 0, 4]);
+        let position = BitboardPositionWithOrdering::from_seq_with_dims(5, 4, &SequencePosition::parse("1").unwrap()).unwrap();
+
+        // Strong and weak resets start the next search from scratch alike
+        solver.solve(&position);
+        assert!(solver.stored_move(&position).is_some());
+        Solver::<BitboardPositionWithOrdering>::reset_explored_positions(&mut solver);
+        assert_eq!(solver.stored_move(&position), None);
+        solver.weak_solve(&position);
+        assert!(solver.stored_move(&position).is_some());
+        WeakSolver::<BitboardPositionWithOrdering>::reset_explored_positions(&mut solver);
+        assert_eq!(solver.stored_move(&position), None);
+        assert_eq!(WeakSolver::<BitboardPositionWithOrdering>::explored_positions(&solver), 0);
+    }
+
+    #[test]
+    fn missing_opening_book() {
         let mut solver = FinalAlphaBeta::new(7, 6, (0..7).collect());
-        solver.load_opening_book("./opening-books/7x6_small.book");
+        assert!(solver.load_opening_book("./opening-books/missing.book").is_err());
 
         assert_eq!(solver.solve(
-        &mut BitboardPositionWithOrdering::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))).0,
+        &BitboardPositionWithOrdering::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
 
     #[test]
-    #[ignore]
+    fn shared_opening_book() {
+        use crate::alpha_beta_with_opening_book::AlphaBetaWithOpeningBook;
+        use crate::opening_book_generator::OpeningBookGenerator;
+
+        let path = std::env::temp_dir().join(format!("connect_4_shared_opening_book_{}.book", std::process::id()));
+        let path = path.to_str().unwrap();
+        OpeningBookGenerator::new(4, 4, 2, vec![1, 2, 0, 3]).generate(path, |_, _| ()).unwrap();
+
+        let mut solver = FinalAlphaBeta::new(4, 4, vec![1, 2, 0, 3]);
+        solver.load_opening_book(path).unwrap();
+        let mut other_solver = AlphaBetaWithOpeningBook::new(4, 4, vec![1, 2, 0, 3]);
+        other_solver.set_opening_book(solver.opening_book()).unwrap();
+        assert_eq!(Arc::strong_count(&solver.opening_book()), 3);
+
+        let position = BitboardPositionWithOrdering::from_seq_with_dims(4, 4, &SequencePosition::parse("").unwrap()).unwrap();
+        assert_eq!(solver.solve(&position), 0);
+        assert_eq!(other_solver.solve(&position), 0);
+        // Found in the book: only the root is explored, once per null window search
+        let mut bookless_solver = FinalAlphaBeta::new(4, 4, vec![1, 2, 0, 3]);
+        assert_eq!(bookless_solver.solve(&position), 0);
+        let explored_positions = Solver::<BitboardPositionWithOrdering>::explored_positions(&solver);
+        assert!(explored_positions <= 2);
+        assert!(explored_positions < Solver::<BitboardPositionWithOrdering>::explored_positions(&bookless_solver));
+
+        let mut wrong_board: FinalAlphaBeta = FinalAlphaBeta::new(7, 6, (0..7).collect());
+        assert_eq!(
+            wrong_board.set_opening_book(solver.opening_book()),
+            Err(BookError::DimensionMismatch { expected: (7, 6), found: (4, 4) })
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn book_best_move() {
+        use crate::opening_book_generator::OpeningBookGenerator;
+
+        let path = std::env::temp_dir().join(format!("connect_4_book_best_move_{}.book", std::process::id()));
+        let path = path.to_str().unwrap();
+        OpeningBookGenerator::new(4, 4, 2, vec![1, 2, 0, 3]).generate(path, |_, _| ()).unwrap();
+
+        let mut solver = FinalAlphaBeta::new(4, 4, vec![1, 2, 0, 3]);
+        solver.load_opening_book(path).unwrap();
+        let position = BitboardPositionWithOrdering::from_seq_with_dims(4, 4, &SequencePosition::parse("").unwrap()).unwrap();
+        let book_move = solver.opening_book().best_move(&position);
+        assert!(book_move.is_some());
+
+        // The score comes from the book, along with its move
+        assert_eq!(solver.weak_best_move(&position), (0, book_move));
+        assert_eq!(solver.best_move(&position), (0, book_move));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn load_small_opening_book() {
+        let path = crate::opening_book_generator::fixture_book("final_alpha_beta_small_opening_book", 4);
+        let mut solver = FinalAlphaBeta::new(4, 4, vec![1, 2, 0, 3]);
+        solver.load_opening_book(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // Positions in the book and deeper ones are solved like without it
+        let mut reference = AlphaBetaSolver::new(vec![1, 2, 0, 3]);
+        for sequence in ["", "2", "23", "2332", "233214", "23321411"] {
+            let position = BitboardPositionWithOrdering::from_seq_with_dims(4, 4, &SequencePosition::parse(sequence).unwrap()).unwrap();
+            assert_eq!(solver.solve(&position), reference.solve(&position), "{}", sequence);
+        }
+    }
+
+    #[test]
     fn load_large_opening_book() {
+        let path = crate::opening_book_generator::fixture_book("final_alpha_beta_large_opening_book", 6);
+        let mut solver = FinalAlphaBeta::new(4, 4, vec![1, 2, 0, 3]);
+        solver.load_opening_book(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        // Positions in the book and deeper ones are solved like without it
+        let mut reference = AlphaBetaSolver::new(vec![1, 2, 0, 3]);
+        for sequence in ["", "2", "23", "2332", "233214", "23321411"] {
+            let position = BitboardPositionWithOrdering::from_seq_with_dims(4, 4, &SequencePosition::parse(sequence).unwrap()).unwrap();
+            assert_eq!(solver.solve(&position), reference.solve(&position), "{}", sequence);
+        }
+    }
+
+    #[test]
+    fn limited_search_completes() {
         let mut solver = FinalAlphaBeta::new(7, 6, (0..7).collect());
-        solver.load_opening_book("./opening-books/7x6.book");
+        let position = BitboardPositionWithOrdering::try_from(
+            &SequencePosition::parse("2252576253462244111563365343671351441").unwrap()
+        ).unwrap();
+
+        let result = solver.solve_with_limit(&position, SearchLimit::Time(Duration::from_secs(60)));
+        assert!(result.is_exact());
+        assert_eq!((result.min, result.depth, result.cancelled), (-1, None, false));
+        let mut position2 = position.clone();
+        position2.play(result.best_move.unwrap());
+        assert_eq!(-solver.solve(&position2), -1);
+
+        let winning = BitboardPositionWithOrdering::try_from(&SequencePosition::parse("121212").unwrap()).unwrap();
+        let result = solver.solve_with_limit(&winning, SearchLimit::Nodes(0));
+        assert_eq!((result.best_move, result.min, result.max), (Some(0), 18, 18));
+    }
 
-        assert_eq!(solver.solve(
-        &mut BitboardPositionWithOrdering::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))).0,
-                -1);
+    #[test]
+    fn limited_search_bounds() {
+        // The first player wins the empty 7x6 board with their 41st disc
+        let mut solver = FinalAlphaBeta::new(7, 6, center_first(7));
+        let position = BitboardPositionWithOrdering::new(7, 6);
+
+        let result = solver.solve_with_limit(&position, SearchLimit::Nodes(20_000));
+        assert!(result.min <= 1 && 1 <= result.max && !result.is_exact());
+        assert!(result.depth.unwrap() >= 1);
+        assert!(position.can_play(result.best_move.unwrap()));
+        assert!(Solver::<BitboardPositionWithOrdering>::explored_positions(&solver) <= 20_000);
+    }
+
+    #[test]
+    fn limited_search_of_losing_moves() {
+        // Losing positions search their children for the move, within the same budget
+        let position = BitboardPositionWithOrdering::try_from(&SequencePosition::parse("113231166715427").unwrap()).unwrap();
+        let score = FinalAlphaBeta::new(7, 6, center_first(7)).solve(&position);
+        assert_eq!(score, -2);
+        for nodes in [2000, 150_000, 190_000, 320_000, 1_000_000] {
+            let mut solver = FinalAlphaBeta::new(7, 6, center_first(7));
+            let result = solver.solve_with_limit(&position, SearchLimit::Nodes(nodes));
+            assert!(Solver::<BitboardPositionWithOrdering>::explored_positions(&solver) <= nodes, "{} nodes", nodes);
+            assert!(result.min <= score && score <= result.max);
+
+            let mut position2 = position.clone();
+            position2.play(result.best_move.unwrap());
+            if result.is_exact() {
+                assert_eq!(-FinalAlphaBeta::new(7, 6, center_first(7)).solve(&position2), score);
+            }
+        }
+    }
+
+    #[test]
+    fn limited_search_time() {
+        let mut solver = FinalAlphaBeta::new(7, 6, center_first(7));
+        let start = std::time::Instant::now();
+        let result = solver.solve_with_limit(&BitboardPositionWithOrdering::new(7, 6), SearchLimit::Time(Duration::from_millis(50)));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn aborted_search_keeps_table_valid() {
+        let mut solver = FinalAlphaBeta::new(5, 4, center_first(5));
+        let position = BitboardPositionWithOrdering::new(5, 4);
+        for nodes in [10, 100, 1000] {
+            let result = solver.solve_with_limit(&position, SearchLimit::Nodes(nodes));
+            assert!(result.min <= 0 && 0 <= result.max);
+        }
+        assert_eq!(solver.solve(&position), FinalAlphaBeta::new(5, 4, center_first(5)).solve(&position));
+    }
+
+    #[test]
+    fn heuristic_finds_forced_wins() {
+        // Red threatens both ends of the bottom row: the exact search is stopped at once
+        let mut solver = FinalAlphaBeta::new(7, 6, center_first(7));
+        let position = BitboardPositionWithOrdering::try_from(&SequencePosition::parse("3344").unwrap()).unwrap();
+        let result = solver.solve_with_limit(&position, SearchLimit::Nodes(200));
+        assert!(result.min > 0);
+        assert!([1, 4].contains(&result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn cancelled_search() {
+        let token = Arc::new(AtomicBool::new(true));
+        let mut solver = FinalAlphaBeta::new(7, 6, center_first(7));
+        solver.control().set_cancel_token(Arc::clone(&token));
+
+        // Nothing is proven: neither a score nor a move
+        let position = BitboardPositionWithOrdering::new(7, 6);
+        assert_eq!(solver.try_solve(&position), None);
+        assert!(solver.control().is_cancelled());
+        let result = solver.solve_with_limit(&position, SearchLimit::Nodes(1_000_000));
+        assert!(position.can_play(result.best_move.unwrap()));
+        assert_eq!((result.depth, result.cancelled), (None, true));
+        assert!(!result.is_exact());
+
+        // The table holds no result of the cancelled searches
+        token.store(false, Ordering::Relaxed);
+        let position = BitboardPositionWithOrdering::try_from(
+            &SequencePosition::parse("2252576253462244111563365343671351441").unwrap()
+        ).unwrap();
+        assert_eq!(solver.try_solve(&position), Some(-1));
+        assert!(!solver.control().is_cancelled());
+    }
+
+    #[test]
+    fn cancelled_from_another_thread() {
+        let token = Arc::new(AtomicBool::new(false));
+        let mut solver = FinalAlphaBeta::new(7, 6, center_first(7));
+        solver.control().set_cancel_token(Arc::clone(&token));
+
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            token.store(true, Ordering::Relaxed);
+        });
+        let start = std::time::Instant::now();
+        assert_eq!(solver.try_solve(&BitboardPositionWithOrdering::new(7, 6)), None);
+        canceller.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(solver.control().is_cancelled());
+    }
+
+    #[test]
+    fn cancelled_analysis() {
+        let token = Arc::new(AtomicBool::new(true));
+        let mut solver = FinalAlphaBeta::new(7, 6, center_first(7));
+        solver.control().set_cancel_token(Arc::clone(&token));
+
+        // No move is given from a cancelled search
+        let position = BitboardPositionWithOrdering::try_from(&SequencePosition::parse("2252576253462244111563365343671351441").unwrap()).unwrap();
+        assert_eq!(solver.best_move(&position).1, None);
+        let analysis = solver.analyse(&position);
+        assert_eq!((analysis.best_move, analysis.principal_variation.len()), (None, 0));
+        assert_eq!(solver.optimal_move(&position, -1), None);
+
+        token.store(false, Ordering::Relaxed);
+        assert!(solver.best_move(&position).1.is_some());
+    }
+
+    #[test]
+    fn progress() {
+        let windows = Arc::new(Mutex::new(Vec::new()));
+        let mut solver = FinalAlphaBeta::new(7, 6, (0..7).collect());
+        let windows2 = Arc::clone(&windows);
+        solver.control().set_progress_callback(move |progress| windows2.lock().unwrap().push(*progress));
+
+        let position = BitboardPositionWithOrdering::try_from(
+            &SequencePosition::parse("2252576253462244111563365343671351441").unwrap()
+        ).unwrap();
+        solver.solve(&position);
+
+        let windows = windows.lock().unwrap();
+        assert!(!windows.is_empty());
+        for pair in windows.windows(2) {
+            assert!(pair[0].alpha <= pair[1].alpha && pair[1].beta <= pair[0].beta);
+            assert!(pair[0].explored_positions <= pair[1].explored_positions && pair[0].elapsed <= pair[1].elapsed);
+        }
+        let last = windows.last().unwrap();
+        assert_eq!((last.alpha, last.beta), (-1, -1));
     }
 }
\ No newline at end of file
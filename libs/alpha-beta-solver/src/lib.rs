@@ -8,8 +8,15 @@ pub mod alpha_beta_with_optimised_transposition;
 pub mod alpha_beta_with_lower_bound_transposition;
 pub mod alpha_beta_with_opening_book;
 pub mod final_alpha_beta;
+pub mod parallel_alpha_beta;
+pub mod shared_transposition_table;
+pub mod search_limit;
+pub mod search_control;
+pub mod opening_book;
+pub mod opening_book_generator;
+pub mod variant_solver;
+pub mod ai_player;
 
 mod simple_transposition_table;
 mod optimised_transposition_table;
 mod lower_bound_transposition_table;
-mod opening_book;
\ No newline at end of file
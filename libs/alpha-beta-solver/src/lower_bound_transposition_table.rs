@@ -1,40 +1,69 @@
+use lib_game_board::bitboard::Bitboard;
+
 pub const TABLE_SIZE: usize = (1 << 23) + 9; // first prime greater than 2^23
 
-pub struct LowerBoundTranspositionTable {
+/// Only the partial keys are stored: with `TABLE_SIZE` slots, the table is exact
+/// for keys of up to 55 bits with `u64` bitboards, and up to 87 bits with `u128` ones.
+/// Each slot can also hold the best move of its position.
+pub struct LowerBoundTranspositionTable<B: Bitboard = u64> {
     size: usize,
-    keys: Vec<u32>,
-    values: Vec<Option<u16>>
+    keys: Vec<B::PartialKey>,
+    values: Vec<Option<u16>>,
+    moves: Vec<Option<u8>>
 }
 
-impl LowerBoundTranspositionTable {
+impl<B: Bitboard> LowerBoundTranspositionTable<B> {
     pub fn new(size: usize) -> Self {
         assert_eq!(size % 2, 1, "LowerBoundTranspositionTable size must be odd, but it is equal to {size}.");
 
         Self { 
             size, 
-            keys: vec![0; size], 
-            values: vec![None; size]
+            keys: vec![B::PartialKey::default(); size], 
+            values: vec![None; size],
+            moves: vec![None; size]
         }
     }
     
-    fn index(&self, key: u64) -> usize {
-        key as usize % self.size
+    /// Number of bits of the longest keys told apart by a table of `size` slots:
+    /// a key is known by its slot along with its partial key.
+    pub fn exact_key_bits(size: usize) -> usize {
+        size.ilog2() as usize + 8 * std::mem::size_of::<B::PartialKey>()
+    }
+
+    fn index(&self, key: B) -> usize {
+        key.modulo(self.size as u64) as usize
+    }
+
+    pub fn insert(&mut self, key: B, value: u16) {
+        self.insert_with_move(key, value, None);
     }
 
-    pub fn insert(&mut self, key: u64, value: u16) {
+    /// Stores `best_move` in the same slot as `value`, so that both are overridden together.
+    pub fn insert_with_move(&mut self, key: B, value: u16, best_move: Option<u8>) {
         let index = self.index(key);
-        self.keys[index] = key as u32; // possibly truncated
+        self.keys[index] = key.partial_key(); // possibly truncated
         self.values[index] = Some(value);
+        self.moves[index] = best_move;
     }
 
-    pub fn get(&self, key: u64) -> Option<u16> {
+    pub fn get(&self, key: B) -> Option<u16> {
         let index = self.index(key);
-        if self.keys[index] == key as u32 {
+        if self.keys[index] == key.partial_key() {
             self.values[index]
         } else {
             None
         }
     }
+
+    /// Move stored along with the value of `key`, if any.
+    pub fn get_move(&self, key: B) -> Option<u8> {
+        let index = self.index(key);
+        if self.keys[index] == key.partial_key() && self.values[index].is_some() {
+            self.moves[index]
+        } else {
+            None
+        }
+    }
 }
 
 #[cfg(test)]
@@ -43,7 +72,7 @@ mod optimised_transposition_table_tests {
 
     #[test]
     fn insert_get() {
-        let mut table = LowerBoundTranspositionTable::new(11);
+        let mut table: LowerBoundTranspositionTable = LowerBoundTranspositionTable::new(11);
 
         table.insert(42, 21);
         assert_eq!(table.get(42), Some(21));
@@ -51,7 +80,7 @@ mod optimised_transposition_table_tests {
 
     #[test]
     fn insert_get_index() {
-        let mut table = LowerBoundTranspositionTable::new(11);
+        let mut table: LowerBoundTranspositionTable = LowerBoundTranspositionTable::new(11);
 
         table.insert(10, 21);
         assert_eq!(table.get(0), None);
@@ -59,11 +88,30 @@ mod optimised_transposition_table_tests {
 
     #[test]
     fn index_override() {
-        let mut table = LowerBoundTranspositionTable::new(11);
+        let mut table: LowerBoundTranspositionTable = LowerBoundTranspositionTable::new(11);
 
         table.insert(10, 21);
         table.insert(21, 22);
         assert_eq!(table.get(21), Some(22));
         assert_eq!(table.get(10), None);
     }
+
+    #[test]
+    fn moves() {
+        let mut table: LowerBoundTranspositionTable = LowerBoundTranspositionTable::new(11);
+
+        table.insert_with_move(10, 21, Some(3));
+        assert_eq!(table.get_move(10), Some(3));
+        assert_eq!(table.get_move(0), None);
+        // The move of a key does not outlive its value
+        table.insert(21, 22);
+        assert_eq!(table.get_move(21), None);
+        assert_eq!(table.get_move(10), None);
+    }
+
+    #[test]
+    fn exact_key_bits() {
+        assert_eq!(LowerBoundTranspositionTable::<u64>::exact_key_bits(TABLE_SIZE), 55);
+        assert_eq!(LowerBoundTranspositionTable::<u128>::exact_key_bits(TABLE_SIZE), 87);
+    }
 }
\ No newline at end of file
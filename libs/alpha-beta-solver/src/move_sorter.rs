@@ -1,17 +1,19 @@
-pub struct MoveSorter {
+use lib_game_board::bitboard::Bitboard;
+
+pub struct MoveSorter<B: Bitboard = u64> {
     size: usize,
-    entries: Vec<(u64, usize)>
+    entries: Vec<(B, usize)>
 }
 
-impl MoveSorter {
+impl<B: Bitboard> MoveSorter<B> {
     pub fn new(width: usize) -> Self {
         Self {
             size: 0,
-            entries: vec![(0, 0); width]
+            entries: vec![(B::ZERO, 0); width]
         }
     }
 
-    pub fn add(&mut self, move_bit: u64, score: usize) {
+    pub fn add(&mut self, move_bit: B, score: usize) {
         let mut index = self.size;
         
         while index != 0 && self.entries[index-1].1 > score {
@@ -24,12 +26,12 @@ impl MoveSorter {
         self.size += 1;
     }
 
-    pub fn get_next(&mut self) -> u64 {
+    pub fn get_next(&mut self) -> B {
         if self.size > 0 {
             self.size -= 1;
             self.entries[self.size].0
         } else {
-            0
+            B::ZERO
         }
     }
 }
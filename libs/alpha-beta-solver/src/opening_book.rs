@@ -1,15 +1,121 @@
-use lib_game_board::Position;
-use crate::lower_bound_transposition_table::{LowerBoundTranspositionTable, TABLE_SIZE};
+use lib_game_board::{KeyedPosition, DEFAULT_WIN_LENGTH, bitboard::Bitboard};
 
-use std::io::Read;
-use std::io::BufReader;
-use std::fs::File;
+use memmap2::Mmap;
 
+use std::fmt;
+use std::io;
+use std::fs::{self, File};
+use std::ops::Deref;
+
+/// Size in bytes of the keys written by `save`.
+pub const KEY_SIZE: usize = 4;
+/// Starts the header of versioned books. Unversioned books start with their
+/// width, which cannot be the ASCII code of `C`.
+pub const MAGIC: &[u8; 4] = b"C4BK";
+/// Version of the books built in memory. Version 1 is the unversioned format.
+pub const VERSION: u8 = 2;
+/// Width, height, depth, key size, value size and log size.
+const HEADER_SIZE: usize = 6;
+/// Tables larger than `2^MAX_LOG_SIZE` slots are rejected.
+const MAX_LOG_SIZE: usize = 40;
+
+/// Reasons why an opening book cannot be loaded or modified.
+/// Lengths are the number of bytes of the whole file.
+#[derive(Debug, PartialEq, Clone)]
+pub enum BookError {
+    MissingFile { path: String },
+    Io { path: String, kind: io::ErrorKind },
+    DimensionMismatch { expected: (usize, usize), found: (usize, usize) },
+    UnsupportedVersion { version: u8 },
+    UnsupportedKeySize { key_size: usize },
+    UnsupportedValueSize { value_size: usize },
+    UnsupportedLogSize { log_size: usize },
+    TruncatedData { expected: usize, found: usize },
+    TrailingData { expected: usize, found: usize },
+    /// Loaded books are mapped from their file, which is never written.
+    ReadOnly,
+    /// Books created by `new` have no slot: build them with `with_log_size`.
+    NoSlot
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use BookError::*;
+        match self {
+            MissingFile { path } =>
+                write!(f, "opening book {} does not exist", path),
+            Io { path, kind } =>
+                write!(f, "unable to read opening book {}: {}", path, kind),
+            DimensionMismatch { expected, found } =>
+                write!(f, "opening book is for a {}x{} board, but a {}x{} board was expected", found.0, found.1, expected.0, expected.1),
+            UnsupportedVersion { version } =>
+                write!(f, "unsupported opening book version {}", version),
+            UnsupportedKeySize { key_size } =>
+                write!(f, "unsupported key size of {} bytes", key_size),
+            UnsupportedValueSize { value_size } =>
+                write!(f, "unsupported value size of {} bytes", value_size),
+            UnsupportedLogSize { log_size } =>
+                write!(f, "unsupported log size of {}", log_size),
+            TruncatedData { expected, found } =>
+                write!(f, "truncated opening book: {} bytes expected, {} found", expected, found),
+            TrailingData { expected, found } =>
+                write!(f, "unexpected data at the end of the opening book: {} bytes expected, {} found", expected, found),
+            ReadOnly =>
+                write!(f, "loaded opening books are read-only"),
+            NoSlot =>
+                write!(f, "the opening book has no slot to store positions")
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+/// Bytes of a book file: the header, the keys, then the values.
+enum BookData {
+    /// Built in memory, by the generator or by a merge.
+    Owned(Vec<u8>),
+    /// Mapped from a book file: lookups read the file directly, without copying it.
+    Mapped(Mmap)
+}
+
+impl Deref for BookData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            BookData::Owned(data) => data,
+            BookData::Mapped(data) => data
+        }
+    }
+}
+
+/// Opening book, stored as a hash table indexed by `key % size`, where `size`
+/// is the first prime greater than or equal to `2^log_size`. Only the
+/// `key_size` lowest bytes of the keys are stored: by the Chinese remainder
+/// theorem they are enough to identify a key as long as `size * 2^(8*key_size)`
+/// exceeds the largest key. A value of `0` marks an empty slot.
+///
+/// Versioned books start with `MAGIC` and their version. From version 2, the
+/// values are followed by the best column of each position, plus one, `0`
+/// meaning that it is unknown. Unversioned books are still read, as version 1.
+///
+/// Positions are looked up by their canonical key, so a position and its
+/// reflection share their entry. Best columns are stored in the orientation
+/// of the canonical key.
+///
+/// Loaded books are memory-mapped and read-only: wrap them in an `Arc` to share
+/// them between solvers.
 pub struct OpeningBook {
     width: usize,
     height: usize,
     depth: usize,
-    transposition_table: LowerBoundTranspositionTable
+    key_size: usize,
+    log_size: usize,
+    size: usize,
+    version: u8,
+    /// Position of the keys in `data`, after the header.
+    offset: usize,
+    data: BookData
 }
 
 impl OpeningBook {
@@ -18,131 +124,638 @@ impl OpeningBook {
             width,
             height,
             depth: 0,
-            transposition_table: LowerBoundTranspositionTable::new(TABLE_SIZE)
+            key_size: 0,
+            log_size: 0,
+            size: 0,
+            version: VERSION,
+            offset: 0,
+            data: BookData::Owned(Vec::new())
         }
     }
 
-    pub fn load(&mut self, filename: &str) {
-        let file = File::open(&filename).expect(format!("Unable to read file: {}", filename).as_str());
-        let mut reader = BufReader::new(&file);
+    /// Empty book holding positions up to `depth` moves, with `next_prime(2^log_size)` slots.
+    pub fn with_log_size(width: usize, height: usize, depth: usize, log_size: usize) -> Self {
+        let size = next_prime(1 << log_size);
+        let mut data = MAGIC.to_vec();
+        data.extend([VERSION, width as u8, height as u8, depth as u8, KEY_SIZE as u8, 1, log_size as u8]);
+        let offset = data.len();
+        data.resize(offset + size * (KEY_SIZE + 2), 0);
 
-        let mut width = [0u8; 1];
-        reader.read_exact(&mut width).unwrap();
-        assert_eq!(width[0] as usize, self.width);
+        Self {
+            width,
+            height,
+            depth,
+            key_size: KEY_SIZE,
+            log_size,
+            size,
+            version: VERSION,
+            offset,
+            data: BookData::Owned(data)
+        }
+    }
 
-        let mut height = [0u8; 1];
-        reader.read_exact(&mut height).unwrap();
-        assert_eq!(height[0] as usize, self.height);
+    /// Smallest `log_size` leaving twice as many slots as `nb_entries`, and enough
+    /// of them for the truncated keys of a `width`x`height` board to stay unique.
+    pub fn log_size_for(width: usize, height: usize, nb_entries: usize) -> usize {
+        let log_size = (2 * nb_entries).next_power_of_two().trailing_zeros() as usize;
+        // At least 5 slots, so that the size is an odd prime
+        log_size.max((width * (height + 1)).saturating_sub(8 * KEY_SIZE)).max(2)
+    }
 
-        let mut max_depth = [0u8; 1];
-        reader.read_exact(&mut max_depth).unwrap();
-        self.depth = max_depth[0] as usize;
+    /// Loads a book, reading its dimensions from the file.
+    pub fn open(filename: &str) -> Result<Self, BookError> {
+        let data = map_file(filename)?;
+        let (_, header_start) = Self::read_version(&data)?;
+        if data.len() < header_start + HEADER_SIZE {
+            return Err(BookError::TruncatedData { expected: header_start + HEADER_SIZE, found: data.len() });
+        }
 
-        let mut key_size = [0u8; 1];
-        reader.read_exact(&mut key_size).unwrap();
-        let key_size = key_size[0] as usize;
-        assert!(key_size <= 32/8); // Keys are stored as u32 in LowerBoundTranspositionTable
+        let mut book = Self::new(data[header_start] as usize, data[header_start + 1] as usize);
+        book.parse(data)?;
+        Ok(book)
+    }
+
+    /// Loads a book of the dimensions of this one. The book is left unchanged if the file is invalid.
+    pub fn load(&mut self, filename: &str) -> Result<(), BookError> {
+        let data = map_file(filename)?;
+        self.parse(data)
+    }
+
+    /// Version of the book, and position of the header following it.
+    fn read_version(data: &[u8]) -> Result<(u8, usize), BookError> {
+        if !data.starts_with(MAGIC) {
+            return Ok((1, 0));
+        }
+
+        match data.get(MAGIC.len()) {
+            None => Err(BookError::TruncatedData { expected: MAGIC.len() + 1 + HEADER_SIZE, found: data.len() }),
+            Some(&version) if version < 2 || version > VERSION => Err(BookError::UnsupportedVersion { version }),
+            Some(&version) => Ok((version, MAGIC.len() + 1))
+        }
+    }
+
+    fn parse(&mut self, data: BookData) -> Result<(), BookError> {
+        let (version, header_start) = Self::read_version(&data)?;
+        let offset = header_start + HEADER_SIZE;
+        if data.len() < offset {
+            return Err(BookError::TruncatedData { expected: offset, found: data.len() });
+        }
+        let header = &data[header_start..offset];
+
+        let (width, height) = (header[0] as usize, header[1] as usize);
+        if (width, height) != (self.width, self.height) {
+            return Err(BookError::DimensionMismatch { expected: (self.width, self.height), found: (width, height) });
+        }
 
-        let mut value_size = [0u8; 1];
-        reader.read_exact(&mut value_size).unwrap();
-        let value_size = value_size[0] as usize;
-        assert!(value_size <= 8/8); // Values are stored as u8 in LowerBoundTranspositionTable
+        let depth = header[2] as usize;
 
-        let mut log_size = [0u8; 1];
-        reader.read_exact(&mut log_size).unwrap();
-        //let log_size = log_size[0];
+        let key_size = header[3] as usize;
+        if !(1..=32/8).contains(&key_size) { // Keys are read as u32
+            return Err(BookError::UnsupportedKeySize { key_size });
+        }
 
-        let number_values = ((file.metadata().unwrap().len() - 6) / (key_size + value_size) as u64) as usize;
+        let value_size = header[4] as usize;
+        if value_size != 1 { // Values are read as u8
+            return Err(BookError::UnsupportedValueSize { value_size });
+        }
 
-        let mut keys = Vec::with_capacity(number_values);
-        for _ in 0..number_values {
-            let mut key = vec![0u8; key_size];
-            reader.read_exact(&mut key).unwrap();
-            
-            let key = match key_size {
-                1 => u8::from_le_bytes(key.try_into().unwrap()) as u64,
-                2 => u16::from_le_bytes(key.try_into().unwrap()) as u64,
-                _ => {
-                    for _ in 0..(64/8-key_size) {
-                        key.push(0u8);
-                    }
-                    u64::from_le_bytes(key.try_into().unwrap())
-                }
-            };
+        let log_size = header[5] as usize;
+        if log_size > MAX_LOG_SIZE {
+            return Err(BookError::UnsupportedLogSize { log_size });
+        }
+        let size = next_prime(1 << log_size);
 
-            keys.push(key);
+        let moves_size = if version >= 2 { 1 } else { 0 };
+        let expected = offset + size * (key_size + value_size + moves_size);
+        if data.len() < expected {
+            return Err(BookError::TruncatedData { expected, found: data.len() });
+        } else if data.len() > expected {
+            return Err(BookError::TrailingData { expected, found: data.len() });
         }
-        
-        for i in 0..number_values {
-            let mut value = vec![0u8; value_size];
-            reader.read_exact(&mut value).unwrap();
 
-            let value = match value_size {
-                1 => u8::from_le_bytes(value.try_into().unwrap()) as u16,
-                _ => {
-                    for _ in 0..(64/8-value_size) {
-                        value.push(0u8);
-                    }
-                    u16::from_le_bytes(value.try_into().unwrap())
-                }
-            };
+        self.depth = depth;
+        self.key_size = key_size;
+        self.log_size = log_size;
+        self.size = size;
+        self.version = version;
+        self.offset = offset;
+        self.data = data;
+
+        Ok(())
+    }
 
-            self.transposition_table.insert(keys[i], value);
+    /// Writes the book in the format read by `load`.
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        fs::write(filename, &*self.data)
+    }
+
+    /// Stores `value` and the best column of the position, if known,
+    /// overriding the position previously stored in the same slot.
+    /// `key` and `best_move` are expected in the canonical orientation.
+    pub fn insert(&mut self, key: u64, value: u8, best_move: Option<usize>) -> Result<(), BookError> {
+        assert_ne!(value, 0, "0 marks the empty slots of the book.");
+        if self.size == 0 {
+            return Err(BookError::NoSlot);
         }
+        let index = (key % self.size as u64) as usize;
+        let key_offset = self.offset + index * self.key_size;
+        let value_offset = self.offset + self.size * self.key_size + index;
+        let move_offset = value_offset + self.size;
+
+        let data = match &mut self.data {
+            BookData::Owned(data) => data,
+            BookData::Mapped(_) => return Err(BookError::ReadOnly)
+        };
+        data[key_offset..key_offset + self.key_size]
+            .copy_from_slice(&truncate_key(key, self.key_size).to_le_bytes()[..self.key_size]);
+        data[value_offset] = value;
+        data[move_offset] = best_move.map_or(0, |column| column as u8 + 1);
+        Ok(())
     }
 
-    pub fn get(&self, position: &impl Position) -> Option<u16> {
-        if position.nb_moves() > self.depth {
-            None
+    /// Whether the slot of `key` holds another position.
+    fn collides(&self, key: u64) -> bool {
+        let index = (key % self.size as u64) as usize;
+        self.values()[index] != 0 && self.stored_key(index) != truncate_key(key, self.key_size)
+    }
+
+    pub fn get(&self, position: &impl KeyedPosition) -> Option<u16> {
+        // Books are generated for four-in-a-row
+        if position.nb_moves() > self.depth || self.size == 0 || position.win_length() != DEFAULT_WIN_LENGTH {
+            return None;
+        }
+
+        // Books only hold boards fitting in 64 bits
+        let key = position.canonical_key().to_u64()?;
+        let index = (key % self.size as u64) as usize;
+        let value = self.values()[index];
+        if value != 0 && self.stored_key(index) == truncate_key(key, self.key_size) {
+            Some(value as u16)
         } else {
-            self.transposition_table.get(position.key())
+            None
+        }
+    }
+
+    /// Best column of the position stored in the book, if any.
+    pub fn best_move(&self, position: &impl KeyedPosition) -> Option<usize> {
+        self.get(position)?;
+        let index = position.canonical_key().modulo(self.size as u64) as usize;
+        self.best_move_at(index).map(|column| position.canonical_column(column))
+    }
+
+    /// Score of the position stored in the book, if any.
+    pub fn score(&self, position: &impl KeyedPosition) -> Option<i32> {
+        self.get(position).map(|value| value as i32 + position.min_score() - 1)
+    }
+
+    /// Stored entries, with their full keys rebuilt from their slot and truncated key,
+    /// their value and their best column.
+    pub fn entries(&self) -> impl Iterator<Item = (u64, u8, Option<usize>)> + '_ {
+        let size = self.size as u128;
+        let modulus = 1u128 << (8 * self.key_size);
+        // Inverse of 2^(8*key_size) modulo the prime size
+        let inverse = if size > 2 { pow_mod(modulus % size, size - 2, size) } else { 0 };
+
+        self.values().iter().enumerate()
+            .filter(|(_, &value)| value != 0)
+            .map(move |(index, &value)| {
+                let key = self.stored_key(index) as u128;
+                // key = truncated + modulus * m, with key % size == index
+                let m = (index as u128 + size - key % size) % size * inverse % size;
+                ((key + modulus * m) as u64, value, self.best_move_at(index))
+            })
+    }
+
+    /// Combines two books of the same board, possibly of different depths. When two
+    /// positions share a slot, the one of the shallower book is kept: the number of
+    /// positions dropped this way, from either book, is returned with the merged book.
+    pub fn merge(&self, other: &OpeningBook) -> (OpeningBook, usize) {
+        assert_eq!((self.width, self.height), (other.width, other.height), "Cannot merge books of different boards.");
+
+        let (deeper, shallower) = if self.depth >= other.depth { (self, other) } else { (other, self) };
+        let nb_entries = deeper.len() + shallower.len();
+        let log_size = Self::log_size_for(self.width, self.height, nb_entries).max(self.log_size).max(other.log_size);
+
+        let mut book = OpeningBook::with_log_size(self.width, self.height, deeper.depth, log_size);
+        let mut dropped = 0;
+        for (key, value, best_move) in deeper.entries().chain(shallower.entries()) {
+            if book.collides(key) {
+                dropped += 1;
+            }
+            book.insert(key, value, best_move).expect("Merged books are built with slots.");
+        }
+        (book, dropped)
+    }
+
+    fn stored_key(&self, index: usize) -> u32 {
+        let offset = self.offset + index * self.key_size;
+        let mut bytes = [0u8; 4];
+        bytes[..self.key_size].copy_from_slice(&self.data[offset..offset + self.key_size]);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn values(&self) -> &[u8] {
+        let offset = self.offset + self.size * self.key_size;
+        &self.data[offset..offset + self.size]
+    }
+
+    fn best_move_at(&self, index: usize) -> Option<usize> {
+        if self.version < 2 {
+            return None;
         }
+        let offset = self.offset + self.size * (self.key_size + 1);
+        self.data[offset + index].checked_sub(1).map(|column| column as usize)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    pub fn key_size(&self) -> usize {
+        self.key_size
+    }
+
+    pub fn log_size(&self) -> usize {
+        self.log_size
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Number of slots of the table.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of stored positions.
+    pub fn len(&self) -> usize {
+        self.values().iter().filter(|&&value| value != 0).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn map_file(filename: &str) -> Result<BookData, BookError> {
+    let error = |error: io::Error| match error.kind() {
+        io::ErrorKind::NotFound => BookError::MissingFile { path: filename.to_string() },
+        kind => BookError::Io { path: filename.to_string(), kind }
+    };
+
+    let file = File::open(filename).map_err(error)?;
+    if file.metadata().map_err(error)?.len() == 0 {
+        // Empty files cannot be mapped
+        return Ok(BookData::Owned(Vec::new()));
     }
+    // SAFETY: book files are not supposed to be modified while they are used.
+    let data = unsafe { Mmap::map(&file) }.map_err(error)?;
+    Ok(BookData::Mapped(data))
+}
+
+/// Keeps the `key_size` lowest bytes of `key`.
+pub(crate) fn truncate_key(key: u64, key_size: usize) -> u32 {
+    (key & ((1u64 << (8*key_size)) - 1)) as u32
+}
+
+/// First prime greater than or equal to `n`.
+// `is_multiple_of` would need Rust 1.87
+#[allow(clippy::manual_is_multiple_of)]
+pub(crate) fn next_prime(n: usize) -> usize {
+    let is_prime = |n: usize| n >= 2 && (2..).take_while(|d| d*d <= n).all(|d| n % d != 0);
+    (n..).find(|&n| is_prime(n)).unwrap()
+}
+
+fn pow_mod(mut base: u128, mut exponent: u128, modulus: u128) -> u128 {
+    let mut result = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = result * base % modulus;
+        }
+        base = base * base % modulus;
+        exponent >>= 1;
+    }
+    result
 }
 
 #[cfg(test)]
 mod opening_book_tests {
     use super::*;
 
+    #[test]
+    fn next_prime_values() {
+        assert_eq!(next_prime(1), 2);
+        assert_eq!(next_prime(8), 11);
+        assert_eq!(next_prime(11), 11);
+        assert_eq!(next_prime(1 << 23), (1 << 23) + 9);
+    }
+
+    #[test]
+    fn empty_book() {
+        use lib_game_board::bitboard_position::BitboardPosition;
+
+        let book = OpeningBook::new(7, 6);
+        assert_eq!(book.get(&BitboardPosition::new(7, 6)), None);
+    }
+
+    mod writing {
+        use super::*;
+        use lib_game_board::{bitboard_position_with_ordering::BitboardPositionWithOrdering, sequence_position::SequencePosition};
+
+        fn position(sequence: &str) -> BitboardPositionWithOrdering {
+            BitboardPositionWithOrdering::try_from(&SequencePosition::parse(sequence).unwrap()).unwrap()
+        }
+
+        #[test]
+        fn save_and_open() {
+            let path = std::env::temp_dir()
+                .join(format!("connect_4_save_and_open_{}.book", std::process::id()));
+            let path = path.to_str().unwrap();
+
+            let mut book = OpeningBook::with_log_size(7, 6, 2, OpeningBook::log_size_for(7, 6, 2));
+            book.insert(position("").canonical_key(), 20, Some(3)).unwrap();
+            book.insert(position("44").canonical_key(), 19, None).unwrap();
+            book.save(path).unwrap();
+
+            let book = OpeningBook::open(path).unwrap();
+            assert_eq!((book.width(), book.height(), book.depth(), book.key_size()), (7, 6, 2, 4));
+            assert_eq!(book.version(), VERSION);
+            assert_eq!(book.len(), 2);
+            assert_eq!(book.get(&position("")), Some(20));
+            assert_eq!(book.best_move(&position("")), Some(3));
+            assert_eq!(book.score(&position("44")), Some(19 - 18 - 1));
+            assert_eq!(book.best_move(&position("44")), None);
+            assert_eq!(book.get(&position("43")), None);
+            assert_eq!(book.best_move(&position("43")), None);
+
+            std::fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn mirrored_lookup() {
+            let mut book = OpeningBook::with_log_size(7, 6, 2, OpeningBook::log_size_for(7, 6, 1));
+            book.insert(position("12").canonical_key(), 21, Some(2)).unwrap();
+
+            assert_eq!(book.get(&position("76")), Some(21));
+            assert_eq!(book.best_move(&position("12")), Some(2));
+            assert_eq!(book.best_move(&position("76")), Some(4));
+            assert_eq!(book.get(&position("13")), None);
+        }
+
+        #[test]
+        fn other_win_length() {
+            let mut book = OpeningBook::with_log_size(7, 6, 2, OpeningBook::log_size_for(7, 6, 1));
+            book.insert(position("").canonical_key(), 20, Some(3)).unwrap();
+
+            // Same key, but not the same game
+            let connect_five = BitboardPositionWithOrdering::with_win_length(7, 6, 5);
+            assert_eq!(book.get(&connect_five), None);
+            assert_eq!(book.best_move(&connect_five), None);
+            assert_eq!(book.score(&connect_five), None);
+        }
+
+        #[test]
+        fn entries_full_keys() {
+            let keys: Vec<u64> = ["", "4", "4453", "12345671"].iter().map(|sequence| position(sequence).key()).collect();
+            let mut book = OpeningBook::with_log_size(7, 6, 8, OpeningBook::log_size_for(7, 6, keys.len()));
+            for &key in &keys {
+                book.insert(key, 1, None).unwrap();
+            }
+
+            let mut entries: Vec<u64> = book.entries().map(|(key, _, _)| key).collect();
+            entries.sort();
+            let mut keys = keys;
+            keys.sort();
+            assert_eq!(entries, keys);
+        }
+
+        #[test]
+        fn merge() {
+            let mut shallow = OpeningBook::with_log_size(4, 4, 1, OpeningBook::log_size_for(4, 4, 2));
+            shallow.insert(position("").canonical_key(), 6, Some(1)).unwrap();
+            shallow.insert(position("1").canonical_key(), 7, None).unwrap();
+            let mut deep = OpeningBook::with_log_size(4, 4, 3, OpeningBook::log_size_for(4, 4, 1));
+            deep.insert(position("123").canonical_key(), 8, Some(2)).unwrap();
+
+            let (merged, dropped) = shallow.merge(&deep);
+            assert_eq!(dropped, 0);
+            assert_eq!(merged.depth(), 3);
+            assert_eq!(merged.len(), 3);
+            assert_eq!(merged.get(&position("")), Some(6));
+            assert_eq!(merged.get(&position("1")), Some(7));
+            assert_eq!(merged.get(&position("123")), Some(8));
+            assert_eq!(merged.best_move(&position("")), Some(1));
+            assert_eq!(merged.best_move(&position("1")), None);
+            assert_eq!(merged.best_move(&position("123")), Some(2));
+        }
+
+        #[test]
+        fn merge_collision() {
+            // The merged book has 11 slots: keys 1 and 12 share one, while key 3 is in both books
+            let mut shallow = OpeningBook::with_log_size(4, 4, 1, 2);
+            shallow.insert(12, 4, Some(0)).unwrap();
+            shallow.insert(3, 5, None).unwrap();
+            let mut deep = OpeningBook::with_log_size(4, 4, 3, 2);
+            deep.insert(1, 7, None).unwrap();
+            deep.insert(3, 5, None).unwrap();
+
+            let (merged, dropped) = deep.merge(&shallow);
+            assert_eq!((merged.size(), dropped), (11, 1));
+            let mut entries: Vec<_> = merged.entries().collect();
+            entries.sort();
+            assert_eq!(entries, vec![(3, 5, None), (12, 4, Some(0))]);
+        }
+    }
+
+    mod errors {
+        use super::*;
+
+        /// Writes `content` to a temporary book file, and loads it on a 4x4 board.
+        fn load_bytes(name: &str, content: &[u8]) -> Result<(), BookError> {
+            let path = std::env::temp_dir().join(format!("connect_4_{}_{}.book", name, std::process::id()));
+            let path = path.to_str().unwrap();
+            fs::write(path, content).unwrap();
+
+            let result = OpeningBook::new(4, 4).load(path);
+            fs::remove_file(path).unwrap();
+            result
+        }
+
+        /// Valid unversioned 4x4 book of depth 1 with 5 empty slots.
+        fn valid_book() -> Vec<u8> {
+            let mut content = vec![4, 4, 1, 4, 1, 2];
+            content.extend([0u8; 5 * 5]);
+            content
+        }
+
+        /// Same book, in the current version.
+        fn valid_versioned_book() -> Vec<u8> {
+            let mut content = MAGIC.to_vec();
+            content.extend([VERSION, 4, 4, 1, 4, 1, 2]);
+            content.extend([0u8; 5 * 6]);
+            content
+        }
+
+        #[test]
+        fn valid() {
+            assert_eq!(load_bytes("valid", &valid_book()), Ok(()));
+            assert_eq!(load_bytes("valid_versioned", &valid_versioned_book()), Ok(()));
+        }
+
+        #[test]
+        fn unsupported_version() {
+            let mut content = valid_versioned_book();
+            content[MAGIC.len()] = VERSION + 1;
+            assert_eq!(load_bytes("unsupported_version", &content), Err(BookError::UnsupportedVersion { version: VERSION + 1 }));
+        }
+
+        #[test]
+        fn missing_file() {
+            assert_eq!(
+                OpeningBook::new(4, 4).load("./opening-books/missing.book"),
+                Err(BookError::MissingFile { path: "./opening-books/missing.book".to_string() })
+            );
+        }
+
+        #[test]
+        fn dimension_mismatch() {
+            let mut content = valid_book();
+            content[0] = 7;
+            assert_eq!(
+                load_bytes("dimension_mismatch", &content),
+                Err(BookError::DimensionMismatch { expected: (4, 4), found: (7, 4) })
+            );
+        }
+
+        #[test]
+        fn unsupported_sizes() {
+            let mut content = valid_book();
+            content[3] = 8;
+            assert_eq!(load_bytes("key_size", &content), Err(BookError::UnsupportedKeySize { key_size: 8 }));
+
+            let mut content = valid_book();
+            content[4] = 2;
+            assert_eq!(load_bytes("value_size", &content), Err(BookError::UnsupportedValueSize { value_size: 2 }));
+
+            let mut content = valid_book();
+            content[5] = 200;
+            assert_eq!(load_bytes("log_size", &content), Err(BookError::UnsupportedLogSize { log_size: 200 }));
+        }
+
+        #[test]
+        fn truncated_data() {
+            let content = valid_book();
+            assert_eq!(
+                load_bytes("truncated_header", &content[..3]),
+                Err(BookError::TruncatedData { expected: 6, found: 3 })
+            );
+            assert_eq!(
+                load_bytes("truncated_data", &content[..20]),
+                Err(BookError::TruncatedData { expected: 31, found: 20 })
+            );
+
+            let mut content = valid_book();
+            content.push(0);
+            assert_eq!(
+                load_bytes("trailing_data", &content),
+                Err(BookError::TrailingData { expected: 31, found: 32 })
+            );
+
+            // The moves are missing
+            let content = valid_versioned_book();
+            assert_eq!(
+                load_bytes("truncated_moves", &content[..content.len() - 5]),
+                Err(BookError::TruncatedData { expected: 41, found: 36 })
+            );
+        }
+
+        #[test]
+        fn empty_file() {
+            assert_eq!(load_bytes("empty_file", &[]), Err(BookError::TruncatedData { expected: 6, found: 0 }));
+        }
+
+        #[test]
+        fn loaded_book_read_only() {
+            let path = std::env::temp_dir().join(format!("connect_4_read_only_{}.book", std::process::id()));
+            let path = path.to_str().unwrap();
+            fs::write(path, valid_book()).unwrap();
+
+            let mut book = OpeningBook::new(4, 4);
+            book.load(path).unwrap();
+            fs::remove_file(path).unwrap();
+            assert_eq!(book.insert(0, 3, None), Err(BookError::ReadOnly));
+        }
+
+        #[test]
+        fn book_without_slot() {
+            assert_eq!(OpeningBook::new(4, 4).insert(0, 3, None), Err(BookError::NoSlot));
+        }
+
+        #[test]
+        fn book_left_unchanged() {
+            let mut book = OpeningBook::with_log_size(4, 4, 2, 2);
+            book.insert(0, 3, None).unwrap();
+            assert!(book.load("./opening-books/missing.book").is_err());
+            assert_eq!((book.depth(), book.len()), (2, 1));
+        }
+    }
+
     mod loading {
         use super::*;
+        use crate::opening_book_generator::fixture_book;
 
         #[test]
         fn load_small() {
-            let mut book = OpeningBook::new(7, 6);
-            book.load("./opening-books/7x6_small.book");
+            let path = fixture_book("load_small", 4);
+            let mut book = OpeningBook::new(4, 4);
+            book.load(&path).unwrap();
+            fs::remove_file(path).unwrap();
         }
 
         #[test]
-        #[ignore]
         fn load_large() {
-            let mut book = OpeningBook::new(7, 6);
-            book.load("./opening-books/7x6.book");
+            let path = fixture_book("load_large", 6);
+            let mut book = OpeningBook::new(4, 4);
+            book.load(&path).unwrap();
+            fs::remove_file(path).unwrap();
         }
     }
 
     mod informations {
         use super::*;
+        use crate::opening_book_generator::fixture_book;
 
         #[test]
         fn info_small() {
-            let mut book = OpeningBook::new(7, 6);
-            book.load("./opening-books/7x6_small.book");
+            let path = fixture_book("info_small", 4);
+            let mut book = OpeningBook::new(4, 4);
+            book.load(&path).unwrap();
+            fs::remove_file(path).unwrap();
 
-            assert_eq!(book.width, 7);
-            assert_eq!(book.height, 6);
-            assert_eq!(book.depth, 16);
+            assert_eq!(book.width, 4);
+            assert_eq!(book.height, 4);
+            assert_eq!(book.depth, 4);
         }
 
         #[test]
-        #[ignore]
         fn info_large() {
-            let mut book = OpeningBook::new(7, 6);
-            book.load("./opening-books/7x6.book");
+            let path = fixture_book("info_large", 6);
+            let mut book = OpeningBook::new(4, 4);
+            book.load(&path).unwrap();
+            fs::remove_file(path).unwrap();
 
-            assert_eq!(book.width, 7);
-            assert_eq!(book.height, 6);
-            assert_eq!(book.depth, 14);
+            assert_eq!(book.width, 4);
+            assert_eq!(book.height, 4);
+            assert_eq!(book.depth, 6);
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,241 @@
+use lib_game_board::{Position, KeyedPosition, Analyser, bitboard_position_with_ordering::BitboardPositionWithOrdering};
+use crate::final_alpha_beta::FinalAlphaBeta;
+use crate::opening_book::{OpeningBook, MAGIC, VERSION};
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+
+/// A solved position in the `.partial` file: the full key, the value, then the best column plus one.
+const RECORD_SIZE: usize = 8 + 1 + 1;
+
+/// The value and best move of the solved positions, by canonical key.
+/// Moves are in the orientation of the canonical key.
+type Solved = HashMap<u64, (u8, Option<usize>)>;
+
+/// Builds opening books readable by `OpeningBook`, by solving with `FinalAlphaBeta`
+/// every position reachable in at most `depth` moves.
+pub struct OpeningBookGenerator {
+    width: usize,
+    height: usize,
+    depth: usize,
+    move_order: Vec<usize>
+}
+
+impl OpeningBookGenerator {
+    pub fn new(width: usize, height: usize, depth: usize, move_order: Vec<usize>) -> Self {
+        assert!(depth < width * height, "The depth of the book must be smaller than the number of cells.");
+        Self { width, height, depth, move_order }
+    }
+
+    /// Unique positions reachable in at most `depth` moves where the game is not over yet,
+    /// a position and its reflection counting once.
+    /// The deepest positions come first: they are cheaper to solve, and their results
+    /// stay in the transposition table of the solver for the shallower ones.
+    pub fn positions(&self) -> Vec<BitboardPositionWithOrdering> {
+        let mut levels = vec![vec![BitboardPositionWithOrdering::new(self.width, self.height)]];
+
+        for _ in 0..self.depth {
+            let mut seen = HashSet::new();
+            let mut next_level = Vec::new();
+
+            for position in levels.last().unwrap() {
+                for column in 0..self.width {
+                    if position.can_play(column) && !position.is_winning_move(column) {
+                        let mut position2 = position.clone();
+                        position2.play(column);
+                        if seen.insert(position2.canonical_key()) {
+                            next_level.push(position2);
+                        }
+                    }
+                }
+            }
+
+            levels.push(next_level);
+        }
+
+        levels.into_iter().rev().flatten().collect()
+    }
+
+    /// Solves the positions and writes the book to `path`.
+    /// Each solved position is saved to `<path>.partial`, so that an interrupted
+    /// generation resumes where it stopped. `progress` is called with the number
+    /// of solved positions and the total number of positions.
+    pub fn generate(&self, path: &str, mut progress: impl FnMut(usize, usize)) -> io::Result<()> {
+        let positions = self.positions();
+        let partial_path = format!("{}.partial", path);
+        let (mut solved, mut partial) = self.open_partial(&partial_path)?;
+
+        let mut solver = FinalAlphaBeta::new(self.width, self.height, self.move_order.clone());
+        let mut solved_nb = positions.iter().filter(|position| solved.contains_key(&position.canonical_key())).count();
+        progress(solved_nb, positions.len());
+
+        for position in &positions {
+            if solved.contains_key(&position.canonical_key()) {
+                continue;
+            }
+
+            let (score, best_move) = solver.best_move(position);
+            let value = (score - position.min_score() + 1) as u8;
+            let best_move = best_move.map(|column| position.canonical_column(column));
+            partial.write_all(&position.canonical_key().to_le_bytes())?;
+            partial.write_all(&[value, best_move.map_or(0, |column| column as u8 + 1)])?;
+            solved.insert(position.canonical_key(), (value, best_move));
+
+            solved_nb += 1;
+            progress(solved_nb, positions.len());
+        }
+
+        self.write_book(path, &positions, &solved)?;
+        fs::remove_file(partial_path)
+    }
+
+    /// Opens the checkpoint file, creating it if needed, and reads the positions already solved.
+    fn open_partial(&self, partial_path: &str) -> io::Result<(Solved, File)> {
+        let mut header = MAGIC.to_vec();
+        header.extend([VERSION, self.width as u8, self.height as u8, self.depth as u8]);
+        let mut solved = HashMap::new();
+
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(partial_path)?;
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+
+        if content.is_empty() {
+            file.write_all(&header)?;
+            return Ok((solved, file));
+        }
+        if content.len() < header.len() || content[..header.len()] != header {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} was created for another board, depth or book version.", partial_path)
+            ));
+        }
+
+        let records = content[header.len()..].chunks_exact(RECORD_SIZE);
+        // Drop the last record if the previous run stopped while writing it.
+        file.set_len((header.len() + records.len() * RECORD_SIZE) as u64)?;
+        for record in records {
+            let best_move = record[9].checked_sub(1).map(|column| column as usize);
+            solved.insert(u64::from_le_bytes(record[..8].try_into().unwrap()), (record[8], best_move));
+        }
+
+        Ok((solved, file))
+    }
+
+    fn write_book(&self, path: &str, positions: &[BitboardPositionWithOrdering], solved: &Solved) -> io::Result<()> {
+        let log_size = OpeningBook::log_size_for(self.width, self.height, positions.len());
+        let mut book = OpeningBook::with_log_size(self.width, self.height, self.depth, log_size);
+
+        // Shallower positions come last, and override the deeper ones sharing their slot.
+        for position in positions {
+            let (value, best_move) = solved[&position.canonical_key()];
+            book.insert(position.canonical_key(), value, best_move).map_err(io::Error::other)?;
+        }
+
+        book.save(path)
+    }
+}
+
+/// Generates a 4x4 book of `depth` moves in the temporary directory, for the tests needing a book file.
+/// The 7x6 books take too long to generate for the tests.
+#[cfg(test)]
+pub(crate) fn fixture_book(name: &str, depth: usize) -> String {
+    let path = std::env::temp_dir().join(format!("connect_4_{}_{}.book", name, std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    OpeningBookGenerator::new(4, 4, depth, vec![1, 2, 0, 3]).generate(&path, |_, _| ()).unwrap();
+    path
+}
+
+#[cfg(test)]
+mod opening_book_generator_tests {
+    use super::*;
+    use lib_game_board::Solver;
+    use std::panic::{self, AssertUnwindSafe};
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("connect_4_{}_{}.book", name, std::process::id()))
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn positions_count() {
+        // Mirrored positions count once, and no position of up to 3 moves is symmetric on a 4x4 board
+        assert_eq!(OpeningBookGenerator::new(4, 4, 2, vec![1, 2, 0, 3]).positions().len(), 1 + 4/2 + 16/2);
+        // "44" is the only symmetric position after 2 moves on a 7x6 board
+        assert_eq!(OpeningBookGenerator::new(7, 6, 2, (0..7).collect()).positions().len(), 1 + 4 + (49 - 1)/2 + 1);
+        // "123" and "321" lead to the same position
+        assert_eq!(OpeningBookGenerator::new(4, 4, 3, vec![1, 2, 0, 3]).positions().len(), 1 + 4/2 + 16/2 + 52/2);
+        assert_eq!(OpeningBookGenerator::new(4, 4, 3, vec![1, 2, 0, 3]).positions()[0].nb_moves(), 3);
+    }
+
+    #[test]
+    fn generate_and_load() {
+        let path = temp_path("generate_and_load");
+        let generator = OpeningBookGenerator::new(4, 4, 4, vec![1, 2, 0, 3]);
+        let mut calls = 0;
+        generator.generate(&path, |_, total| { calls += 1; assert_eq!(total, generator.positions().len()); }).unwrap();
+        assert_eq!(calls, generator.positions().len() + 1);
+        assert!(!std::path::Path::new(&format!("{}.partial", path)).exists());
+
+        let mut book = OpeningBook::new(4, 4);
+        book.load(&path).unwrap();
+        let mut solver = FinalAlphaBeta::new(4, 4, vec![1, 2, 0, 3]);
+        let mut found = 0;
+        for position in generator.positions() {
+            // Positions sharing a slot with a shallower one are lost
+            if let Some(value) = book.get(&position) {
+                let score = solver.solve(&position);
+                assert_eq!(value as i32 - 5 - 1, score);
+
+                // The stored move reaches the score of the position
+                let column = book.best_move(&position).expect("Best move missing from the book.");
+                if !position.is_winning_move(column) {
+                    let mut position2 = position.clone();
+                    position2.play(column);
+                    assert_eq!(-solver.solve(&position2), score);
+                }
+                found += 1;
+            }
+        }
+        assert!(book.get(&BitboardPositionWithOrdering::new(4, 4)).is_some());
+        assert!(found * 4 >= generator.positions().len() * 3);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn resume() {
+        let generator = OpeningBookGenerator::new(4, 4, 3, vec![1, 2, 0, 3]);
+        let complete_path = temp_path("resume_complete");
+        generator.generate(&complete_path, |_, _| ()).unwrap();
+
+        // Interrupt a generation after a few positions
+        let path = temp_path("resume");
+        let interrupted = panic::catch_unwind(AssertUnwindSafe(|| {
+            generator.generate(&path, |solved, _| if solved == 10 { panic!("interrupted") }).unwrap();
+        }));
+        assert!(interrupted.is_err());
+
+        let mut first_progress = None;
+        generator.generate(&path, |solved, _| { first_progress.get_or_insert(solved); }).unwrap();
+        assert_eq!(first_progress, Some(10));
+        assert_eq!(fs::read(&path).unwrap(), fs::read(&complete_path).unwrap());
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(complete_path).unwrap();
+    }
+
+    #[test]
+    fn partial_from_other_board() {
+        let path = temp_path("partial_from_other_board");
+        fs::write(format!("{}.partial", path), [5, 4, 3]).unwrap();
+
+        let result = OpeningBookGenerator::new(4, 4, 3, vec![1, 2, 0, 3]).generate(&path, |_, _| ());
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_file(format!("{}.partial", path)).unwrap();
+    }
+}
@@ -1,35 +1,39 @@
+use lib_game_board::bitboard::Bitboard;
+
 pub const TABLE_SIZE: usize = (1 << 23) + 9; // first prime greater than 2^23
 
-pub struct OptimisedTranspositionTable {
+/// Only the partial keys are stored: with `TABLE_SIZE` slots, the table is exact
+/// for keys of up to 55 bits with `u64` bitboards, and up to 87 bits with `u128` ones.
+pub struct OptimisedTranspositionTable<B: Bitboard = u64> {
     size: usize,
-    keys: Vec<u32>,
+    keys: Vec<B::PartialKey>,
     values: Vec<Option<u8>>
 }
 
-impl OptimisedTranspositionTable {
+impl<B: Bitboard> OptimisedTranspositionTable<B> {
     pub fn new(size: usize) -> Self {
         assert_eq!(size % 2, 1, "OptimisedTranspositionTable size must be odd, but it is equal to {size}.");
 
         Self { 
             size, 
-            keys: vec![0; size], 
+            keys: vec![B::PartialKey::default(); size], 
             values: vec![None; size]
         }
     }
     
-    fn index(&self, key: u64) -> usize {
-        key as usize % self.size
+    fn index(&self, key: B) -> usize {
+        key.modulo(self.size as u64) as usize
     }
 
-    pub fn insert(&mut self, key: u64, value: u8) {
+    pub fn insert(&mut self, key: B, value: u8) {
         let index = self.index(key);
-        self.keys[index] = key as u32; // possibly truncated
+        self.keys[index] = key.partial_key(); // possibly truncated
         self.values[index] = Some(value);
     }
 
-    pub fn get(&self, key: u64) -> Option<u8> {
+    pub fn get(&self, key: B) -> Option<u8> {
         let index = self.index(key);
-        if self.keys[index] == key as u32 {
+        if self.keys[index] == key.partial_key() {
             self.values[index]
         } else {
             None
@@ -43,7 +47,7 @@ mod optimised_transposition_table_tests {
 
     #[test]
     fn insert_get() {
-        let mut table = OptimisedTranspositionTable::new(11);
+        let mut table: OptimisedTranspositionTable = OptimisedTranspositionTable::new(11);
 
         table.insert(42, 21);
         assert_eq!(table.get(42), Some(21));
@@ -51,7 +55,7 @@ mod optimised_transposition_table_tests {
 
     #[test]
     fn insert_get_index() {
-        let mut table = OptimisedTranspositionTable::new(11);
+        let mut table: OptimisedTranspositionTable = OptimisedTranspositionTable::new(11);
 
         table.insert(10, 21);
         assert_eq!(table.get(0), None);
@@ -59,7 +63,7 @@ mod optimised_transposition_table_tests {
 
     #[test]
     fn index_override() {
-        let mut table = OptimisedTranspositionTable::new(11);
+        let mut table: OptimisedTranspositionTable = OptimisedTranspositionTable::new(11);
 
         table.insert(10, 21);
         table.insert(21, 22);
@@ -0,0 +1,415 @@
+use lib_game_board::{BitboardMovePosition, Solver, WeakSolver, Analyser};
+use lib_game_board::bitboard::Bitboard;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::shared_transposition_table::{SharedTranspositionTable, TABLE_SIZE};
+use crate::move_sorter::MoveSorter;
+use crate::opening_book::{OpeningBook, BookError};
+use crate::search_control::{SearchControl, Controllable};
+
+/// Lazy SMP version of the Final solver: several threads search the same position
+/// with different move orders, sharing their results through a common transposition
+/// table. The first thread to finish gives the score, and stops the others.
+pub struct ParallelAlphaBeta<B: Bitboard = u64> {
+    move_order: Vec<usize>,
+    nb_threads: usize,
+    explored_positions: usize,
+    transposition_table: Arc<SharedTranspositionTable<B>>,
+    opening_book: Arc<OpeningBook>,
+    control: SearchControl
+}
+
+impl<B: Bitboard> ParallelAlphaBeta<B> {
+    pub fn new(width: usize, height: usize, move_order: Vec<usize>, nb_threads: usize) -> Self {
+        assert!(nb_threads > 0, "ParallelAlphaBeta needs at least one thread.");
+        assert!(Self::supports(width, height), "A {}x{} board is not supported by a {}bits solver.", width, height, B::BITS);
+
+        Self {
+            move_order,
+            nb_threads,
+            explored_positions: 0,
+            transposition_table: Arc::new(SharedTranspositionTable::new(TABLE_SIZE)),
+            opening_book: Arc::new(OpeningBook::new(width, height)),
+            control: SearchControl::default()
+        }
+    }
+
+    /// Whether `width`x`height` boards can be solved: they need at least two cells,
+    /// and their keys must be told apart by the shared transposition table.
+    pub fn supports(width: usize, height: usize) -> bool {
+        width * height >= 2 && width * (height + 1) <= SharedTranspositionTable::<B>::exact_key_bits(TABLE_SIZE)
+    }
+
+    pub fn nb_threads(&self) -> usize {
+        self.nb_threads
+    }
+
+    /// Loads the opening book stored in `book_path`. If it is invalid,
+    /// the solver keeps searching without a book.
+    pub fn load_opening_book(&mut self, book_path: &str) -> Result<(), BookError> {
+        let mut book = OpeningBook::new(self.opening_book.width(), self.opening_book.height());
+        book.load(book_path)?;
+        self.opening_book = Arc::new(book);
+        Ok(())
+    }
+
+    /// Transposition table of the solver, which other solvers of the same board may share.
+    pub fn transposition_table(&self) -> Arc<SharedTranspositionTable<B>> {
+        Arc::clone(&self.transposition_table)
+    }
+
+    /// Searches with a table shared with other solvers, which must play on the same board.
+    pub fn set_transposition_table(&mut self, transposition_table: Arc<SharedTranspositionTable<B>>) {
+        self.transposition_table = transposition_table;
+    }
+
+    /// Move order of the thread `thread`: each thread breaks the ties between
+    /// the moves of equal score differently, so that they explore different subtrees.
+    fn thread_move_order(&self, thread: usize) -> Vec<usize> {
+        let mut move_order = self.move_order.clone();
+        let length = move_order.len().max(1);
+        move_order.rotate_left(thread % length);
+        if (thread / length) % 2 == 1 {
+            move_order.reverse();
+        }
+        move_order
+    }
+
+    /// Null window search of the score between `min` and `max` by all the threads.
+    /// A cancelled search returns `min`, the lower bound it started from.
+    fn search<P: BitboardMovePosition<Bitboard = B> + Clone + Sync>(&mut self, position: &P, min: i32, max: i32) -> i32 {
+        self.control.start();
+        let stop = AtomicBool::new(false);
+        let cancel_token = self.control.cancel_token();
+        let move_orders: Vec<Vec<usize>> = (0..self.nb_threads).map(|thread| self.thread_move_order(thread)).collect();
+        let mut workers: Vec<Worker<B>> = move_orders.into_iter()
+            .map(|move_order| Worker {
+                move_order,
+                explored_positions: 0,
+                transposition_table: &self.transposition_table,
+                opening_book: &self.opening_book,
+                stop: &stop,
+                cancel_token: cancel_token.as_deref()
+            })
+            .collect();
+
+        let (main, helpers) = workers.split_first_mut().unwrap();
+        let explored_before = self.explored_positions;
+        let control = &mut self.control;
+        let scores: Vec<Option<i32>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = helpers.iter_mut()
+                .map(|worker| scope.spawn(|| worker.search(position, min, max, |_, _, _| ())))
+                .collect();
+            // Only the main thread reports the progress, along with its own explored positions
+            let mut scores = vec![main.search(position, min, max, |min, max, explored_positions| {
+                control.report(min, max, explored_before + explored_positions);
+            })];
+            scores.extend(handles.into_iter().map(|handle| handle.join().unwrap()));
+            scores
+        });
+
+        let explored_positions: usize = workers.iter().map(|worker| worker.explored_positions).sum();
+        self.explored_positions += explored_positions;
+        // Threads only stop without a score when the search is cancelled
+        scores.into_iter().flatten().next().unwrap_or_else(|| {
+            self.control.cancel();
+            min
+        })
+    }
+}
+
+/// Thread of a parallel search.
+struct Worker<'a, B: Bitboard> {
+    move_order: Vec<usize>,
+    explored_positions: usize,
+    transposition_table: &'a SharedTranspositionTable<B>,
+    opening_book: &'a OpeningBook,
+    /// Set once a thread found the score.
+    stop: &'a AtomicBool,
+    cancel_token: Option<&'a AtomicBool>
+}
+
+impl<B: Bitboard> Worker<'_, B> {
+    fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed) || self.cancel_token.is_some_and(|token| token.load(Ordering::Relaxed))
+    }
+
+    /// Score between `min` and `max`, `None` when another thread found it first or the search
+    /// is cancelled. `report` is given the bounds after each null window iteration.
+    fn search(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone), mut min: i32, mut max: i32, mut report: impl FnMut(i32, i32, usize)) -> Option<i32> {
+        while min < max {
+            let mut med = min + (max - min)/2;
+
+            if med <= 0 && min/2 < med { med = min/2; }
+            else if med >= 0 && max/2 > med { med = max/2; }
+
+            let r = self.solve_range(position, med, med+1);
+            if self.is_stopped() {
+                return None;
+            }
+            if r <= med {
+                max = r;
+            } else {
+                min = r;
+            }
+            report(min, max, self.explored_positions);
+        }
+
+        self.stop.store(true, Ordering::Relaxed);
+        Some(min)
+    }
+
+    fn solve_range(&mut self, position: &(impl BitboardMovePosition<Bitboard = B> + Clone), mut alpha: i32, mut beta: i32) -> i32 {
+        self.explored_positions += 1;
+        if self.is_stopped() {
+            return 0;
+        }
+
+        // Anticipate loosing move
+        let next = position.possible_non_loosing_moves();
+        if next == B::ZERO {
+            return -((position.width()*position.height() - position.nb_moves()) as i32)/2;
+        }
+
+        // Check for a draw game
+        if position.nb_moves() >= position.width() * position.height() - 2 {
+            return 0;
+        }
+
+        let min = - ((position.width()*position.height() - 2 - position.nb_moves()) as i32)/2;
+        if alpha < min {
+            alpha = min;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        let max = ((position.width()*position.height() - 1 - position.nb_moves()) as i32)/2;
+        if beta > max {
+            beta = max;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+
+        let position_min_score = position.min_score();
+        let position_max_score = position.max_score();
+
+        // Compare lower and upper bound to the transposition table content
+        if let Some(val) = self.transposition_table.get(position.canonical_key()) {
+            if val > (position_max_score - position_min_score + 1) as u16 { // we have a lower bound
+                let min = val as i32 + 2*position_min_score - position_max_score - 2;
+                if alpha < min {
+                    alpha = min;
+                    if alpha >= beta {
+                        return alpha;
+                    }
+                }
+            } else { // we have an upper bound
+                let max = val as i32 + position_min_score - 1;
+                if beta > max {
+                    beta = max;
+                    if alpha >= beta {
+                        return beta;
+                    }
+                }
+            }
+        };
+
+        if let Some(val) = self.opening_book.get(position) {
+            return val as i32 + position_min_score - 1;
+        }
+
+        let mut move_sorter = MoveSorter::new(position.width());
+        for column in self.move_order.iter().rev() {
+            let move_bit = next & B::column_mask(position.height(), *column);
+            if move_bit != B::ZERO {
+                move_sorter.add(move_bit, position.move_score(move_bit));
+            }
+        }
+
+        loop {
+            let next = move_sorter.get_next();
+            if next == B::ZERO { break; }
+
+            let mut position2 = position.clone();
+            position2.play_move(next);
+
+            let score = -self.solve_range(&position2, -beta, -alpha);
+            // The scores of a stopped search are meaningless
+            if self.is_stopped() {
+                return 0;
+            }
+
+            if score >= beta {
+                self.transposition_table.insert(position.canonical_key(), (score + position_max_score - 2*position_min_score + 2) as u16);
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        self.transposition_table.insert(position.canonical_key(), (alpha - position_min_score + 1) as u16);
+        alpha
+    }
+}
+
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone + Sync> Solver<P> for ParallelAlphaBeta<B> {
+    fn solve(&mut self, position: &P) -> i32 {
+        if position.can_win_next() {
+            return ((position.width()*position.height() + 1 - position.nb_moves()) as i32)/2;
+        }
+
+        let min = - ((position.width()*position.height() - position.nb_moves()) as i32)/2;
+        let max = ((position.width()*position.height() + 1 - position.nb_moves()) as i32)/2;
+        self.search(position, min, max)
+    }
+
+    fn explored_positions(&self) -> usize {
+        self.explored_positions
+    }
+
+    fn reset_explored_positions(&mut self) {
+        self.explored_positions = 0;
+        self.transposition_table = Arc::new(SharedTranspositionTable::new(TABLE_SIZE));
+    }
+}
+
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone + Sync> Analyser<P> for ParallelAlphaBeta<B> {
+    fn is_cancelled(&mut self) -> bool {
+        self.control.is_cancelled()
+    }
+}
+
+/// The cancel token stops every thread, while the progress is the one of the main thread.
+impl<B: Bitboard> Controllable for ParallelAlphaBeta<B> {
+    fn control(&mut self) -> &mut SearchControl {
+        &mut self.control
+    }
+}
+
+impl<B: Bitboard, P: BitboardMovePosition<Bitboard = B> + Clone + Sync> WeakSolver<P> for ParallelAlphaBeta<B> {
+    fn weak_solve(&mut self, position: &P) -> i32 {
+        if position.can_win_next() {
+            return 1;
+        }
+
+        self.search(position, -1, 1)
+    }
+
+    fn explored_positions(&self) -> usize {
+        self.explored_positions
+    }
+
+    fn reset_explored_positions(&mut self) {
+        self.explored_positions = 0;
+        self.transposition_table = Arc::new(SharedTranspositionTable::new(TABLE_SIZE));
+    }
+}
+
+#[cfg(test)]
+mod parallel_alpha_beta_tests {
+    use super::*;
+    use lib_game_board::{Position, center_first};
+    use lib_game_board::bitboard_position_with_ordering::{BitboardPositionWithOrdering, WideBitboardPosition};
+    use lib_game_board::sequence_position::SequencePosition;
+    use crate::final_alpha_beta::FinalAlphaBeta;
+
+    fn position(sequence: &str) -> BitboardPositionWithOrdering {
+        BitboardPositionWithOrdering::try_from(&SequencePosition::parse(sequence).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn bitboard_correctness() {
+        for nb_threads in [1, 2, 4] {
+            let mut solver = ParallelAlphaBeta::new(7, 6, (0..7).collect(), nb_threads);
+            assert_eq!(solver.solve(&position("2252576253462244111563365343671351441")), -1);
+            assert_eq!(solver.weak_solve(&position("2252576253462244111563365343671351441")), -1);
+        }
+    }
+
+    #[test]
+    fn same_scores_as_final() {
+        let mut solver = ParallelAlphaBeta::new(7, 6, center_first(7), 3);
+        let mut reference = FinalAlphaBeta::new(7, 6, center_first(7));
+        for sequence in ["121212", "7422341735647741166133573473242566", "23163416124767223154467471272416755633", "71255763773133525731261364622167124446454"] {
+            assert_eq!(solver.solve(&position(sequence)), reference.solve(&position(sequence)), "{}", sequence);
+        }
+    }
+
+    #[test]
+    fn small_and_wide_boards() {
+        let mut solver = ParallelAlphaBeta::new(5, 4, center_first(5), 4);
+        let empty = BitboardPositionWithOrdering::from_seq_with_dims(5, 4, &SequencePosition::parse("").unwrap()).unwrap();
+        assert_eq!(solver.solve(&empty), FinalAlphaBeta::new(5, 4, center_first(5)).solve(&empty));
+
+        let sequence = SequencePosition::parse("168972888788892426222261635119969541466433314").unwrap();
+        let position = WideBitboardPosition::from_seq_with_dims(9, 7, &sequence).unwrap();
+        let mut solver = ParallelAlphaBeta::new(9, 7, center_first(9), 2);
+        assert_eq!(solver.solve(&position), FinalAlphaBeta::new(9, 7, center_first(9)).solve(&position));
+    }
+
+    #[test]
+    fn best_move() {
+        let mut solver = ParallelAlphaBeta::new(7, 6, (0..7).collect(), 2);
+        let position = position("2252576253462244111563365343671351441");
+
+        let (score, column) = solver.best_move(&position);
+        let mut position2 = position.clone();
+        position2.play(column.unwrap());
+        assert_eq!(-solver.solve(&position2), score);
+    }
+
+    #[test]
+    fn shared_table() {
+        let position = position("7422341735647741166133573473242566");
+        let mut first = ParallelAlphaBeta::new(7, 6, center_first(7), 2);
+        let score = first.solve(&position);
+
+        let mut second = ParallelAlphaBeta::new(7, 6, center_first(7), 1);
+        second.set_transposition_table(first.transposition_table());
+        assert_eq!(second.solve(&position), score);
+        assert!(Solver::<BitboardPositionWithOrdering>::explored_positions(&second) < Solver::<BitboardPositionWithOrdering>::explored_positions(&first));
+    }
+
+    #[test]
+    fn cancelled_search() {
+        let token = Arc::new(AtomicBool::new(true));
+        let mut solver = ParallelAlphaBeta::new(7, 6, center_first(7), 2);
+        solver.control().set_cancel_token(Arc::clone(&token));
+        assert_eq!(solver.try_solve(&BitboardPositionWithOrdering::new(7, 6)), None);
+        assert!(solver.control().is_cancelled());
+
+        // The table holds no result of the cancelled search
+        token.store(false, Ordering::Relaxed);
+        assert_eq!(solver.solve(&position("2252576253462244111563365343671351441")), -1);
+        assert!(!solver.control().is_cancelled());
+    }
+
+    #[test]
+    fn progress() {
+        let windows = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut solver = ParallelAlphaBeta::new(7, 6, (0..7).collect(), 2);
+        let windows2 = Arc::clone(&windows);
+        solver.control().set_progress_callback(move |progress| windows2.lock().unwrap().push(*progress));
+        solver.solve(&position("2252576253462244111563365343671351441"));
+
+        // The main thread may be stopped by another one before its last iteration
+        let windows = windows.lock().unwrap();
+        for pair in windows.windows(2) {
+            assert!(pair[0].alpha <= pair[1].alpha && pair[1].beta <= pair[0].beta);
+        }
+        assert!(windows.iter().all(|window| window.alpha <= -1 && -1 <= window.beta));
+    }
+
+    #[test]
+    fn thread_move_orders() {
+        let solver = ParallelAlphaBeta::<u64>::new(7, 6, center_first(7), 16);
+        assert_eq!(solver.thread_move_order(0), center_first(7));
+        assert_eq!(solver.thread_move_order(1), vec![2, 4, 1, 5, 0, 6, 3]);
+        assert_eq!(solver.thread_move_order(7), vec![6, 0, 5, 1, 4, 2, 3]);
+        assert_eq!(solver.nb_threads(), 16);
+    }
+}
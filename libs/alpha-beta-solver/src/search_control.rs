@@ -0,0 +1,127 @@
+use lib_game_board::{Position, Solver};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// State of a search, reported after each null window iteration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchProgress {
+    /// Bounds of the score proven so far.
+    pub alpha: i32,
+    pub beta: i32,
+    /// Positions explored by the solver, including those of its previous searches.
+    pub explored_positions: usize,
+    pub elapsed: Duration
+}
+
+/// Called with the progress of the search.
+pub type ProgressCallback = Box<dyn FnMut(&SearchProgress) + Send>;
+
+/// Cancellation token and progress callback of a solver.
+pub struct SearchControl {
+    cancel_token: Option<Arc<AtomicBool>>,
+    progress_callback: Option<ProgressCallback>,
+    /// Whether the token was seen set since the search started. Results of the search
+    /// are then meaningless, even if the token is cleared in the meantime.
+    cancelled: bool,
+    start: Instant
+}
+
+impl Default for SearchControl {
+    fn default() -> Self {
+        Self { cancel_token: None, progress_callback: None, cancelled: false, start: Instant::now() }
+    }
+}
+
+impl SearchControl {
+    /// Stops the search once `token` is set. The token is not cleared by the solver.
+    pub fn set_cancel_token(&mut self, token: Arc<AtomicBool>) {
+        self.cancel_token = Some(token);
+    }
+
+    pub fn set_progress_callback(&mut self, callback: impl FnMut(&SearchProgress) + Send + 'static) {
+        self.progress_callback = Some(Box::new(callback));
+    }
+
+    /// Whether the last search was cancelled.
+    pub fn is_cancelled(&mut self) -> bool {
+        if !self.cancelled {
+            self.cancelled = self.cancel_token.as_ref().is_some_and(|token| token.load(Ordering::Relaxed));
+        }
+        self.cancelled
+    }
+
+    pub(crate) fn cancel_token(&self) -> Option<Arc<AtomicBool>> {
+        self.cancel_token.clone()
+    }
+
+    /// Records a cancellation seen by another thread.
+    pub(crate) fn cancel(&mut self) {
+        self.cancelled = true;
+    }
+
+    pub(crate) fn start(&mut self) {
+        self.cancelled = false;
+        self.start = Instant::now();
+    }
+
+    pub(crate) fn report(&mut self, alpha: i32, beta: i32, explored_positions: usize) {
+        let elapsed = self.start.elapsed();
+        if let Some(callback) = &mut self.progress_callback {
+            callback(&SearchProgress { alpha, beta, explored_positions, elapsed });
+        }
+    }
+}
+
+/// Solvers whose searches can be cancelled and observed. Those searching by null windows
+/// report their progress after each window, the others only once they have the score.
+/// A cancelled `solve` returns a meaningless score: `try_solve` tells it apart, and the
+/// moves of `Analyser` are `None`.
+pub trait Controllable {
+    fn control(&mut self) -> &mut SearchControl;
+
+    /// Score of `position`, `None` when the search was cancelled before finding it.
+    fn try_solve<P: Position>(&mut self, position: &P) -> Option<i32> where Self: Solver<P> + Sized {
+        // Positions solved without searching keep no cancellation of a previous search
+        self.control().start();
+        let score = self.solve(position);
+        (!self.control().cancelled).then_some(score)
+    }
+}
+
+#[cfg(test)]
+mod search_control_tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn cancellation_is_kept_until_next_search() {
+        let token = Arc::new(AtomicBool::new(false));
+        let mut control = SearchControl::default();
+        assert!(!control.is_cancelled());
+        control.set_cancel_token(Arc::clone(&token));
+        assert!(!control.is_cancelled());
+
+        token.store(true, Ordering::Relaxed);
+        assert!(control.is_cancelled());
+        token.store(false, Ordering::Relaxed);
+        assert!(control.is_cancelled());
+        control.start();
+        assert!(!control.is_cancelled());
+    }
+
+    #[test]
+    fn progress() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let mut control = SearchControl::default();
+        control.report(-1, 1, 10);
+
+        let reports2 = Arc::clone(&reports);
+        control.set_progress_callback(move |progress| reports2.lock().unwrap().push(*progress));
+        control.start();
+        control.report(-2, 3, 42);
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!((reports[0].alpha, reports[0].beta, reports[0].explored_positions), (-2, 3, 42));
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+/// Budget of a search, after which it stops with the best result found so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchLimit {
+    Time(Duration),
+    /// Number of explored positions.
+    Nodes(usize)
+}
+
+impl SearchLimit {
+    pub(crate) fn halved(&self) -> Self {
+        self.divided(2)
+    }
+
+    /// Share of the limit given to each of `parts` searches.
+    pub(crate) fn divided(&self, parts: usize) -> Self {
+        match self {
+            SearchLimit::Time(duration) => SearchLimit::Time(*duration / parts.max(1) as u32),
+            SearchLimit::Nodes(nodes) => SearchLimit::Nodes(nodes / parts.max(1))
+        }
+    }
+}
+
+/// Result of a search within a `SearchLimit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitedSearch {
+    pub best_move: Option<usize>,
+    /// Bounds of the exact score of the position, equal when it was solved.
+    pub min: i32,
+    pub max: i32,
+    /// Depth of the last heuristic search completed, `None` when the position was solved.
+    /// `Some(0)` when the score was found, but the budget ran out before its move.
+    pub depth: Option<usize>,
+    /// Whether the search was cancelled: the bounds still hold, but `best_move` may be any legal move.
+    pub cancelled: bool
+}
+
+impl LimitedSearch {
+    /// Whether the position was solved, along with its best move.
+    pub fn is_exact(&self) -> bool {
+        self.min == self.max && self.depth.is_none() && !self.cancelled
+    }
+
+    /// Exact score when the position was solved, the middle of the bounds otherwise.
+    pub fn estimate(&self) -> i32 {
+        self.min + (self.max - self.min)/2
+    }
+}
+
+/// Tracks the resources spent by a search since it started.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Budget {
+    limit: SearchLimit,
+    start: Instant,
+    first_node: usize
+}
+
+impl Budget {
+    /// Budget of a search starting now, when `explored_positions` positions were already explored.
+    pub(crate) fn new(limit: SearchLimit, explored_positions: usize) -> Self {
+        Self { limit, start: Instant::now(), first_node: explored_positions }
+    }
+
+    /// Same start as `self`, with another limit.
+    pub(crate) fn with_limit(&self, limit: SearchLimit) -> Self {
+        Self { limit, ..*self }
+    }
+
+    /// Reading the clock is slow: time is only checked every 1024 positions.
+    #[allow(clippy::manual_is_multiple_of)] // `is_multiple_of` would need Rust 1.87
+    pub(crate) fn is_exhausted(&self, explored_positions: usize) -> bool {
+        match self.limit {
+            SearchLimit::Time(duration) => explored_positions % 1024 == 0 && self.start.elapsed() >= duration,
+            SearchLimit::Nodes(nodes) => explored_positions - self.first_node >= nodes
+        }
+    }
+}
+
+#[cfg(test)]
+mod search_limit_tests {
+    use super::*;
+
+    #[test]
+    fn nodes() {
+        let budget = Budget::new(SearchLimit::Nodes(10), 100);
+        assert!(!budget.is_exhausted(109));
+        assert!(budget.is_exhausted(110));
+        assert!(budget.with_limit(SearchLimit::Nodes(10).halved()).is_exhausted(105));
+        assert_eq!(SearchLimit::Nodes(10).divided(3), SearchLimit::Nodes(3));
+        assert_eq!(SearchLimit::Time(Duration::from_secs(6)).divided(0), SearchLimit::Time(Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn time() {
+        let budget = Budget::new(SearchLimit::Time(Duration::ZERO), 0);
+        assert!(budget.is_exhausted(1024));
+        // The clock is not read in between
+        assert!(!budget.is_exhausted(1025));
+        assert!(!Budget::new(SearchLimit::Time(Duration::from_secs(60)), 0).is_exhausted(1024));
+    }
+}
@@ -0,0 +1,169 @@
+use lib_game_board::bitboard::Bitboard;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub const TABLE_SIZE: usize = (1 << 23) + 9; // first prime greater than 2^23
+
+/// Keys are identified by their remainder modulo this prime, on top of their slot:
+/// by the Chinese remainder theorem, the table is exact for keys of up to 78 bits.
+const CHECK_MODULUS: u64 = (1 << 56) - 5; // last prime before 2^56
+
+/// The values of the solvers stay below 256 on every board the table is exact for.
+const VALUE_BITS: u32 = 8;
+
+/// Transposition table shared by several threads, or several solvers, without locks.
+/// Each slot is a single atomic word, where the check of the key is XORed over the value:
+/// a slot is always read as one thread wrote it, and XORing back the check of another key
+/// leaves bits above the value, so that the slot is read as empty.
+pub struct SharedTranspositionTable<B: Bitboard = u64> {
+    size: usize,
+    entries: Vec<AtomicU64>,
+    bitboard: PhantomData<B>
+}
+
+impl<B: Bitboard> SharedTranspositionTable<B> {
+    pub fn new(size: usize) -> Self {
+        assert_eq!(size % 2, 1, "SharedTranspositionTable size must be odd, but it is equal to {size}.");
+
+        Self {
+            size,
+            entries: (0..size).map(|_| AtomicU64::new(0)).collect(),
+            bitboard: PhantomData
+        }
+    }
+
+    /// Number of bits of the longest keys told apart by a table of `size` slots.
+    pub fn exact_key_bits(size: usize) -> usize {
+        (size.ilog2() + CHECK_MODULUS.ilog2()) as usize
+    }
+
+    fn index(&self, key: B) -> usize {
+        key.modulo(self.size as u64) as usize
+    }
+
+    /// Never 0, so that empty slots match no key.
+    fn check(key: B) -> u64 {
+        (key.modulo(CHECK_MODULUS) + 1) << VALUE_BITS
+    }
+
+    pub fn insert(&self, key: B, value: u16) {
+        debug_assert!(value >> VALUE_BITS == 0, "Value {value} does not fit in the table.");
+        self.entries[self.index(key)].store(Self::check(key) ^ value as u64, Ordering::Relaxed);
+    }
+
+    pub fn get(&self, key: B) -> Option<u16> {
+        let value = self.entries[self.index(key)].load(Ordering::Relaxed) ^ Self::check(key);
+        if value >> VALUE_BITS == 0 {
+            Some(value as u16)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod shared_transposition_table_tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn insert_get() {
+        let table: SharedTranspositionTable = SharedTranspositionTable::new(11);
+
+        table.insert(42, 21);
+        assert_eq!(table.get(42), Some(21));
+        assert_eq!(table.get(0), None);
+
+        // Values use the whole word below the check
+        table.insert(0, 255);
+        assert_eq!(table.get(0), Some(255));
+        table.insert(0, 0);
+        assert_eq!(table.get(0), Some(0));
+    }
+
+    #[test]
+    fn index_override() {
+        let table: SharedTranspositionTable = SharedTranspositionTable::new(11);
+
+        table.insert(10, 21);
+        table.insert(21, 22);
+        assert_eq!(table.get(21), Some(22));
+        assert_eq!(table.get(10), None);
+    }
+
+    #[test]
+    fn exact_keys() {
+        let table: SharedTranspositionTable<u128> = SharedTranspositionTable::new(11);
+
+        // Same slot and same remainder modulo 2^56: only the check modulo a prime tells them apart
+        let key = (1u128 << 77) + 5;
+        let other = key + 11 * (1 << 56);
+        table.insert(key, 3);
+        assert_eq!(table.get(key), Some(3));
+        assert_eq!(table.get(other), None);
+        // 9x7 keys need 72 bits
+        assert_eq!(SharedTranspositionTable::<u128>::exact_key_bits(TABLE_SIZE), 78);
+    }
+
+    #[test]
+    fn foreign_entry() {
+        let table: SharedTranspositionTable = SharedTranspositionTable::new(11);
+
+        // Whatever value a slot holds, the check of another key leaves bits above it
+        for value in [0, 1, 21, 255] {
+            table.insert(10, value);
+            assert_eq!(table.get(21), None);
+            assert_eq!(table.get(32), None);
+        }
+    }
+
+    #[test]
+    fn concurrent_writes() {
+        // Every key of the threads lands in the same slot, along with a value derived from the key
+        let table: Arc<SharedTranspositionTable> = Arc::new(SharedTranspositionTable::new(7));
+        let value = |key: u64| (key.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> 56) as u16;
+
+        let threads: Vec<_> = (0..4u64).map(|thread| {
+            let table = Arc::clone(&table);
+            std::thread::spawn(move || {
+                let mut hits = 0;
+                for round in 0..20_000u64 {
+                    let key = 7 * (thread * 100_000 + round);
+                    table.insert(key, value(key));
+                    for other in [key, 7 * ((thread + 1) % 4 * 100_000 + round)] {
+                        if let Some(found) = table.get(other) {
+                            assert_eq!(found, value(other), "Torn entry for key {}.", other);
+                            hits += 1;
+                        }
+                    }
+                }
+                hits
+            })
+        }).collect();
+
+        let hits: usize = threads.into_iter().map(|thread| thread.join().unwrap()).sum();
+        assert!(hits > 0);
+    }
+
+    #[test]
+    fn concurrent_writes_and_reads_of_many_keys() {
+        let table: Arc<SharedTranspositionTable<u128>> = Arc::new(SharedTranspositionTable::new(101));
+        let value = |key: u128| (key % 251) as u16;
+
+        std::thread::scope(|scope| {
+            for thread in 0..4u128 {
+                let table = &table;
+                scope.spawn(move || {
+                    for round in 0..50_000u128 {
+                        let key = (thread << 100) + round * 7919;
+                        if round % 3 == 0 {
+                            table.insert(key, value(key));
+                        } else if let Some(found) = table.get(key - 7919) {
+                            assert_eq!(found, value(key - 7919));
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
@@ -1,26 +1,28 @@
+use lib_game_board::bitboard::Bitboard;
+
 pub const TABLE_SIZE: usize = 10_000_000;
 
 #[derive(Debug)]
-pub struct SimpleTranspositionTable {
-    table: Vec<TableEntry>
+pub struct SimpleTranspositionTable<B: Bitboard = u64> {
+    table: Vec<TableEntry<B>>
 }
 
-impl SimpleTranspositionTable {
+impl<B: Bitboard> SimpleTranspositionTable<B> {
     pub fn new(size: usize) -> Self {
         assert!(size > 0);
         Self { table: vec![TableEntry::default(); size] }
     }
 
-    fn index(&self, key: u64) -> usize {
-        key as usize % self.table.len()
+    fn index(&self, key: B) -> usize {
+        key.modulo(self.table.len() as u64) as usize
     }
 
-    pub fn insert(&mut self, key: u64, val: u8) {
+    pub fn insert(&mut self, key: B, val: u8) {
         let index = self.index(key);
         self.table[index] = TableEntry::new(key, val);
     }
 
-    pub fn get(&self, key: u64) -> Option<u8> {
+    pub fn get(&self, key: B) -> Option<u8> {
         let index = self.index(key);
 
         if self.table[index].key() == key {
@@ -31,18 +33,18 @@ impl SimpleTranspositionTable {
     }
 }
 
-#[derive(Debug, Clone)]
-struct TableEntry {
-    key: u64,
+#[derive(Debug, Clone, Default)]
+struct TableEntry<B: Bitboard> {
+    key: B,
     val: Option<u8>
 }
 
-impl TableEntry {
-    pub fn new(key: u64, val: u8) -> Self {
+impl<B: Bitboard> TableEntry<B> {
+    pub fn new(key: B, val: u8) -> Self {
         Self { key, val: Some(val) }
     }
 
-    pub fn key(&self) -> u64 {
+    pub fn key(&self) -> B {
         self.key
     }
 
@@ -51,19 +53,13 @@ impl TableEntry {
     }
 }
 
-impl std::default::Default for TableEntry {
-    fn default() -> Self {
-        Self { key: 0, val: None }
-    }
-}
-
 #[cfg(test)]
 mod transposition_table_tests {
     use super::*;
 
     #[test]
     fn insert_get() {
-        let mut table = SimpleTranspositionTable::new(10);
+        let mut table: SimpleTranspositionTable = SimpleTranspositionTable::new(10);
 
         table.insert(42, 21);
         assert_eq!(table.get(42), Some(21));
@@ -71,7 +67,7 @@ mod transposition_table_tests {
 
     #[test]
     fn insert_get_index() {
-        let mut table = SimpleTranspositionTable::new(10);
+        let mut table: SimpleTranspositionTable = SimpleTranspositionTable::new(10);
 
         table.insert(10, 21);
         assert_eq!(table.get(0), None);
@@ -79,7 +75,7 @@ mod transposition_table_tests {
 
     #[test]
     fn index_override() {
-        let mut table = SimpleTranspositionTable::new(10);
+        let mut table: SimpleTranspositionTable = SimpleTranspositionTable::new(10);
 
         table.insert(10, 21);
         table.insert(20, 22);
@@ -0,0 +1,274 @@
+use lib_game_board::variant_position::{VariantPosition, VariantKey, Move, Outcome};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper
+}
+
+/// Weak Alpha-Beta solver of the rule variants: scores are 1 when the player to move
+/// wins, -1 when they lose and 0 for a draw.
+///
+/// Pops can bring a position back, which is a draw: the search stops there. Such a
+/// draw depends on the moves leading to the position, so it is never stored in the
+/// transposition table, nor any draw derived from it. Positions played before the
+/// solved one are not taken into account.
+pub struct VariantSolver {
+    explored_positions: usize,
+    transposition_table: HashMap<VariantKey, (i32, Bound)>,
+    /// Positions of the current line of play.
+    history: HashSet<VariantKey>
+}
+
+impl Default for VariantSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VariantSolver {
+    pub fn new() -> Self {
+        Self { explored_positions: 0, transposition_table: HashMap::new(), history: HashSet::new() }
+    }
+
+    /// Score of the position for the player to move.
+    pub fn solve(&mut self, position: &VariantPosition) -> i32 {
+        self.negamax(position, -1, 1).0
+    }
+
+    /// Returns the score of the position along with a move reaching it,
+    /// `None` when the game is over.
+    pub fn best_move(&mut self, position: &VariantPosition) -> (i32, Option<Move>) {
+        let score = self.solve(position);
+        self.history.insert(position.key());
+
+        let best_move = position.moves().into_iter().find(|&played| {
+            let mut position2 = position.clone();
+            position2.play(played);
+            self.child_score(position, &position2, -1, 1).0 == score
+        });
+
+        self.history.remove(&position.key());
+        (score, best_move)
+    }
+
+    pub fn explored_positions(&self) -> usize {
+        self.explored_positions
+    }
+
+    pub fn reset_explored_positions(&mut self) {
+        self.explored_positions = 0;
+    }
+
+    /// Returns the score, bounded by `alpha` and `beta`, and whether it depends on the history.
+    fn negamax(&mut self, position: &VariantPosition, mut alpha: i32, mut beta: i32) -> (i32, bool) {
+        self.explored_positions += 1;
+
+        match position.outcome() {
+            Some(Outcome::Win(player)) => return (if player == position.player_turn() { 1 } else { -1 }, false),
+            Some(Outcome::Draw) => return (0, false),
+            None => ()
+        }
+
+        let key = position.key();
+        if self.history.contains(&key) {
+            return (0, true);
+        }
+
+        if let Some(&(score, bound)) = self.transposition_table.get(&key) {
+            match bound {
+                Bound::Exact => return (score, false),
+                Bound::Lower => alpha = alpha.max(score),
+                Bound::Upper => beta = beta.min(score)
+            }
+            if alpha >= beta {
+                return (score, false);
+            }
+        }
+
+        let initial_alpha = alpha;
+        let mut best_score = -1;
+        let mut history_dependent = false;
+        self.history.insert(key);
+
+        for played in position.moves() {
+            let mut position2 = position.clone();
+            position2.play(played);
+
+            let (score, dependent) = self.child_score(position, &position2, alpha, beta);
+            history_dependent |= dependent;
+            best_score = best_score.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        self.history.remove(&key);
+
+        // A win or a loss is forced whatever the history: repetitions only give draws
+        history_dependent &= best_score == 0;
+        if !history_dependent {
+            let bound = if best_score <= initial_alpha {
+                Bound::Upper
+            } else if best_score >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+            self.transposition_table.insert(key, (best_score, bound));
+        }
+
+        (best_score, history_dependent)
+    }
+
+    /// Score of `child` for the player to move in `parent`, who may move again in Pop Ten.
+    fn child_score(&mut self, parent: &VariantPosition, child: &VariantPosition, alpha: i32, beta: i32) -> (i32, bool) {
+        if child.player_turn() == parent.player_turn() {
+            self.negamax(child, alpha, beta)
+        } else {
+            let (score, dependent) = self.negamax(child, -beta, -alpha);
+            (-score, dependent)
+        }
+    }
+}
+
+#[cfg(test)]
+mod variant_solver_tests {
+    use super::*;
+    use lib_game_board::{WeakSolver, Cell};
+    use lib_game_board::variant_position::Rules;
+    use lib_game_board::bitboard_position_with_ordering::BitboardPositionWithOrdering;
+    use crate::final_alpha_beta::FinalAlphaBeta;
+    use std::collections::hash_map::Entry;
+
+    #[test]
+    fn standard_as_final_alpha_beta() {
+        for (width, height, win_length) in [(4, 4, 4), (4, 4, 3), (5, 4, 3), (3, 3, 2)] {
+            let position = VariantPosition::new(Rules { win_length, ..Rules::standard(width, height) });
+            let score = VariantSolver::new().solve(&position);
+
+            let mut solver = FinalAlphaBeta::new(width, height, lib_game_board::center_first(width));
+            let expected = solver.weak_solve(&BitboardPositionWithOrdering::with_win_length(width, height, win_length));
+            assert_eq!(score, expected.signum(), "{}x{} connect-{}", width, height, win_length);
+        }
+    }
+
+    #[test]
+    fn winning_pop() {
+        // Red wins by popping column 3, which also aligns yellow
+        let mut position = VariantPosition::new(Rules { win_length: 3, ..Rules::pop_out(3, 3) });
+        for column in [2, 0, 0, 1, 1, 2, 2, 0] {
+            position.play(Move::Drop(column));
+        }
+        let (score, best_move) = VariantSolver::new().best_move(&position);
+        assert_eq!(score, 1);
+        assert!(position.can_play(best_move.unwrap()));
+
+        position.play(Move::Pop(2));
+        assert_eq!(position.outcome(), Some(Outcome::Win(Cell::Red)));
+    }
+
+    /// Retrograde analysis of every reachable position: positions that cannot be
+    /// forced to a win or a loss are draws.
+    fn retrograde_score(root: &VariantPosition) -> i32 {
+        let mut positions = HashMap::from([(root.key(), root.clone())]);
+        let mut to_visit = vec![root.clone()];
+        while let Some(position) = to_visit.pop() {
+            for played in position.moves() {
+                let mut position2 = position.clone();
+                position2.play(played);
+                if let Entry::Vacant(entry) = positions.entry(position2.key()) {
+                    entry.insert(position2.clone());
+                    to_visit.push(position2);
+                }
+            }
+        }
+
+        let mut scores: HashMap<VariantKey, i32> = positions.iter()
+            .filter_map(|(&key, position)| match position.outcome() {
+                Some(Outcome::Win(player)) => Some((key, if player == position.player_turn() { 1 } else { -1 })),
+                Some(Outcome::Draw) => Some((key, 0)),
+                None => None
+            })
+            .collect();
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (&key, position) in &positions {
+                if scores.contains_key(&key) {
+                    continue;
+                }
+                let children: Vec<Option<i32>> = position.moves().into_iter().map(|played| {
+                    let mut position2 = position.clone();
+                    position2.play(played);
+                    let sign = if position2.player_turn() == position.player_turn() { 1 } else { -1 };
+                    scores.get(&position2.key()).map(|score| sign * score)
+                }).collect();
+
+                if children.contains(&Some(1)) {
+                    scores.insert(key, 1);
+                    changed = true;
+                } else if children.iter().all(|&score| score == Some(-1)) {
+                    scores.insert(key, -1);
+                    changed = true;
+                }
+            }
+        }
+
+        scores.get(&root.key()).copied().unwrap_or(0)
+    }
+
+    #[test]
+    fn same_as_retrograde_analysis() {
+        let rules = [
+            Rules { win_length: 2, ..Rules::pop_out(2, 2) },
+            Rules { win_length: 2, ..Rules::pop_out(3, 2) },
+            Rules { win_length: 3, ..Rules::pop_out(3, 2) },
+            Rules { win_length: 3, ..Rules::pop_out(2, 3) },
+            Rules { win_length: 3, ..Rules::pop_out(3, 3) },
+            Rules { win_length: 2, ..Rules::pop_ten(3, 2, 2) },
+            Rules { win_length: 2, ..Rules::pop_ten(2, 2, 3) },
+            Rules { win_length: 3, ..Rules::pop_ten(3, 3, 2) },
+            Rules::five_in_a_row(3, 3)
+        ];
+        for rules in rules {
+            let position = VariantPosition::new(rules);
+            let mut solver = VariantSolver::new();
+            assert_eq!(solver.solve(&position), retrograde_score(&position), "{:?}", rules);
+
+            // The transposition table filled by the first search stays valid
+            for played in position.moves() {
+                let mut position2 = position.clone();
+                position2.play(played);
+                assert_eq!(solver.solve(&position2), retrograde_score(&position2), "{:?} after {}", rules, played);
+            }
+        }
+    }
+
+    #[test]
+    fn repetition_is_a_draw() {
+        // Each player can only pop and drop back in their own full column, repeating the position
+        let mut position = VariantPosition::new(Rules { win_length: 3, ..Rules::pop_out(2, 2) });
+        for column in [0, 1, 0, 1] {
+            position.play(Move::Drop(column));
+        }
+        let mut solver = VariantSolver::new();
+        assert_eq!(solver.solve(&position), 0);
+        // Nothing depending on the line of play was stored
+        assert!(solver.transposition_table.is_empty());
+    }
+
+    #[test]
+    fn pop_ten_extra_turns() {
+        let position = VariantPosition::new(Rules { win_length: 2, ..Rules::pop_ten(3, 2, 2) });
+        let mut solver = VariantSolver::new();
+        let score = solver.solve(&position);
+        let (best_score, best_move) = solver.best_move(&position);
+        assert_eq!(best_score, score);
+        assert!(matches!(best_move, Some(Move::Drop(_))));
+    }
+}
@@ -13,14 +13,14 @@ impl Benchmark
         Self { test_sets }
     }
 
-    pub fn benchmark<P: Position + Clone>(&self, solver: &mut impl Solver) -> Vec<Statistics> {
+    pub fn benchmark<P: Position + Clone>(&self, solver: &mut impl Solver<P>) -> Vec<Statistics> {
         self.test_sets
             .iter()
             .map(|test| test.test_solver::<P>(solver))
             .collect()
     }
 
-    pub fn benchmark_weak<P: Position + Clone>(&self, solver: &mut impl WeakSolver) -> Vec<Statistics> {
+    pub fn benchmark_weak<P: Position + Clone>(&self, solver: &mut impl WeakSolver<P>) -> Vec<Statistics> {
         self.test_sets
             .iter()
             .map(|test| test.test_weak_solver::<P>(solver))
@@ -43,8 +43,8 @@ mod benchmark_tests {
             Self { value }
         }
     }
-    impl Solver for TestSolver {
-        fn solve(&mut self, _position: &impl Position) -> i32{
+    impl<P: Position> Solver<P> for TestSolver {
+        fn solve(&mut self, _position: &P) -> i32{
             self.value
         }
 
@@ -52,12 +52,10 @@ mod benchmark_tests {
             0
         }
 
-        fn reset_explored_positions(&mut self) {
-            ()
-        }
+        fn reset_explored_positions(&mut self) {}
     }
-    impl WeakSolver for TestSolver {
-        fn weak_solve(&mut self, _position: &impl Position) -> i32{
+    impl<P: Position> WeakSolver<P> for TestSolver {
+        fn weak_solve(&mut self, _position: &P) -> i32{
             self.value
         }
 
@@ -65,9 +63,7 @@ mod benchmark_tests {
             0
         }
 
-        fn reset_explored_positions(&mut self) {
-            ()
-        }
+        fn reset_explored_positions(&mut self) {}
     }
 
     mod load {
@@ -76,11 +72,11 @@ mod benchmark_tests {
         #[test]
         #[allow(non_snake_case)]
         fn load_L1_R1() {
-            let test_set = TestSet::new(1, 1, &".", None);
+            let test_set = TestSet::new(1, 1, ".", None);
             
             assert_eq!(
                 test_set.games_moves()[0],
-                (SequencePosition::from(&"32164625".to_string()), 11)
+                (SequencePosition::parse("32164625").unwrap(), 11)
             )
         }
     }
@@ -90,7 +86,7 @@ mod benchmark_tests {
 
         #[test]
         fn test_test_solver_0() {
-            let test_set = TestSet::new(1, 1, &".", None);
+            let test_set = TestSet::new(1, 1, ".", None);
             let mut solver = TestSolver::new(0);
 
             assert_eq!(
@@ -101,7 +97,7 @@ mod benchmark_tests {
 
         #[test]
         fn test_test_solver_11() {
-            let test_set = TestSet::new(1, 1, &".", None);
+            let test_set = TestSet::new(1, 1, ".", None);
             let mut solver = TestSolver::new(11);
 
             let correctly_solved: usize = test_set
@@ -120,7 +116,7 @@ mod benchmark_tests {
 
         #[test]
         fn test_test_solver_0() {
-            let test_set = TestSet::new(1, 1, &".", None);
+            let test_set = TestSet::new(1, 1, ".", None);
             let mut solver = TestSolver::new(0);
 
             assert_eq!(
@@ -131,7 +127,7 @@ mod benchmark_tests {
 
         #[test]
         fn test_test_solver_1() {
-            let test_set = TestSet::new(1, 1, &".", None);
+            let test_set = TestSet::new(1, 1, ".", None);
             let mut solver = TestSolver::new(1);
 
             let correctly_solved: usize = test_set
@@ -144,4 +140,110 @@ mod benchmark_tests {
             assert_eq!(correctly_solved, 723);
         }
     }
-}
\ No newline at end of file
+
+    mod cross_check {
+        use super::*;
+        use lib_alpha_beta_solver::alpha_beta_with_lower_bound_transposition::AlphaBetaWithLowerBoundTransposition;
+        use lib_game_board::{bitboard_position_with_ordering::BitboardPositionWithOrdering, stack_position::StackPosition};
+
+        #[test]
+        #[allow(non_snake_case)]
+        fn grid_stack_bitboard_L3_R1() {
+            let benchmark = Benchmark::new(vec![TestSet::new(3, 1, ".", Some(20))]);
+            let move_order = vec![3, 4, 2, 5, 1, 6, 0];
+
+            let grid_stats = benchmark.benchmark::<GridPosition>(&mut AlphaBetaWithLowerBoundTransposition::new(move_order.clone()));
+            let stack_stats = benchmark.benchmark::<StackPosition>(&mut AlphaBetaWithLowerBoundTransposition::new(move_order.clone()));
+            let bitboard_stats = benchmark.benchmark::<BitboardPositionWithOrdering>(&mut AlphaBetaWithLowerBoundTransposition::new(move_order));
+
+            assert_eq!(grid_stats[0].accuracy(), 1.0);
+            assert_eq!(grid_stats[0].results(), bitboard_stats[0].results());
+            assert_eq!(grid_stats[0].explored_positions_nb(), bitboard_stats[0].explored_positions_nb());
+            assert_eq!(stack_stats[0].explored_positions_nb(), bitboard_stats[0].explored_positions_nb());
+        }
+    }
+
+    mod canonical_key {
+        use super::*;
+        use lib_alpha_beta_solver::alpha_beta_with_lower_bound_transposition::AlphaBetaWithLowerBoundTransposition;
+        use lib_game_board::{KeyedPosition, AnticipatingPosition, BitboardMovePosition, Cell, sequence_position::PositionError};
+        use lib_game_board::bitboard_position_with_ordering::BitboardPositionWithOrdering;
+
+        /// Bitboard whose mirrored positions do not share their key.
+        #[derive(Clone)]
+        struct RawKeyPosition(BitboardPositionWithOrdering);
+
+        impl Position for RawKeyPosition {
+            fn player_turn(&self) -> Cell { self.0.player_turn() }
+            fn width(&self) -> usize { self.0.width() }
+            fn height(&self) -> usize { self.0.height() }
+            fn can_play(&self, column: usize) -> bool { self.0.can_play(column) }
+            fn play(&mut self, column: usize) { self.0.play(column) }
+            fn is_winning_move(&self, column: usize) -> bool { self.0.is_winning_move(column) }
+            fn nb_moves(&self) -> usize { self.0.nb_moves() }
+            fn win_length(&self) -> usize { self.0.win_length() }
+            fn from_seq_with_win_length(width: usize, height: usize, win_length: usize, sequence: &SequencePosition) -> Result<Self, PositionError> {
+                BitboardPositionWithOrdering::from_seq_with_win_length(width, height, win_length, sequence).map(RawKeyPosition)
+            }
+        }
+
+        impl KeyedPosition for RawKeyPosition {
+            type Bitboard = u64;
+
+            fn key(&self) -> u64 { self.0.key() }
+            fn canonical_key(&self) -> u64 { self.0.key() }
+        }
+
+        impl AnticipatingPosition for RawKeyPosition {
+            fn can_win_next(&self) -> bool { self.0.can_win_next() }
+            fn possible_non_loosing_moves(&self) -> u64 { self.0.possible_non_loosing_moves() }
+        }
+
+        impl BitboardMovePosition for RawKeyPosition {
+            fn move_score(&self, move_bit: u64) -> usize { self.0.move_score(move_bit) }
+            fn play_move(&mut self, move_bit: u64) { self.0.play_move(move_bit) }
+        }
+
+        /// Positions explored to solve the mirror of each game, right after the game itself.
+        fn mirrored_games_explored_positions<P: BitboardMovePosition + Clone>(test_set: &TestSet) -> usize {
+            let mut solver = AlphaBetaWithLowerBoundTransposition::new(vec![3, 4, 2, 5, 1, 6, 0]);
+            let mut explored_positions = 0;
+
+            for (sequence, score) in test_set.games_moves() {
+                let mirrored = sequence.sequence().iter().map(|column| (b'0' + 8 - *column as u8) as char).collect::<String>();
+                let mirrored = SequencePosition::parse(&mirrored).unwrap();
+
+                Solver::<P>::reset_explored_positions(&mut solver);
+                assert_eq!(solver.solve(&P::from_seq(sequence).unwrap()), *score);
+                let before = Solver::<P>::explored_positions(&solver);
+                assert_eq!(solver.solve(&P::from_seq(&mirrored).unwrap()), *score);
+                explored_positions += Solver::<P>::explored_positions(&solver) - before;
+            }
+
+            explored_positions
+        }
+
+        #[test]
+        #[allow(non_snake_case)]
+        fn fewer_explored_positions_L1_R1() {
+            let benchmark = Benchmark::new(vec![TestSet::new(1, 1, ".", Some(3))]);
+            let move_order = vec![3, 4, 2, 5, 1, 6, 0];
+
+            let canonical_stats = benchmark.benchmark::<BitboardPositionWithOrdering>(&mut AlphaBetaWithLowerBoundTransposition::new(move_order.clone()));
+            let raw_stats = benchmark.benchmark::<RawKeyPosition>(&mut AlphaBetaWithLowerBoundTransposition::new(move_order));
+
+            assert_eq!(canonical_stats[0].results(), raw_stats[0].results());
+            assert!(canonical_stats[0].explored_positions_nb().iter().sum::<usize>() < raw_stats[0].explored_positions_nb().iter().sum());
+        }
+
+        #[test]
+        #[allow(non_snake_case)]
+        fn mirrored_games_L2_R1() {
+            let test_set = TestSet::new(2, 1, ".", Some(10));
+            let canonical = mirrored_games_explored_positions::<BitboardPositionWithOrdering>(&test_set);
+            let raw = mirrored_games_explored_positions::<RawKeyPosition>(&test_set);
+            // Re-solving an already solved position is almost free
+            assert!(canonical * 4 < raw);
+        }
+    }
+}
@@ -6,11 +6,12 @@ use lib_alpha_beta_solver::{
     alpha_beta_with_ordering::AlphaBetaWithOrdering,
     alpha_beta_with_optimised_transposition::AlphaBetaWithOptimisedTransposition,
     alpha_beta_with_lower_bound_transposition::AlphaBetaWithLowerBoundTransposition,
-    alpha_beta_with_opening_book::AlphaBetaWithOpeningBook
+    alpha_beta_with_opening_book::AlphaBetaWithOpeningBook,
+    final_alpha_beta::FinalAlphaBeta,
+    parallel_alpha_beta::ParallelAlphaBeta
 };
 use lib_game_board::{
-    Solver, 
-    WeakSolver, 
+    center_first,
     grid_position::GridPosition, 
     stack_position::StackPosition, 
     bitboard_position::BitboardPosition, 
@@ -25,194 +26,121 @@ pub mod statistics;
 pub mod test_set;
 pub mod benchmark;
 
+/// Benchmarks `$solver` on the position representation named by `$position_string`.
+/// Only the listed representations are accepted: each solver is paired with
+/// the positions providing the capabilities it requires.
+macro_rules! benchmark_with {
+    ($benchmark:ident, $solver:ident, $weak:ident, $position_string:ident, { $($name:literal => $position:ty),+ }) => {
+        match $position_string {
+            $($name => if $weak {
+                $benchmark.benchmark_weak::<$position>(&mut $solver)
+            } else {
+                $benchmark.benchmark::<$position>(&mut $solver)
+            },)+
+            _ => panic!("Unknown position name.")
+        }
+    };
+}
+
 
 pub fn run_benchmark(solver_string: &str, weak_string: &str, position_string: &str, move_ordering_string: &str, length: usize, rating: usize, games_number: Option<usize>) {
+    // The test sets are played on the standard board
+    let (width, height) = (7, 6);
     let move_ordering = match move_ordering_string {
-        "left_to_right" => (0..7).collect(),
-        "center_first" => vec![3, 4, 2, 5, 1, 6, 0],
+        "left_to_right" => (0..width).collect(),
+        "center_first" => center_first(width),
         _ => { assert!(solver_string == "min_max", "Unknown move ordering."); vec![] }
     };
 
-    let test_sets = vec![TestSet::new(length, rating, &"libs/benchmark", games_number)];
+    let test_sets = vec![TestSet::new(length, rating, "libs/benchmark", games_number)];
     let benchmark = Benchmark::new(test_sets);
 
-    println!("Loading solver...");
-    let mut solver: AllowedSolver = match solver_string {
-        "min_max" => AllowedSolver::MinMaxSolver(MinMaxSolver::new()),
-        "alpha_beta" => AllowedSolver::AlphaBetaSolver(AlphaBetaSolver::new(move_ordering)),
-        "alpha_beta_with_transposition" => AllowedSolver::AlphaBetaWithTransposition(AlphaBetaWithTransposition::new(move_ordering)),
-        "alpha_beta_with_iterative_deepening" => AllowedSolver::AlphaBetaWithIterativeDeepening(AlphaBetaWithIterativeDeepening::new(move_ordering)),
-        "anticipating_alpha_beta" => AllowedSolver::AnticipatingAlphaBeta(AnticipatingAlphaBeta::new(move_ordering)),
-        "alpha_beta_with_ordering" => AllowedSolver::AlphaBetaWithOrdering(AlphaBetaWithOrdering::new(move_ordering)),
-        "alpha_beta_with_optimised_transposition" => AllowedSolver::AlphaBetaWithOptimisedTransposition(AlphaBetaWithOptimisedTransposition::new(move_ordering)),
-        "alpha_beta_with_lower_bound_transposition" => AllowedSolver::AlphaBetaWithLowerBoundTransposition(AlphaBetaWithLowerBoundTransposition::new(move_ordering)),
-        "alpha_beta_with_opening_book" => {
-            let mut solver = AlphaBetaWithOpeningBook::new(7, 6, move_ordering);
-            solver.load_opening_book("libs/alpha-beta-solver/opening-books/7x6.book");
-            AllowedSolver::AlphaBetaWithOpeningBook(solver)
-        },
-        _ => panic!("Unknown solver name.")
-    };
-
-
     println!("\n\nSelected arguments:");
     println!("\t- Solver: {}", solver_string);
     println!("\t- Solving type: {}", weak_string);
     println!("\t- Move ordering: {}", move_ordering_string);
     println!("\t- Test set: L{} R{}", length, rating);
-    println!("");
+    println!();
 
+    let weak = match weak_string {
+        "strong" => false,
+        "weak" => true,
+        _ => panic!("Unknown weak/strong argument.")
+    };
+
+    println!("Loading solver...");
     let now = Instant::now();
 
-    let stats: Vec<Statistics> = 
-    if weak_string == "strong" { 
-        match position_string {
-            "grid" => benchmark.benchmark::<GridPosition>(&mut solver),
-            "stack" => benchmark.benchmark::<StackPosition>(&mut solver),
-            "bitboard" => 
-            if solver_string == "anticipating_alpha_beta" { 
-                benchmark.benchmark::<AnticipatingBitboardPosition>(&mut solver) 
-            } else if solver_string == "alpha_beta_with_ordering" 
-                || solver_string == "alpha_beta_with_optimised_transposition" 
-                || solver_string == "alpha_beta_with_lower_bound_transposition"
-                || solver_string == "alpha_beta_with_opening_book" 
-            { 
-                benchmark.benchmark::<BitboardPositionWithOrdering>(&mut solver) 
-            } else { 
-                benchmark.benchmark::<BitboardPosition>(&mut solver) 
-            },
-            _ => panic!("Unknown position name.")
-        }
-    } else if weak_string == "weak" {
-        assert_ne!(solver_string, "min_max", "MinMax solver does not implement weak solving.");
-        match position_string {
-            "grid" => benchmark.benchmark_weak::<GridPosition>(&mut solver),
-            "stack" => benchmark.benchmark_weak::<StackPosition>(&mut solver),
-            "bitboard" => 
-            if solver_string == "anticipating_alpha_beta" { 
-                benchmark.benchmark_weak::<AnticipatingBitboardPosition>(&mut solver) 
-            } else if solver_string == "alpha_beta_with_ordering" 
-                || solver_string == "alpha_beta_with_optimised_transposition" 
-                || solver_string == "alpha_beta_with_lower_bound_transposition"
-                || solver_string == "alpha_beta_with_opening_book" 
-            { 
-                benchmark.benchmark_weak::<BitboardPositionWithOrdering>(&mut solver) 
-            } else { 
-                benchmark.benchmark_weak::<BitboardPosition>(&mut solver) 
-            },
-            _ => panic!("Unknown position name.")
-        }
-    } else {
-        panic!("Unknown weak/strong argument.")
+    let stats: Vec<Statistics> = match solver_string {
+        "min_max" => {
+            assert!(!weak, "MinMax solver does not implement weak solving.");
+            let mut solver = MinMaxSolver::new();
+            match position_string {
+                "grid" => benchmark.benchmark::<GridPosition>(&mut solver),
+                "stack" => benchmark.benchmark::<StackPosition>(&mut solver),
+                "bitboard" => benchmark.benchmark::<BitboardPosition>(&mut solver),
+                _ => panic!("Unknown position name.")
+            }
+        },
+        "alpha_beta" => {
+            let mut solver = AlphaBetaSolver::new(move_ordering);
+            benchmark_with!(benchmark, solver, weak, position_string, { "grid" => GridPosition, "stack" => StackPosition, "bitboard" => BitboardPosition })
+        },
+        "alpha_beta_with_transposition" => {
+            let mut solver = AlphaBetaWithTransposition::new(move_ordering);
+            benchmark_with!(benchmark, solver, weak, position_string, { "grid" => GridPosition, "stack" => StackPosition, "bitboard" => BitboardPosition })
+        },
+        "alpha_beta_with_iterative_deepening" => {
+            let mut solver = AlphaBetaWithIterativeDeepening::new(move_ordering);
+            benchmark_with!(benchmark, solver, weak, position_string, { "grid" => GridPosition, "stack" => StackPosition, "bitboard" => BitboardPosition })
+        },
+        "anticipating_alpha_beta" => {
+            let mut solver = AnticipatingAlphaBeta::new(move_ordering);
+            benchmark_with!(benchmark, solver, weak, position_string, { "grid" => GridPosition, "stack" => StackPosition, "bitboard" => AnticipatingBitboardPosition })
+        },
+        "alpha_beta_with_ordering" => {
+            let mut solver = AlphaBetaWithOrdering::new(move_ordering);
+            benchmark_with!(benchmark, solver, weak, position_string, { "grid" => GridPosition, "stack" => StackPosition, "bitboard" => BitboardPositionWithOrdering })
+        },
+        "alpha_beta_with_optimised_transposition" => {
+            let mut solver = AlphaBetaWithOptimisedTransposition::new(move_ordering);
+            benchmark_with!(benchmark, solver, weak, position_string, { "grid" => GridPosition, "stack" => StackPosition, "bitboard" => BitboardPositionWithOrdering })
+        },
+        "alpha_beta_with_lower_bound_transposition" => {
+            let mut solver = AlphaBetaWithLowerBoundTransposition::new(move_ordering);
+            benchmark_with!(benchmark, solver, weak, position_string, { "grid" => GridPosition, "stack" => StackPosition, "bitboard" => BitboardPositionWithOrdering })
+        },
+        "alpha_beta_with_opening_book" => {
+            let mut solver = AlphaBetaWithOpeningBook::new(width, height, move_ordering);
+            if let Err(error) = solver.load_opening_book("libs/alpha-beta-solver/opening-books/7x6.book") {
+                println!("[WARNING] {}: benchmarking without opening book.", error);
+            }
+            benchmark_with!(benchmark, solver, weak, position_string, { "grid" => GridPosition, "stack" => StackPosition, "bitboard" => BitboardPositionWithOrdering })
+        },
+        "final_alpha_beta" => {
+            let mut solver = FinalAlphaBeta::new(width, height, move_ordering);
+            if let Err(error) = solver.load_opening_book("libs/alpha-beta-solver/opening-books/7x6.book") {
+                println!("[WARNING] {}: benchmarking without opening book.", error);
+            }
+            benchmark_with!(benchmark, solver, weak, position_string, { "grid" => GridPosition, "stack" => StackPosition, "bitboard" => BitboardPositionWithOrdering })
+        },
+        parallel if parallel.starts_with("parallel_alpha_beta") => {
+            // The number of threads may follow the name, such as `parallel_alpha_beta_8`
+            let nb_threads = match parallel.strip_prefix("parallel_alpha_beta_") {
+                Some(nb_threads) => nb_threads.parse().expect("Invalid number of threads."),
+                None => std::thread::available_parallelism().map_or(1, |nb_threads| nb_threads.get())
+            };
+            println!("Searching with {} threads.", nb_threads);
+            let mut solver = ParallelAlphaBeta::new(width, height, move_ordering, nb_threads);
+            if let Err(error) = solver.load_opening_book("libs/alpha-beta-solver/opening-books/7x6.book") {
+                println!("[WARNING] {}: benchmarking without opening book.", error);
+            }
+            benchmark_with!(benchmark, solver, weak, position_string, { "grid" => GridPosition, "stack" => StackPosition, "bitboard" => BitboardPositionWithOrdering })
+        },
+        _ => panic!("Unknown solver name.")
     };
 
     println!("Benchmark done in {:?}.\n", now.elapsed());
 
     println!("Benchmark results:\n   {}", stats[0]);
 }
-
-enum AllowedSolver {
-    MinMaxSolver(MinMaxSolver),
-    AlphaBetaSolver(AlphaBetaSolver),
-    AlphaBetaWithTransposition(AlphaBetaWithTransposition),
-    AlphaBetaWithIterativeDeepening(AlphaBetaWithIterativeDeepening),
-    AnticipatingAlphaBeta(AnticipatingAlphaBeta),
-    AlphaBetaWithOrdering(AlphaBetaWithOrdering),
-    AlphaBetaWithOptimisedTransposition(AlphaBetaWithOptimisedTransposition),
-    AlphaBetaWithLowerBoundTransposition(AlphaBetaWithLowerBoundTransposition),
-    AlphaBetaWithOpeningBook(AlphaBetaWithOpeningBook)
-}
-
-impl Solver for AllowedSolver {
-    fn solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
-        use AllowedSolver::*;
-        match self {
-            MinMaxSolver(ref mut solver) => solver.solve(position),
-            AlphaBetaSolver(ref mut solver) => solver.solve(position),
-            AlphaBetaWithTransposition(ref mut solver) => solver.solve(position),
-            AlphaBetaWithIterativeDeepening(ref mut solver) => solver.solve(position),
-            AnticipatingAlphaBeta(ref mut solver) => solver.solve(position),
-            AlphaBetaWithOrdering(ref mut solver) => solver.solve(position),
-            AlphaBetaWithOptimisedTransposition(ref mut solver) => solver.solve(position),
-            AlphaBetaWithLowerBoundTransposition(ref mut solver) => solver.solve(position),
-            AlphaBetaWithOpeningBook(ref mut solver) => solver.solve(position)
-        }
-    }
-
-    fn explored_positions(&self) -> usize {
-        use AllowedSolver::*;
-        match self {
-            MinMaxSolver(solver) => Solver::explored_positions(solver),
-            AlphaBetaSolver(solver) => Solver::explored_positions(solver),
-            AlphaBetaWithTransposition(solver) => Solver::explored_positions(solver),
-            AlphaBetaWithIterativeDeepening(solver) => Solver::explored_positions(solver),
-            AnticipatingAlphaBeta(solver) => Solver::explored_positions(solver),
-            AlphaBetaWithOrdering(solver) => Solver::explored_positions(solver),
-            AlphaBetaWithOptimisedTransposition(solver) => Solver::explored_positions(solver),
-            AlphaBetaWithLowerBoundTransposition(solver) => Solver::explored_positions(solver),
-            AlphaBetaWithOpeningBook(solver) => Solver::explored_positions(solver)
-        }
-    }
-
-    fn reset_explored_positions(&mut self) {
-        use AllowedSolver::*;
-        match self {
-            MinMaxSolver(ref mut solver) => Solver::explored_positions(solver),
-            AlphaBetaSolver(ref mut solver) => Solver::explored_positions(solver),
-            AlphaBetaWithTransposition(ref mut solver) => Solver::explored_positions(solver),
-            AlphaBetaWithIterativeDeepening(ref mut solver) => Solver::explored_positions(solver),
-            AnticipatingAlphaBeta(ref mut solver) => Solver::explored_positions(solver),
-            AlphaBetaWithOrdering(ref mut solver) => Solver::explored_positions(solver),
-            AlphaBetaWithOptimisedTransposition(ref mut solver) => Solver::explored_positions(solver),
-            AlphaBetaWithLowerBoundTransposition(ref mut solver) => Solver::explored_positions(solver),
-            AlphaBetaWithOpeningBook(ref mut solver) => Solver::explored_positions(solver)
-        };
-    }
-}
-
-impl WeakSolver for AllowedSolver {
-    fn weak_solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
-        use AllowedSolver::*;
-        match self {
-            MinMaxSolver(_) => panic!("MinMaxSolver does not implement WeakSolver trait."),
-            AlphaBetaSolver(ref mut solver) => solver.weak_solve(position),
-            AlphaBetaWithTransposition(ref mut solver) => solver.weak_solve(position),
-            AlphaBetaWithIterativeDeepening(ref mut solver) => solver.weak_solve(position),
-            AnticipatingAlphaBeta(ref mut solver) => solver.weak_solve(position),
-            AlphaBetaWithOrdering(ref mut solver) => solver.weak_solve(position),
-            AlphaBetaWithOptimisedTransposition(ref mut solver) => solver.weak_solve(position),
-            AlphaBetaWithLowerBoundTransposition(ref mut solver) => solver.weak_solve(position),
-            AlphaBetaWithOpeningBook(ref mut solver) => solver.weak_solve(position)
-        }
-    }
-
-    fn explored_positions(&self) -> usize {
-        use AllowedSolver::*;
-        match self {
-            MinMaxSolver(_) => panic!("MinMaxSolver does not implement WeakSolver trait."),
-            AlphaBetaSolver(solver) => WeakSolver::explored_positions(solver),
-            AlphaBetaWithTransposition(solver) => WeakSolver::explored_positions(solver),
-            AlphaBetaWithIterativeDeepening(solver) => WeakSolver::explored_positions(solver),
-            AnticipatingAlphaBeta(solver) => WeakSolver::explored_positions(solver),
-            AlphaBetaWithOrdering(solver) => WeakSolver::explored_positions(solver),
-            AlphaBetaWithOptimisedTransposition(solver) => WeakSolver::explored_positions(solver),
-            AlphaBetaWithLowerBoundTransposition(solver) => WeakSolver::explored_positions(solver),
-            AlphaBetaWithOpeningBook(solver) => WeakSolver::explored_positions(solver)
-        }
-    }
-
-    fn reset_explored_positions(&mut self) {
-        use AllowedSolver::*;
-        match self {
-            MinMaxSolver(_) => panic!("MinMaxSolver does not implement WeakSolver trait."),
-            AlphaBetaSolver(ref mut solver) => WeakSolver::reset_explored_positions(solver),
-            AlphaBetaWithTransposition(ref mut solver) => WeakSolver::reset_explored_positions(solver),
-            AlphaBetaWithIterativeDeepening(ref mut solver) => WeakSolver::reset_explored_positions(solver),
-            AnticipatingAlphaBeta(ref mut solver) => WeakSolver::reset_explored_positions(solver),
-            AlphaBetaWithOrdering(ref mut solver) => WeakSolver::reset_explored_positions(solver),
-            AlphaBetaWithOptimisedTransposition(ref mut solver) => WeakSolver::reset_explored_positions(solver),
-            AlphaBetaWithLowerBoundTransposition(ref mut solver) => WeakSolver::reset_explored_positions(solver),
-            AlphaBetaWithOpeningBook(ref mut solver) => WeakSolver::reset_explored_positions(solver)
-        }
-    }
-}
\ No newline at end of file
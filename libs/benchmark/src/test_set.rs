@@ -16,8 +16,8 @@ impl TestSet
 {
     pub fn new(length: usize, rating: usize, datasets_path: &str, games_number: Option<usize>) -> Self 
     {
-        assert!(1 <= rating && rating <= 3);
-        assert!(1 <= length && length <= 3);
+        assert!((1..=3).contains(&rating));
+        assert!((1..=3).contains(&length));
         if length == 3 {
             assert!(rating == 1);
         }
@@ -37,7 +37,7 @@ impl TestSet
     fn load_test(length: usize, rating: usize, datasets_path: &str) -> Vec<(SequencePosition, i32)> {
         //let file_path = format!("./datasets/Test_L{}_R{}", length, rating);
         let file_path = format!("{}/datasets/Test_L{}_R{}", datasets_path, length, rating);
-        let file = File::open(&file_path).expect(format!("Unable to read file: {}", file_path).as_str());
+        let file = File::open(&file_path).unwrap_or_else(|_| panic!("Unable to read file: {}", file_path));
         let reader = BufReader::new(file);
         let mut games_moves = Vec::with_capacity(1_000);
 
@@ -51,7 +51,7 @@ impl TestSet
 
             assert_eq!(elements.len(), 2);
 
-            let position = SequencePosition::from(&elements[0]);
+            let position = SequencePosition::parse(&elements[0]).expect("Unable to parse position.");
             let score: i32 = elements[1].parse().expect("Unable to parse score.");
 
             games_moves.push((position, score));
@@ -64,7 +64,7 @@ impl TestSet
         &self.games_moves
     }
 
-    pub fn test_solver<P: Position + Clone>(&self, solver: &mut impl Solver) -> Statistics {
+    pub fn test_solver<P: Position + Clone>(&self, solver: &mut impl Solver<P>) -> Statistics {
         let mut execution_times: Vec<Duration> = Vec::with_capacity(self.games_moves.len());
         let mut explored_positions_nb: Vec<usize> = Vec::with_capacity(self.games_moves.len());
 
@@ -76,7 +76,7 @@ impl TestSet
                 solver.reset_explored_positions();
 
                 let now = Instant::now();
-                let solved_score = solver.solve(&mut P::from_seq(position));
+                let solved_score = solver.solve(&P::from_seq(position).expect("Invalid position in test set."));
                 execution_times.push(now.elapsed());
                 inc_progress_bar();
 
@@ -97,7 +97,7 @@ impl TestSet
         Statistics::new(results, execution_times, explored_positions_nb)
     }
 
-    pub fn test_weak_solver<P: Position + Clone>(&self, solver: &mut impl WeakSolver) -> Statistics {
+    pub fn test_weak_solver<P: Position + Clone>(&self, solver: &mut impl WeakSolver<P>) -> Statistics {
         let mut execution_times: Vec<Duration> = Vec::with_capacity(self.games_moves.len());
         let mut explored_positions_nb: Vec<usize> = Vec::with_capacity(self.games_moves.len());
 
@@ -114,7 +114,7 @@ impl TestSet
                 solver.reset_explored_positions();
 
                 let now = Instant::now();
-                let solved_score = solver.weak_solve(&mut P::from_seq(position));
+                let solved_score = solver.weak_solve(&P::from_seq(position).expect("Invalid position in test set."));
                 execution_times.push(now.elapsed());
                 inc_progress_bar();
 
@@ -0,0 +1,104 @@
+use lib_alpha_beta_solver::{opening_book::OpeningBook, opening_book_generator::OpeningBookGenerator};
+use lib_game_board::{Position, center_first, bitboard_position_with_ordering::BitboardPositionWithOrdering, sequence_position::SequencePosition};
+use progress_bar::*;
+
+use std::time::Instant;
+
+/// Generates the opening book of a `width`x`height` board up to `depth` moves, and writes it to `path`.
+/// An interrupted generation resumes from `<path>.partial` when launched again with the same arguments.
+pub fn generate_book(width: usize, height: usize, depth: usize, path: &str) {
+    println!("\n\nSelected arguments:");
+    println!("\t- Board: {}x{}", width, height);
+    println!("\t- Depth: {}", depth);
+    println!("\t- Output: {}", path);
+    println!();
+
+    let generator = OpeningBookGenerator::new(width, height, depth, center_first(width));
+
+    println!("Enumerating positions...");
+    let now = Instant::now();
+
+    let mut started = false;
+    generator.generate(path, |solved, total| {
+        if !started {
+            println!("{} positions to solve, {} already solved.", total, solved);
+            init_progress_bar(total);
+            set_progress_bar_action("Solving", Color::LightBlue, Style::Normal);
+            started = true;
+        }
+        set_progress_bar_progress(solved);
+    }).unwrap_or_else(|error| panic!("Unable to generate the opening book: {}", error));
+    finalize_progress_bar();
+
+    println!("Opening book generated in {:?}.", now.elapsed());
+}
+
+/// Prints the header fields of the book stored in `path`, and how many positions it holds.
+pub fn book_info(path: &str) {
+    let book = match OpeningBook::open(path) {
+        Ok(book) => book,
+        Err(error) => return println!("Unable to load the opening book: {}.", error)
+    };
+
+    println!("\n\nOpening book {}:", path);
+    println!("\t- Version: {}{}", book.version(), if book.version() >= 2 { " (with best moves)" } else { "" });
+    println!("\t- Board: {}x{}", book.width(), book.height());
+    println!("\t- Depth: {}", book.depth());
+    println!("\t- Key size: {} bytes", book.key_size());
+    println!("\t- Log size: {} ({} slots)", book.log_size(), book.size());
+    println!("\t- Entries: {} ({:.1}% of the slots)", book.len(), 100.0 * book.len() as f64 / book.size().max(1) as f64);
+}
+
+/// Prints the score and best move stored in the book for the position reached by `sequence`.
+pub fn book_lookup(path: &str, sequence: &str) {
+    let book = match OpeningBook::open(path) {
+        Ok(book) => book,
+        Err(error) => return println!("Unable to load the opening book: {}.", error)
+    };
+    // Books only hold boards fitting in 64 bits
+    if book.width() == 0 || book.width() * (book.height() + 1) > 64 {
+        return println!("Unable to look up positions of a {}x{} board.", book.width(), book.height());
+    }
+    let sequence = match SequencePosition::parse(sequence) {
+        Ok(sequence) => sequence,
+        Err(error) => return println!("Invalid sequence: {}.", error)
+    };
+    let position = match BitboardPositionWithOrdering::from_seq_with_dims(book.width(), book.height(), &sequence) {
+        Ok(position) => position,
+        Err(error) => return println!("Invalid position: {}.", error)
+    };
+
+    match book.score(&position) {
+        Some(score) => match book.best_move(&position) {
+            Some(column) => println!("Score: {}, best move: {}", score, column + 1),
+            None => println!("Score: {}", score)
+        },
+        None if position.nb_moves() > book.depth() => println!("The position is deeper than the book ({} moves).", book.depth()),
+        None => println!("The position is not in the book.")
+    }
+}
+
+/// Combines the books stored in `inputs`, of the same board but possibly of different depths, into `output`.
+pub fn book_merge(output: &str, inputs: &[String]) {
+    let books = match inputs.iter().map(|path| OpeningBook::open(path)).collect::<Result<Vec<_>, _>>() {
+        Ok(books) => books,
+        Err(error) => return println!("Unable to load the opening book: {}.", error)
+    };
+    if let Some(book) = books.iter().find(|book| (book.width(), book.height()) != (books[0].width(), books[0].height())) {
+        return println!("Unable to merge a {}x{} book with a {}x{} one.", books[0].width(), books[0].height(), book.width(), book.height());
+    }
+    let mut books = books.into_iter();
+    let mut book = books.next().expect("No book to merge.");
+    let mut dropped = 0;
+    for other in books {
+        let (merged, merge_dropped) = book.merge(&other);
+        book = merged;
+        dropped += merge_dropped;
+    }
+
+    book.save(output).unwrap_or_else(|error| panic!("Unable to write {}: {}", output, error));
+    println!("Merged {} books into {}: {} entries up to depth {}.", inputs.len(), output, book.len(), book.depth());
+    if dropped > 0 {
+        println!("{} entries were dropped, sharing their slot with another position.", dropped);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{*, sequence_position::SequencePosition};
+use crate::{*, sequence_position::{SequencePosition, PositionError}};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct AnticipatingBitboardPosition {
@@ -9,22 +9,46 @@ pub struct AnticipatingBitboardPosition {
     mask: u64,
     nb_moves: usize,
     bottom_mask: u64,
-    board_mask: u64
+    board_mask: u64,
+    win_length: usize
 }
 
 impl AnticipatingBitboardPosition {
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_win_length(width, height, DEFAULT_WIN_LENGTH)
+    }
+
+    pub fn with_win_length(width: usize, height: usize, win_length: usize) -> Self {
         assert!(width*(height+1) <= 64, "The board does not fit inside a 64bits bitboard.");
+        assert!(win_length >= 2, "At least two aligned discs are needed to win.");
         let bottom_mask = AnticipatingBitboardPosition::bottom(width, height);
         Self { 
             player_turn: FIRST_PLAYER, 
             width, height, 
             board: 0, mask: 0, nb_moves: 0, 
             bottom_mask,
-            board_mask: bottom_mask * ((1 << height)-1)
+            board_mask: bottom_mask * ((1 << height)-1),
+            win_length
         }
     }
 
+    /// Takes back the last move, which was played in `column`. Panics when the top disc of
+    /// `column` belongs to the player to move. Taking back another disc of the player who
+    /// moved last leaves a valid position, but not the previous one.
+    pub fn undo(&mut self, column: usize) {
+        let column_discs = self.mask & self.column_mask(column);
+        assert!(column_discs != 0, "There is no disc to take back in column {}.", column + 1);
+
+        // The discs of a column are contiguous: adding its bottom cell carries just above the top disc
+        let top_disc = (column_discs + self.bottom_mask_col(column)) >> 1;
+        // `board` holds the discs of the player to move
+        assert!(top_disc & self.board == 0, "The disc on top of column {} was not played by the last move.", column + 1);
+        self.mask ^= top_disc;
+        self.board ^= self.mask;
+        self.nb_moves -= 1;
+        self.player_turn = self.player_turn.swap_turn();
+    }
+
     fn top_mask_col(&self, column: usize) -> u64 {
         (1 << (self.height - 1)) << (column * (self.height + 1))
     }
@@ -37,7 +61,7 @@ impl AnticipatingBitboardPosition {
         if width == 0 {
             0
         } else {
-            AnticipatingBitboardPosition::bottom(width-1, height) | 1 << (width-1)*(height+1)
+            AnticipatingBitboardPosition::bottom(width-1, height) | 1 << ((width-1)*(height+1))
         }
     }
 
@@ -45,6 +69,15 @@ impl AnticipatingBitboardPosition {
         ((1 << self.height) - 1) << (column * (self.height + 1))
     }
 
+    /// Reflects a bitboard left to right, moving whole columns (with their spare top bit).
+    fn mirror(&self, bitboard: u64) -> u64 {
+        let column_mask = (1 << (self.height + 1)) - 1;
+        (0..self.width).fold(0, |mirrored, column| {
+            let bits = (bitboard >> (column * (self.height + 1))) & column_mask;
+            mirrored | bits << ((self.width - 1 - column) * (self.height + 1))
+        })
+    }
+
     fn winning_positions(&self) -> u64 {
         self.compute_winning_positions(self.board, self.mask)
     }
@@ -59,32 +92,29 @@ impl AnticipatingBitboardPosition {
 
 
     fn compute_winning_positions(&self, board: u64, mask: u64) -> u64 {
-        // Vertical
-        let mut r = (board << 1) & (board << 2) & (board << 3);
-
-        // Horizontal
-        let mut p = (board << (self.height+1)) & (board << 2*(self.height+1));
-        r |= p & (board << 3*(self.height+1));
-        r |= p & (board >> (self.height+1));
-        p = (board >> (self.height+1)) & (board >> 2*(self.height+1));
-        r |= p & (board << (self.height+1));
-        r |= p & (board >> 3*(self.height+1));
-
-        //diagonal 1
-        p = (board << self.height) & (board << 2*self.height);
-        r |= p & (board << 3*self.height);
-        r |= p & (board >> self.height);
-        p = (board >> self.height) & (board >> 2*self.height);
-        r |= p & (board << self.height);
-        r |= p & (board >> 3*self.height);
-
-        //diagonal 2
-        p = (board << (self.height+2)) & (board << 2*(self.height+2));
-        r |= p & (board << 3*(self.height+2));
-        r |= p & (board >> (self.height+2));
-        p = (board >> (self.height+2)) & (board >> 2*(self.height+2));
-        r |= p & (board << (self.height+2));
-        r |= p & (board >> 3*(self.height+2));
+        let (win_length, height) = (self.win_length, self.height);
+        // Alignments longer than the board are skipped, which also keeps the shifts inside the bitboard.
+        let (fits_horizontally, fits_vertically) = (win_length <= self.width, win_length <= height);
+        let mut r = 0;
+
+        // Vertical: only the cells below can be of the player
+        if fits_vertically {
+            r = (1..win_length).fold(!0, |p, t| p & (board << t));
+        }
+
+        // Horizontal and diagonals: the empty cell follows `k` discs of the player and
+        // precedes the others. The empty top bit of the columns stops diagonals from
+        // wrapping around to the next column.
+        let directions = [(height + 1, fits_horizontally), (height, fits_horizontally && fits_vertically), (height + 2, fits_horizontally && fits_vertically)];
+        for (direction, _) in directions.into_iter().filter(|&(_, fits)| fits) {
+            let mut before = !0;
+            for k in 0..win_length {
+                if k > 0 {
+                    before &= board << (k * direction);
+                }
+                r |= (1..win_length - k).fold(before, |p, t| p & (board >> (t * direction)));
+            }
+        }
 
         r & (self.board_mask ^ mask)
     }
@@ -123,14 +153,32 @@ impl Position for AnticipatingBitboardPosition {
         self.winning_positions() & self.possible_positions() & self.column_mask(column) != 0
     }
 
-    fn from_seq(sequence: &SequencePosition) -> Self {
-        Self::from(sequence)
+    fn win_length(&self) -> usize {
+        self.win_length
+    }
+
+    fn from_seq_with_win_length(width: usize, height: usize, win_length: usize, sequence: &SequencePosition) -> Result<Self, PositionError> {
+        let mut position = Self::with_win_length(width, height, win_length);
+        sequence.play_on(&mut position)?;
+
+        Ok(position)
     }
+}
+
+impl KeyedPosition for AnticipatingBitboardPosition {
+    type Bitboard = u64;
 
     fn key(&self) -> u64 {
         self.board + self.mask
     }
 
+    fn canonical_key(&self) -> u64 {
+        // Columns of the key do not overlap, so the key can be mirrored directly.
+        self.key().min(self.mirror(self.key()))
+    }
+}
+
+impl AnticipatingPosition for AnticipatingBitboardPosition {
     fn can_win_next(&self) -> bool {
         self.winning_positions() & self.possible_positions() != 0
     }
@@ -152,27 +200,13 @@ impl Position for AnticipatingBitboardPosition {
 
         possible_mask & !(opponent_win >> 1)
     }
-
-    fn move_score(&self, _move_bit: u64) -> usize {
-        todo!("move_score not implemented for AnticipatingBitboardPosition.. Use BitboardPositionWithOrdering instead.")
-    }
-
-    fn play_move(&mut self, _move_bit: u64) {
-        todo!("play_move not implemented for AnticipatingBitboardPosition. Use BitboardPositionWithOrdering instead.")
-    }
 }
 
-impl From<&SequencePosition> for AnticipatingBitboardPosition {
-    fn from(sequence_position: &SequencePosition) -> Self {
-        let mut grid_position = AnticipatingBitboardPosition::new(7, 6);        
-        let mut player = FIRST_PLAYER;
+impl TryFrom<&SequencePosition> for AnticipatingBitboardPosition {
+    type Error = PositionError;
 
-        for column in sequence_position.sequence() {
-            grid_position.play(column-1);
-            player = player.swap_turn();
-        }
-
-        grid_position
+    fn try_from(sequence_position: &SequencePosition) -> Result<Self, Self::Error> {
+        AnticipatingBitboardPosition::from_seq(sequence_position)
     }
 }
 
@@ -209,7 +243,51 @@ mod anticipating_bitboard_position_tests {
 
     // TODO: test `winning`
 
-    // TODO: test `unplay`
+    mod undo {
+        use super::*;
+
+        #[test]
+        fn back_to_previous_position() {
+            let sequence = SequencePosition::parse("4453372166").unwrap();
+            let mut position = AnticipatingBitboardPosition::from_seq(&sequence).unwrap();
+            let previous = position.clone();
+
+            for column in [0, 3, 3, 6] {
+                position.play(column);
+            }
+            for column in [6, 3, 3, 0] {
+                position.undo(column);
+            }
+            assert_eq!(position, previous);
+        }
+
+        #[test]
+        fn full_column() {
+            let mut position = AnticipatingBitboardPosition::new(7, 6);
+            for _ in 0..6 {
+                position.play(2);
+            }
+            position.undo(2);
+            assert!(position.can_play(2));
+            assert_eq!(position.nb_moves(), 5);
+            assert_eq!(position.player_turn(), Cell::Yellow);
+        }
+
+        #[test]
+        #[should_panic(expected = "There is no disc to take back in column 3.")]
+        fn empty_column() {
+            let mut position = AnticipatingBitboardPosition::from_seq(&SequencePosition::parse("4").unwrap()).unwrap();
+            position.undo(2);
+        }
+
+        #[test]
+        #[should_panic(expected = "The disc on top of column 4 was not played by the last move.")]
+        fn wrong_order() {
+            // Yellow played last, in column 5, while the top disc of column 4 is red
+            let mut position = AnticipatingBitboardPosition::from_seq(&SequencePosition::parse("45").unwrap()).unwrap();
+            position.undo(3);
+        }
+    }
 
     mod is_winning_move {
         use super::*;
@@ -249,7 +327,7 @@ mod anticipating_bitboard_position_tests {
         fn sequence_empty() {
             let expected_result = AnticipatingBitboardPosition::new(7, 6);
             assert_eq!(
-                AnticipatingBitboardPosition::from(&SequencePosition::from(&"".to_string())),
+                AnticipatingBitboardPosition::try_from(&SequencePosition::parse("").unwrap()).unwrap(),
                 expected_result
             )
         } 
@@ -266,9 +344,25 @@ mod anticipating_bitboard_position_tests {
             expected_result.play(6);
 
             assert_eq!(
-                AnticipatingBitboardPosition::from(&SequencePosition::from(&"1234567".to_string())),
+                AnticipatingBitboardPosition::try_from(&SequencePosition::parse("1234567").unwrap()).unwrap(),
                 expected_result
             )
         } 
+
+        #[test]
+        fn sequence_with_dims() {
+            let mut expected_result = AnticipatingBitboardPosition::new(5, 4);
+            expected_result.play(0);
+            expected_result.play(4);
+
+            assert_eq!(
+                AnticipatingBitboardPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("15").unwrap()).unwrap(),
+                expected_result
+            );
+            assert_eq!(
+                AnticipatingBitboardPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("16").unwrap()),
+                Err(PositionError::ColumnOutOfRange { ply: 1, column: 6, width: 5 })
+            );
+        }
     }
 }
\ No newline at end of file
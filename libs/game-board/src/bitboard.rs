@@ -0,0 +1,92 @@
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::ops::{Add, BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Mul, Not, Shl, Shr, Sub};
+
+/// Unsigned integers holding a board, one column after the other. Each column
+/// takes `height + 1` bits: the spare top bit absorbs the carries of the
+/// additions used to compute keys and moves.
+pub trait Bitboard:
+    Copy + Eq + Ord + Hash + Debug + Default + Send + Sync + 'static
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+    + BitAnd<Output = Self> + BitOr<Output = Self> + BitXor<Output = Self> + Not<Output = Self>
+    + BitAndAssign + BitOrAssign + BitXorAssign
+    + Shl<usize, Output = Self> + Shr<usize, Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+    /// Number of bits, which bounds `width * (height + 1)`.
+    const BITS: usize;
+
+    /// Lowest bits of the keys stored by the transposition tables. By the Chinese
+    /// remainder theorem, they are enough to identify a key as long as the size
+    /// of the table times `2^bits` exceeds the largest key.
+    type PartialKey: Copy + Eq + Hash + Default + Debug;
+
+    fn count_ones(self) -> u32;
+    fn trailing_zeros(self) -> u32;
+    /// Remainder of the division by `modulus`.
+    fn modulo(self, modulus: u64) -> u64;
+    fn partial_key(self) -> Self::PartialKey;
+    /// `None` when the value does not fit in 64 bits.
+    fn to_u64(self) -> Option<u64>;
+
+    /// Cells of `column`, without its spare top bit.
+    fn column_mask(height: usize, column: usize) -> Self {
+        ((Self::ONE << height) - Self::ONE) << (column * (height + 1))
+    }
+}
+
+macro_rules! impl_bitboard {
+    ($bitboard:ty, $partial_key:ty) => {
+        impl Bitboard for $bitboard {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+            const BITS: usize = <$bitboard>::BITS as usize;
+
+            type PartialKey = $partial_key;
+
+            fn count_ones(self) -> u32 {
+                <$bitboard>::count_ones(self)
+            }
+
+            fn trailing_zeros(self) -> u32 {
+                <$bitboard>::trailing_zeros(self)
+            }
+
+            fn modulo(self, modulus: u64) -> u64 {
+                (self % modulus as $bitboard) as u64
+            }
+
+            fn partial_key(self) -> Self::PartialKey {
+                self as $partial_key // truncated
+            }
+
+            fn to_u64(self) -> Option<u64> {
+                u64::try_from(self).ok()
+            }
+        }
+    };
+}
+
+impl_bitboard!(u64, u32);
+impl_bitboard!(u128, u64);
+
+#[cfg(test)]
+mod bitboard_tests {
+    use super::*;
+
+    #[test]
+    fn column_mask() {
+        assert_eq!(u64::column_mask(6, 0), 0b111111);
+        assert_eq!(u64::column_mask(6, 2), 0b111111 << 14);
+        assert_eq!(u128::column_mask(7, 8), 0b1111111 << 64);
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!((1u128 << 70 | 5).partial_key(), 5);
+        assert_eq!((1u128 << 70).to_u64(), None);
+        assert_eq!(42u128.to_u64(), Some(42));
+        assert_eq!((1u128 << 70).modulo(11), ((1u128 << 70) % 11) as u64);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{*, sequence_position::SequencePosition};
+use crate::{*, sequence_position::{SequencePosition, PositionError}};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct BitboardPosition {
@@ -7,13 +7,36 @@ pub struct BitboardPosition {
     height: usize,
     board: u64,
     mask: u64,
-    nb_moves: usize
+    nb_moves: usize,
+    win_length: usize
 }
 
 impl BitboardPosition {
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_win_length(width, height, DEFAULT_WIN_LENGTH)
+    }
+
+    pub fn with_win_length(width: usize, height: usize, win_length: usize) -> Self {
         assert!(width*(height+1) <= 64, "The board does not fit inside a 64bits bitboard.");
-        Self { player_turn: FIRST_PLAYER, width, height, board: 0, mask: 0, nb_moves: 0 }
+        assert!(win_length >= 2, "At least two aligned discs are needed to win.");
+        Self { player_turn: FIRST_PLAYER, width, height, board: 0, mask: 0, nb_moves: 0, win_length }
+    }
+
+    /// Takes back the last move, which was played in `column`. Panics when the top disc of
+    /// `column` belongs to the player to move. Taking back another disc of the player who
+    /// moved last leaves a valid position, but not the previous one.
+    pub fn undo(&mut self, column: usize) {
+        let column_discs = self.mask & self.column_mask(column);
+        assert!(column_discs != 0, "There is no disc to take back in column {}.", column + 1);
+
+        // The discs of a column are contiguous: adding its bottom cell carries just above the top disc
+        let top_disc = (column_discs + self.bottom_mask(column)) >> 1;
+        // `board` holds the discs of the player to move
+        assert!(top_disc & self.board == 0, "The disc on top of column {} was not played by the last move.", column + 1);
+        self.mask ^= top_disc;
+        self.board ^= self.mask;
+        self.nb_moves -= 1;
+        self.player_turn = self.player_turn.swap_turn();
     }
 
     fn top_mask(&self, column: usize) -> u64 {
@@ -28,31 +51,31 @@ impl BitboardPosition {
         ((1 << self.height) - 1) << (column * (self.height + 1))
     }
 
-    fn is_winning_board(&self, board: u64) -> bool {
-        // Horizontal 
-        let mut m = board & (board >> (self.height+1));
-        if m & (m >> (2 * (self.height + 1))) != 0 {
-            return true;
-        }
-
-        // Vertical
-        m = board & (board >> 1);
-        if (m & (m >> 2)) != 0 {
-            return true;
-        }
-
-        // Diagonals
-        m = board & (board >> self.height);
-        if m & (m >> (2 * self.height)) != 0 {
-            return true;
-        }
-
-        m = board & (board >> (self.height+2));
-        if m & (m >> (2*(self.height+2))) != 0 {
-            return true;
-        }
+    /// Reflects a bitboard left to right, moving whole columns (with their spare top bit).
+    fn mirror(&self, bitboard: u64) -> u64 {
+        let column_mask = (1 << (self.height + 1)) - 1;
+        (0..self.width).fold(0, |mirrored, column| {
+            let bits = (bitboard >> (column * (self.height + 1))) & column_mask;
+            mirrored | bits << ((self.width - 1 - column) * (self.height + 1))
+        })
+    }
 
-        false
+    fn is_winning_board(&self, board: u64) -> bool {
+        let (win_length, height) = (self.win_length, self.height);
+        // Alignments longer than the board are skipped, which also keeps the shifts inside the bitboard.
+        let (fits_horizontally, fits_vertically) = (win_length <= self.width, win_length <= height);
+
+        // Horizontal, vertical and both diagonals. The empty top bit of the columns
+        // stops vertical and diagonal alignments from wrapping around.
+        let directions = [
+            (height + 1, fits_horizontally),
+            (1, fits_vertically),
+            (height, fits_horizontally && fits_vertically),
+            (height + 2, fits_horizontally && fits_vertically)
+        ];
+        directions.into_iter()
+            .filter(|&(_, fits)| fits)
+            .any(|(direction, _)| (1..win_length).fold(board, |m, t| m & (board >> (t * direction))) != 0)
     }
 }
 
@@ -91,42 +114,36 @@ impl Position for BitboardPosition {
         self.is_winning_board(board_after)
     }
 
-    fn from_seq(sequence: &SequencePosition) -> Self {
-        Self::from(sequence)
+    fn win_length(&self) -> usize {
+        self.win_length
     }
 
-    fn key(&self) -> u64 {
-        self.board + self.mask
-    }
+    fn from_seq_with_win_length(width: usize, height: usize, win_length: usize, sequence: &SequencePosition) -> Result<Self, PositionError> {
+        let mut position = Self::with_win_length(width, height, win_length);
+        sequence.play_on(&mut position)?;
 
-    fn can_win_next(&self) -> bool {
-        todo!("can_win_next is not implemented for standard BitboardPosition. Use AnticipatingBitboardPosition instead.")
+        Ok(position)
     }
+}
 
-    fn possible_non_loosing_moves(&self) -> u64 {
-        todo!("possible_non_loosing_moves is not implemented for standard BitboardPosition. Use AnticipatingBitboardPosition instead.")
-    }
+impl KeyedPosition for BitboardPosition {
+    type Bitboard = u64;
 
-    fn move_score(&self, _move_bit: u64) -> usize {
-        todo!("move_score not implemented for BitboardPosition.")
+    fn key(&self) -> u64 {
+        self.board + self.mask
     }
 
-    fn play_move(&mut self, _move_bit: u64) {
-        todo!("play_move. not implemented for BitboardPosition.")
+    fn canonical_key(&self) -> u64 {
+        // Columns of the key do not overlap, so the key can be mirrored directly.
+        self.key().min(self.mirror(self.key()))
     }
 }
 
-impl From<&SequencePosition> for BitboardPosition {
-    fn from(sequence_position: &SequencePosition) -> Self {
-        let mut grid_position = BitboardPosition::new(7, 6);        
-        let mut player = FIRST_PLAYER;
-
-        for column in sequence_position.sequence() {
-            grid_position.play(column-1);
-            player = player.swap_turn();
-        }
+impl TryFrom<&SequencePosition> for BitboardPosition {
+    type Error = PositionError;
 
-        grid_position
+    fn try_from(sequence_position: &SequencePosition) -> Result<Self, Self::Error> {
+        BitboardPosition::from_seq(sequence_position)
     }
 }
 
@@ -163,7 +180,51 @@ mod bitboard_position_tests {
 
     // TODO: test `winning`
 
-    // TODO: test `unplay`
+    mod undo {
+        use super::*;
+
+        #[test]
+        fn back_to_previous_position() {
+            let sequence = SequencePosition::parse("4453372166").unwrap();
+            let mut position = BitboardPosition::from_seq(&sequence).unwrap();
+            let previous = position.clone();
+
+            for column in [0, 3, 3, 6] {
+                position.play(column);
+            }
+            for column in [6, 3, 3, 0] {
+                position.undo(column);
+            }
+            assert_eq!(position, previous);
+        }
+
+        #[test]
+        fn full_column() {
+            let mut position = BitboardPosition::new(7, 6);
+            for _ in 0..6 {
+                position.play(2);
+            }
+            position.undo(2);
+            assert!(position.can_play(2));
+            assert_eq!(position.nb_moves(), 5);
+            assert_eq!(position.player_turn(), Cell::Yellow);
+        }
+
+        #[test]
+        #[should_panic(expected = "There is no disc to take back in column 3.")]
+        fn empty_column() {
+            let mut position = BitboardPosition::from_seq(&SequencePosition::parse("4").unwrap()).unwrap();
+            position.undo(2);
+        }
+
+        #[test]
+        #[should_panic(expected = "The disc on top of column 4 was not played by the last move.")]
+        fn wrong_order() {
+            // Yellow played last, in column 5, while the top disc of column 4 is red
+            let mut position = BitboardPosition::from_seq(&SequencePosition::parse("45").unwrap()).unwrap();
+            position.undo(3);
+        }
+    }
 
     mod is_winning_move {
         use super::*;
@@ -203,7 +264,7 @@ mod bitboard_position_tests {
         fn sequence_empty() {
             let expected_result = BitboardPosition::new(7, 6);
             assert_eq!(
-                BitboardPosition::from(&SequencePosition::from(&"".to_string())),
+                BitboardPosition::try_from(&SequencePosition::parse("").unwrap()).unwrap(),
                 expected_result
             )
         } 
@@ -220,9 +281,25 @@ mod bitboard_position_tests {
             expected_result.play(6);
 
             assert_eq!(
-                BitboardPosition::from(&SequencePosition::from(&"1234567".to_string())),
+                BitboardPosition::try_from(&SequencePosition::parse("1234567").unwrap()).unwrap(),
                 expected_result
             )
         } 
+
+        #[test]
+        fn sequence_with_dims() {
+            let mut expected_result = BitboardPosition::new(5, 4);
+            expected_result.play(0);
+            expected_result.play(4);
+
+            assert_eq!(
+                BitboardPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("15").unwrap()).unwrap(),
+                expected_result
+            );
+            assert_eq!(
+                BitboardPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("16").unwrap()),
+                Err(PositionError::ColumnOutOfRange { ply: 1, column: 6, width: 5 })
+            );
+        }
     }
 }
\ No newline at end of file
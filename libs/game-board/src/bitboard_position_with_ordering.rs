@@ -1,113 +1,167 @@
-use crate::{*, sequence_position::SequencePosition};
+use crate::{*, bitboard::Bitboard, sequence_position::{SequencePosition, PositionError}, grid_position::GridPosition, stack_position::StackPosition};
 
+/// Bitboard position generic over the integer holding the board, so that boards
+/// with more than 64 cells (spare top bits included) can be represented.
 #[derive(Debug, PartialEq, Clone)]
-pub struct BitboardPositionWithOrdering {
+pub struct GenericBitboardPosition<B: Bitboard> {
     player_turn: Cell,
     width: usize,
     height: usize,
-    board: u64,
-    mask: u64,
+    board: B,
+    mask: B,
     nb_moves: usize,
-    bottom_mask: u64,
-    board_mask: u64
+    bottom_mask: B,
+    board_mask: B,
+    win_length: usize
 }
 
-impl BitboardPositionWithOrdering {
+/// Bitboard position of the boards fitting in 64 bits, such as 7x6. Boards up to 8x7 fit,
+/// but the transposition tables of the solvers only tell their keys apart up to 55 bits.
+pub type BitboardPositionWithOrdering = GenericBitboardPosition<u64>;
+
+/// Bitboard position of the boards fitting in 128 bits, such as 8x7 or 9x7.
+pub type WideBitboardPosition = GenericBitboardPosition<u128>;
+
+impl<B: Bitboard> GenericBitboardPosition<B> {
     pub fn new(width: usize, height: usize) -> Self {
-        assert!(width*(height+1) <= 64, "The board does not fit inside a 64bits bitboard.");
-        let bottom_mask = BitboardPositionWithOrdering::bottom(width, height);
+        Self::with_win_length(width, height, DEFAULT_WIN_LENGTH)
+    }
+
+    pub fn with_win_length(width: usize, height: usize, win_length: usize) -> Self {
+        assert!(width*(height+1) <= B::BITS, "The board does not fit inside a {}bits bitboard.", B::BITS);
+        assert!(win_length >= 2, "At least two aligned discs are needed to win.");
+        let bottom_mask = Self::bottom(width, height);
         Self { 
             player_turn: FIRST_PLAYER, 
             width, height, 
-            board: 0, mask: 0, nb_moves: 0, 
+            board: B::ZERO, mask: B::ZERO, nb_moves: 0, 
             bottom_mask,
-            board_mask: bottom_mask * ((1 << height)-1)
+            board_mask: bottom_mask * ((B::ONE << height) - B::ONE),
+            win_length
         }
     }
 
-    pub fn board(&self) -> u64 {
+    pub fn board(&self) -> B {
         self.board
     }
 
-    pub fn mask(&self) -> u64 {
+    pub fn mask(&self) -> B {
         self.mask
     }
 
-    fn top_mask_col(&self, column: usize) -> u64 {
-        (1 << (self.height - 1)) << (column * (self.height + 1))
+    /// Takes back the last move, which was played in `column`. Panics when the top disc of
+    /// `column` belongs to the player to move. Taking back another disc of the player who
+    /// moved last leaves a valid position, but not the previous one.
+    pub fn undo(&mut self, column: usize) {
+        let column_discs = self.mask & self.column_mask(column);
+        assert!(column_discs != B::ZERO, "There is no disc to take back in column {}.", column + 1);
+
+        // The discs of a column are contiguous: adding its bottom cell carries just above the top disc
+        let top_disc = (column_discs + self.bottom_mask_col(column)) >> 1;
+        // `board` holds the discs of the player to move
+        assert!(top_disc & self.board == B::ZERO, "The disc on top of column {} was not played by the last move.", column + 1);
+        self.mask ^= top_disc;
+        self.board ^= self.mask;
+        self.nb_moves -= 1;
+        self.player_turn = self.player_turn.swap_turn();
+    }
+
+    fn top_mask_col(&self, column: usize) -> B {
+        (B::ONE << (self.height - 1)) << (column * (self.height + 1))
     }
 
-    fn bottom_mask_col(&self, column: usize) -> u64 {
-        1 << (column * (self.height + 1))
+    fn bottom_mask_col(&self, column: usize) -> B {
+        B::ONE << (column * (self.height + 1))
     }
 
-    fn bottom(width: usize, height: usize) -> u64 {
+    fn bottom(width: usize, height: usize) -> B {
         if width == 0 {
-            0
+            B::ZERO
         } else {
-            BitboardPositionWithOrdering::bottom(width-1, height) | 1 << (width-1)*(height+1)
+            Self::bottom(width-1, height) | B::ONE << ((width-1)*(height+1))
         }
     }
 
-    fn column_mask(&self, column: usize) -> u64 {
-        ((1 << self.height) - 1) << (column * (self.height + 1))
+    fn column_mask(&self, column: usize) -> B {
+        B::column_mask(self.height, column)
     }
 
-    fn winning_positions(&self) -> u64 {
+    /// Reflects a bitboard left to right, moving whole columns (with their spare top bit).
+    fn mirror(&self, bitboard: B) -> B {
+        let column_mask = (B::ONE << (self.height + 1)) - B::ONE;
+        (0..self.width).fold(B::ZERO, |mirrored, column| {
+            let bits = (bitboard >> (column * (self.height + 1))) & column_mask;
+            mirrored | bits << ((self.width - 1 - column) * (self.height + 1))
+        })
+    }
+
+    fn winning_positions(&self) -> B {
         self.compute_winning_positions(self.board, self.mask)
     }
 
-    fn opponent_winning_positions(&self) -> u64 {
+    fn opponent_winning_positions(&self) -> B {
         self.compute_winning_positions(self.board ^ self.mask, self.mask)
     }
     
-    fn possible_positions(&self) -> u64 {
+    fn possible_positions(&self) -> B {
         (self.mask + self.bottom_mask) & self.board_mask
     }
 
 
-    fn compute_winning_positions(&self, board: u64, mask: u64) -> u64 {
-        // Vertical
-        let mut r = (board << 1) & (board << 2) & (board << 3);
+    fn compute_winning_positions(&self, board: B, mask: B) -> B {
+        let (win_length, height) = (self.win_length, self.height);
+        // Alignments longer than the board are skipped, which also keeps the shifts inside the bitboard.
+        let (fits_horizontally, fits_vertically) = (win_length <= self.width, win_length <= height);
+        let mut r = B::ZERO;
 
-        // Horizontal
-        let mut p = (board << (self.height+1)) & (board << 2*(self.height+1));
-        r |= p & (board << 3*(self.height+1));
-        r |= p & (board >> (self.height+1));
-        p = (board >> (self.height+1)) & (board >> 2*(self.height+1));
-        r |= p & (board << (self.height+1));
-        r |= p & (board >> 3*(self.height+1));
-
-        //diagonal 1
-        p = (board << self.height) & (board << 2*self.height);
-        r |= p & (board << 3*self.height);
-        r |= p & (board >> self.height);
-        p = (board >> self.height) & (board >> 2*self.height);
-        r |= p & (board << self.height);
-        r |= p & (board >> 3*self.height);
+        // Vertical: only the cells below can be of the player
+        if fits_vertically {
+            r = (1..win_length).fold(!B::ZERO, |p, t| p & (board << t));
+        }
 
-        //diagonal 2
-        p = (board << (self.height+2)) & (board << 2*(self.height+2));
-        r |= p & (board << 3*(self.height+2));
-        r |= p & (board >> (self.height+2));
-        p = (board >> (self.height+2)) & (board >> 2*(self.height+2));
-        r |= p & (board << (self.height+2));
-        r |= p & (board >> 3*(self.height+2));
+        // Horizontal and diagonals: the empty cell follows `k` discs of the player and
+        // precedes the others. The empty top bit of the columns stops diagonals from
+        // wrapping around to the next column.
+        let directions = [(height + 1, fits_horizontally), (height, fits_horizontally && fits_vertically), (height + 2, fits_horizontally && fits_vertically)];
+        for (direction, _) in directions.into_iter().filter(|&(_, fits)| fits) {
+            let mut before = !B::ZERO;
+            for k in 0..win_length {
+                if k > 0 {
+                    before &= board << (k * direction);
+                }
+                r |= (1..win_length - k).fold(before, |p, t| p & (board >> (t * direction)));
+            }
+        }
 
         r & (self.board_mask ^ mask)
     }
 
-    fn population_count(&self, mut m: u64) -> usize {
-        let mut c = 0;
-        while m != 0 {
-            m &= m - 1;
-            c += 1;
+    /// Builds the bitboards from a cell accessor taking `(column, line)`.
+    fn from_cells(position: &impl Position, cell: impl Fn(usize, usize) -> Cell) -> Self {
+        let (width, height, player_turn) = (position.width(), position.height(), position.player_turn());
+        let nb_moves = position.nb_moves();
+        let mut position = Self::with_win_length(width, height, position.win_length());
+        for column in 0..width {
+            for line in 0..height {
+                let bit = B::ONE << (column * (height + 1) + line);
+                match cell(column, line) {
+                    Cell::Empty => (),
+                    player if player == player_turn => {
+                        position.mask |= bit;
+                        position.board |= bit;
+                    },
+                    _ => position.mask |= bit
+                }
+            }
         }
-        c
+        position.player_turn = player_turn;
+        position.nb_moves = nb_moves;
+
+        position
     }
 }
 
-impl Position for BitboardPositionWithOrdering {
+impl<B: Bitboard> Position for GenericBitboardPosition<B> {
     fn width(&self) -> usize {
         self.width
     }
@@ -117,7 +171,7 @@ impl Position for BitboardPositionWithOrdering {
     }
 
     fn can_play(&self, column: usize) -> bool {
-        self.mask & self.top_mask_col(column) == 0
+        self.mask & self.top_mask_col(column) == B::ZERO
     }
 
     fn play(&mut self, column: usize) {
@@ -137,31 +191,49 @@ impl Position for BitboardPositionWithOrdering {
     }
 
     fn is_winning_move(&self, column: usize) -> bool {
-        self.winning_positions() & self.possible_positions() & self.column_mask(column) != 0
+        self.winning_positions() & self.possible_positions() & self.column_mask(column) != B::ZERO
+    }
+
+    fn win_length(&self) -> usize {
+        self.win_length
     }
 
-    fn from_seq(sequence: &SequencePosition) -> Self {
-        Self::from(sequence)
+    fn from_seq_with_win_length(width: usize, height: usize, win_length: usize, sequence: &SequencePosition) -> Result<Self, PositionError> {
+        let mut position = Self::with_win_length(width, height, win_length);
+        sequence.play_on(&mut position)?;
+
+        Ok(position)
     }
+}
 
-    fn key(&self) -> u64 {
+impl<B: Bitboard> KeyedPosition for GenericBitboardPosition<B> {
+    type Bitboard = B;
+
+    fn key(&self) -> B {
         self.board + self.mask
     }
 
+    fn canonical_key(&self) -> B {
+        // Columns of the key do not overlap, so the key can be mirrored directly.
+        self.key().min(self.mirror(self.key()))
+    }
+}
+
+impl<B: Bitboard> AnticipatingPosition for GenericBitboardPosition<B> {
     fn can_win_next(&self) -> bool {
-        self.winning_positions() & self.possible_positions() != 0
+        self.winning_positions() & self.possible_positions() != B::ZERO
     }
 
-    fn possible_non_loosing_moves(&self) -> u64 {
+    fn possible_non_loosing_moves(&self) -> B {
         assert!(!self.can_win_next());
 
         let mut possible_mask = self.possible_positions();
         let opponent_win = self.opponent_winning_positions();
         let forced_moves = possible_mask & opponent_win;
 
-        if forced_moves != 0 {
-            if forced_moves & (forced_moves - 1) != 0 {
-                return 0; // unable to win since the opponent has two winning moves
+        if forced_moves != B::ZERO {
+            if forced_moves & (forced_moves - B::ONE) != B::ZERO {
+                return B::ZERO; // unable to win since the opponent has two winning moves
             } else {
                 possible_mask = forced_moves; // forced to play the forced move
             }
@@ -169,12 +241,14 @@ impl Position for BitboardPositionWithOrdering {
 
         possible_mask & !(opponent_win >> 1)
     }
+}
 
-    fn move_score(&self, move_bit: u64) -> usize {
-        self.population_count(self.compute_winning_positions(self.board | move_bit, self.mask))
+impl<B: Bitboard> BitboardMovePosition for GenericBitboardPosition<B> {
+    fn move_score(&self, move_bit: B) -> usize {
+        self.compute_winning_positions(self.board | move_bit, self.mask).count_ones() as usize
     }
 
-    fn play_move(&mut self, move_bit: u64) {
+    fn play_move(&mut self, move_bit: B) {
         self.nb_moves += 1;
         self.player_turn = self.player_turn.swap_turn();
         
@@ -183,17 +257,23 @@ impl Position for BitboardPositionWithOrdering {
     }
 }
 
-impl From<&SequencePosition> for BitboardPositionWithOrdering {
-    fn from(sequence_position: &SequencePosition) -> Self {
-        let mut grid_position = BitboardPositionWithOrdering::new(7, 6);        
-        let mut player = FIRST_PLAYER;
+impl<B: Bitboard> TryFrom<&SequencePosition> for GenericBitboardPosition<B> {
+    type Error = PositionError;
 
-        for column in sequence_position.sequence() {
-            grid_position.play(column-1);
-            player = player.swap_turn();
-        }
+    fn try_from(sequence_position: &SequencePosition) -> Result<Self, Self::Error> {
+        Self::from_seq(sequence_position)
+    }
+}
 
-        grid_position
+impl<B: Bitboard> From<&GridPosition> for GenericBitboardPosition<B> {
+    fn from(grid_position: &GridPosition) -> Self {
+        Self::from_cells(grid_position, |column, line| grid_position.grid()[line][column])
+    }
+}
+
+impl<B: Bitboard> From<&StackPosition> for GenericBitboardPosition<B> {
+    fn from(stack_position: &StackPosition) -> Self {
+        Self::from_cells(stack_position, |column, line| *stack_position.stacks()[column].get(line).unwrap_or(&Cell::Empty))
     }
 }
 
@@ -230,7 +310,60 @@ mod bitboard_position_with_ordering_tests {
 
     // TODO: test `winning`
 
-    // TODO: test `unplay`
+    mod undo {
+        use super::*;
+
+        #[test]
+        fn back_to_previous_position() {
+            let sequence = SequencePosition::parse("4453372166").unwrap();
+            let mut position = BitboardPositionWithOrdering::from_seq(&sequence).unwrap();
+            let previous = position.clone();
+
+            for column in [0, 3, 3, 6] {
+                position.play(column);
+            }
+            for column in [6, 3, 3, 0] {
+                position.undo(column);
+            }
+            assert_eq!(position, previous);
+        }
+
+        #[test]
+        fn full_column() {
+            let mut position = BitboardPositionWithOrdering::new(7, 6);
+            for _ in 0..6 {
+                position.play(2);
+            }
+            position.undo(2);
+            assert!(position.can_play(2));
+            assert_eq!(position.nb_moves(), 5);
+            assert_eq!(position.player_turn(), Cell::Yellow);
+        }
+
+        #[test]
+        #[should_panic(expected = "There is no disc to take back in column 3.")]
+        fn empty_column() {
+            let mut position = BitboardPositionWithOrdering::from_seq(&SequencePosition::parse("4").unwrap()).unwrap();
+            position.undo(2);
+        }
+
+        #[test]
+        #[should_panic(expected = "The disc on top of column 4 was not played by the last move.")]
+        fn wrong_order() {
+            // Yellow played last, in column 5, while the top disc of column 4 is red
+            let mut position = BitboardPositionWithOrdering::from_seq(&SequencePosition::parse("45").unwrap()).unwrap();
+            position.undo(3);
+        }
+
+        #[test]
+        fn wide_bitboard() {
+            let mut position = WideBitboardPosition::from_seq_with_dims(9, 7, &SequencePosition::parse("617181").unwrap()).unwrap();
+            let previous = position.clone();
+            position.play(8);
+            position.undo(8);
+            assert_eq!(position, previous);
+        }
+    }
 
     mod is_winning_move {
         use super::*;
@@ -270,7 +403,7 @@ mod bitboard_position_with_ordering_tests {
         fn sequence_empty() {
             let expected_result = BitboardPositionWithOrdering::new(7, 6);
             assert_eq!(
-                BitboardPositionWithOrdering::from(&SequencePosition::from(&"".to_string())),
+                BitboardPositionWithOrdering::try_from(&SequencePosition::parse("").unwrap()).unwrap(),
                 expected_result
             )
         } 
@@ -287,9 +420,198 @@ mod bitboard_position_with_ordering_tests {
             expected_result.play(6);
 
             assert_eq!(
-                BitboardPositionWithOrdering::from(&SequencePosition::from(&"1234567".to_string())),
+                BitboardPositionWithOrdering::try_from(&SequencePosition::parse("1234567").unwrap()).unwrap(),
                 expected_result
             )
         } 
+
+        #[test]
+        fn sequence_with_dims() {
+            let mut expected_result = BitboardPositionWithOrdering::new(5, 4);
+            expected_result.play(0);
+            expected_result.play(4);
+
+            assert_eq!(
+                BitboardPositionWithOrdering::from_seq_with_dims(5, 4, &SequencePosition::parse("15").unwrap()).unwrap(),
+                expected_result
+            );
+            assert_eq!(
+                BitboardPositionWithOrdering::from_seq_with_dims(5, 4, &SequencePosition::parse("16").unwrap()),
+                Err(PositionError::ColumnOutOfRange { ply: 1, column: 6, width: 5 })
+            );
+        }
+    }
+
+    mod canonical_key {
+        use super::*;
+
+        fn position(sequence: &str) -> BitboardPositionWithOrdering {
+            BitboardPositionWithOrdering::from_seq(&SequencePosition::parse(sequence).unwrap()).unwrap()
+        }
+
+        #[test]
+        fn mirrored_positions() {
+            assert_eq!(position("1234").canonical_key(), position("7654").canonical_key());
+            assert_eq!(position("44536").canonical_key(), position("44352").canonical_key());
+            assert_ne!(position("1234").key(), position("7654").key());
+            assert_ne!(position("1234").canonical_key(), position("1235").canonical_key());
+        }
+
+        #[test]
+        fn smallest_key() {
+            assert_eq!(position("4477").canonical_key(), position("4411").key());
+            assert_eq!(position("44").canonical_key(), position("44").key());
+        }
+
+        #[test]
+        fn canonical_column() {
+            let (left, right) = (position("1"), position("7"));
+            let oriented = [left.canonical_column(0), right.canonical_column(6)];
+            assert_eq!(oriented[0], oriented[1]);
+            assert_eq!(right.canonical_column(right.canonical_column(2)), 2);
+            assert_eq!(position("4").canonical_column(2), 2);
+        }
+
+        #[test]
+        fn other_dimensions() {
+            let position = |sequence| BitboardPositionWithOrdering::from_seq_with_dims(5, 4, &SequencePosition::parse(sequence).unwrap()).unwrap();
+            assert_eq!(position("1123").canonical_key(), position("5543").canonical_key());
+        }
+    }
+
+    mod wide_bitboard {
+        use super::*;
+
+        #[test]
+        fn same_as_narrow_bitboard() {
+            let sequence = SequencePosition::parse("4453372166").unwrap();
+            let narrow = BitboardPositionWithOrdering::from_seq(&sequence).unwrap();
+            let wide = WideBitboardPosition::from_seq(&sequence).unwrap();
+
+            assert_eq!(wide.key(), narrow.key() as u128);
+            assert_eq!(wide.canonical_key(), narrow.canonical_key() as u128);
+            assert_eq!(wide.possible_non_loosing_moves(), narrow.possible_non_loosing_moves() as u128);
+            assert_eq!(GridPosition::from(&wide), GridPosition::from(&narrow));
+        }
+
+        #[test]
+        fn large_board() {
+            // 9 columns of 7 cells, plus their spare bits, take 72 bits
+            let mut position = WideBitboardPosition::from_seq_with_dims(9, 7, &SequencePosition::parse("617181").unwrap()).unwrap();
+            assert!(position.is_winning_move(8)); // in the bits above 64
+            assert!(position.is_winning_move(4));
+            assert!(!position.is_winning_move(0));
+
+            for _ in 0..6 {
+                position.play(7);
+            }
+            assert!(!position.can_play(7));
+            assert_eq!(position.canonical_key(), position.key().min(position.mirror(position.key())));
+        }
+
+        #[test]
+        #[should_panic(expected = "The board does not fit inside a 64bits bitboard.")]
+        fn too_large_for_narrow_bitboard() {
+            BitboardPositionWithOrdering::new(9, 7);
+        }
+    }
+
+    mod win_length {
+        use super::*;
+        use crate::{bitboard_position::BitboardPosition, anticipating_bitboard_position::AnticipatingBitboardPosition};
+
+        /// Plays pseudo-random games and checks that every implementation finds the
+        /// same winning moves as the grid.
+        fn check_same_wins(width: usize, height: usize, win_length: usize) {
+            let mut seed = 12345u64;
+            for _ in 0..20 {
+                let mut grid = GridPosition::with_win_length(width, height, win_length);
+                let mut stack = StackPosition::with_win_length(width, height, win_length);
+                let mut wide = WideBitboardPosition::with_win_length(width, height, win_length);
+                let mut narrow = (width*(height+1) <= 64).then(|| (
+                    BitboardPosition::with_win_length(width, height, win_length),
+                    AnticipatingBitboardPosition::with_win_length(width, height, win_length),
+                    BitboardPositionWithOrdering::with_win_length(width, height, win_length)
+                ));
+
+                while grid.nb_moves() < width*height {
+                    for column in (0..width).filter(|&column| grid.can_play(column)) {
+                        let expected = grid.is_winning_move(column);
+                        assert_eq!(stack.is_winning_move(column), expected);
+                        assert_eq!(wide.is_winning_move(column), expected);
+                        if let Some((bitboard, anticipating, ordering)) = &narrow {
+                            assert_eq!(bitboard.is_winning_move(column), expected);
+                            assert_eq!(anticipating.is_winning_move(column), expected);
+                            assert_eq!(ordering.is_winning_move(column), expected);
+                        }
+                    }
+
+                    seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    let column = (0..width).cycle().skip((seed >> 33) as usize % width)
+                        .find(|&column| grid.can_play(column)).unwrap();
+                    if grid.is_winning_move(column) {
+                        break;
+                    }
+                    grid.play(column);
+                    stack.play(column);
+                    wide.play(column);
+                    if let Some((bitboard, anticipating, ordering)) = &mut narrow {
+                        bitboard.play(column);
+                        anticipating.play(column);
+                        ordering.play(column);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn same_wins_as_grid() {
+            for win_length in 2..=6 {
+                check_same_wins(7, 6, win_length);
+            }
+            check_same_wins(4, 4, 3);
+            check_same_wins(9, 7, 5);
+            check_same_wins(3, 3, 4);
+            // Flat boards filling the 64 bits
+            check_same_wins(16, 3, 5);
+            check_same_wins(16, 3, 16);
+        }
+
+        #[test]
+        fn kept_by_conversions() {
+            let grid = GridPosition::from_seq_with_win_length(5, 4, 3, &SequencePosition::parse("1122").unwrap()).unwrap();
+            let position = BitboardPositionWithOrdering::from(&grid);
+            assert_eq!(position.win_length(), 3);
+            assert!(position.is_winning_move(2));
+            assert_eq!(GridPosition::from(&position), grid);
+        }
+
+        #[test]
+        #[should_panic(expected = "At least two aligned discs are needed to win.")]
+        fn too_short() {
+            BitboardPositionWithOrdering::with_win_length(7, 6, 1);
+        }
+    }
+
+    mod from_other_positions {
+        use super::*;
+
+        #[test]
+        fn from_grid_position() {
+            let sequence = SequencePosition::parse("44533721").unwrap();
+            assert_eq!(
+                BitboardPositionWithOrdering::from(&GridPosition::from_seq(&sequence).unwrap()),
+                BitboardPositionWithOrdering::from_seq(&sequence).unwrap()
+            );
+        }
+
+        #[test]
+        fn from_stack_position() {
+            let sequence = SequencePosition::parse("44533721").unwrap();
+            assert_eq!(
+                BitboardPositionWithOrdering::from(&StackPosition::from_seq(&sequence).unwrap()),
+                BitboardPositionWithOrdering::from_seq(&sequence).unwrap()
+            );
+        }
     }
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use crate::{*, sequence_position::SequencePosition, bitboard_position_with_ordering::BitboardPositionWithOrdering};
+use crate::{*, bitboard::Bitboard, sequence_position::{SequencePosition, PositionError}, bitboard_position_with_ordering::{BitboardPositionWithOrdering, GenericBitboardPosition}};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct GridPosition {
@@ -6,16 +6,22 @@ pub struct GridPosition {
     width: usize,
     height: usize,
     grid: Vec<Vec<Cell>>,
-    nb_moves: usize
+    nb_moves: usize,
+    win_length: usize
 }
 
 impl GridPosition {
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_win_length(width, height, DEFAULT_WIN_LENGTH)
+    }
+
+    pub fn with_win_length(width: usize, height: usize, win_length: usize) -> Self {
         assert!(height > 0);
+        assert!(win_length >= 2, "At least two aligned discs are needed to win.");
 
         let grid = (0..height).map(|_| vec![Cell::Empty; width]).collect();
 
-        Self { player_turn: FIRST_PLAYER, width, height, grid, nb_moves: 0 }
+        Self { player_turn: FIRST_PLAYER, width, height, grid, nb_moves: 0, win_length }
     }
 
     pub fn grid(&self) -> &Vec<Vec<Cell>> {
@@ -24,10 +30,9 @@ impl GridPosition {
 
     fn is_align(&self, line: usize, column: usize, incrementer: (i32, i32)) -> Cell {
         let (i0, i1) = incrementer;
-        if self.grid[line][column] == self.grid[(line as i32 + i0) as usize][(column as i32 + i1) as usize] 
-        && self.grid[line][column] == self.grid[(line as i32 + 2*i0) as usize][(column as i32 + 2*i1) as usize]
-        && self.grid[line][column] == self.grid[(line as i32 + 3*i0) as usize][(column as i32 + 3*i1) as usize]
-        {
+        if (1..self.win_length as i32).all(|k|
+            self.grid[line][column] == self.grid[(line as i32 + k*i0) as usize][(column as i32 + k*i1) as usize]
+        ) {
             self.grid[line][column]
         } else {
             Cell::Empty
@@ -47,16 +52,19 @@ impl GridPosition {
     }*/
 
     pub fn winning(&self) -> Cell {
+        // Number of starting cells of an alignment along a dimension
+        let starts = |size: usize| (size + 1).saturating_sub(self.win_length);
+
         // Horizontal
         for line in 0..self.height {
-            for column in 0..self.width-3 {
+            for column in 0..starts(self.width) {
                 let align = self.is_align(line, column, (0, 1));
                 if align != Cell::Empty { return align; }
             }
         }
 
         // Vertical
-        for line in 0..self.height-3 {
+        for line in 0..starts(self.height) {
             for column in 0..self.width {
                 let align = self.is_align(line, column, (1, 0));
                 if align != Cell::Empty { return align; }
@@ -64,14 +72,14 @@ impl GridPosition {
         }
 
         // Diagonals
-        for line in 0..self.height-3 {
-            for column in 0..self.width-3 {
+        for line in 0..starts(self.height) {
+            for column in 0..starts(self.width) {
                 let align = self.is_align(line, column, (1, 1));
                 if align != Cell::Empty { return align; }
             }
         }
-        for line in 0..self.height-3 {
-            for column in 3..self.width {
+        for line in 0..starts(self.height) {
+            for column in self.win_length-1..self.width {
                 let align = self.is_align(line, column, (1, -1));
                 if align != Cell::Empty { return align; }
             }
@@ -129,11 +137,9 @@ impl Position for GridPosition {
             line += 1;
         }
 
-        // Vertical align: check if the 3 cells below are of the player's color
-        if line >= 3
-            && self.grid[line-3][column] == self.player_turn
-            && self.grid[line-2][column] == self.player_turn
-            && self.grid[line-1][column] == self.player_turn {
+        // Vertical align: check if the cells below are of the player's color
+        if line >= self.win_length - 1
+            && (line+1-self.win_length..line).all(|below| self.grid[below][column] == self.player_turn) {
                 return true;
         }
 
@@ -154,7 +160,7 @@ impl Position for GridPosition {
                  }
             }
 
-            if nb_nearby >= 3 {
+            if nb_nearby >= self.win_length - 1 {
                 return true;
             }
         }
@@ -166,75 +172,89 @@ impl Position for GridPosition {
         self.nb_moves
     }
 
-    fn from_seq(sequence: &SequencePosition) -> Self {
-        Self::from(sequence)
+    fn win_length(&self) -> usize {
+        self.win_length
+    }
+
+    fn from_seq_with_win_length(width: usize, height: usize, win_length: usize, sequence: &SequencePosition) -> Result<Self, PositionError> {
+        let mut position = Self::with_win_length(width, height, win_length);
+        sequence.play_on(&mut position)?;
+
+        Ok(position)
     }
+}
+
+impl KeyedPosition for GridPosition {
+    type Bitboard = u64;
 
     fn key(&self) -> u64 {
-        todo!("key not implemented for GridPosition");
+        BitboardPositionWithOrdering::from(self).key()
     }
 
+    fn canonical_key(&self) -> u64 {
+        BitboardPositionWithOrdering::from(self).canonical_key()
+    }
+}
+
+impl AnticipatingPosition for GridPosition {
     fn can_win_next(&self) -> bool {
-        todo!("can_win_next not implemented for GridPosition");
+        (0..self.width).any(|column| self.can_play(column) && self.is_winning_move(column))
     }
 
     fn possible_non_loosing_moves(&self) -> u64 {
-        todo!("possible_non_loosing_moves not implemented for GridPosition");
+        BitboardPositionWithOrdering::from(self).possible_non_loosing_moves()
     }
+}
 
-    fn move_score(&self, _move_bit: u64) -> usize {
-        todo!("move_score not implemented for GridPosition")
+impl BitboardMovePosition for GridPosition {
+    fn move_score(&self, move_bit: u64) -> usize {
+        BitboardPositionWithOrdering::from(self).move_score(move_bit)
     }
 
-    fn play_move(&mut self, _move_bit: u64) {
-        todo!("play_move not implemented for GridPosition")
+    fn play_move(&mut self, move_bit: u64) {
+        let column = move_bit.trailing_zeros() as usize / (self.height + 1);
+        self.play(column);
     }
 }
 
-impl From<&SequencePosition> for GridPosition {
-    fn from(sequence_position: &SequencePosition) -> Self {
-        let mut grid_position = GridPosition::new(7, 6);        
-        let mut player = FIRST_PLAYER;
+impl TryFrom<&SequencePosition> for GridPosition {
+    type Error = PositionError;
 
-        for column in sequence_position.sequence() {
-            grid_position.play(column-1);
-            player = player.swap_turn();
-        }
-
-        grid_position
+    fn try_from(sequence_position: &SequencePosition) -> Result<Self, Self::Error> {
+        GridPosition::from_seq(sequence_position)
     }
 }
 
-impl From<&BitboardPositionWithOrdering> for GridPosition {
-    fn from(bitboard_position: &BitboardPositionWithOrdering) -> Self {
+impl<B: Bitboard> From<&GenericBitboardPosition<B>> for GridPosition {
+    fn from(bitboard_position: &GenericBitboardPosition<B>) -> Self {
         let width = bitboard_position.width();
         let height = bitboard_position.height();
         let player_turn = bitboard_position.player_turn();
         let nb_moves = bitboard_position.nb_moves();
+        let win_length = bitboard_position.win_length();
 
         // Initialise empty grid
         let mut grid: Vec<Vec<Cell>> = (0..height)
             .map(|_| vec![Cell::Empty; width])
             .collect();
 
-        let mut bit_index = 0;
-        for column in 0..width {
-            for line in 0..height {
-                let occupied = bitboard_position.mask() & (1 << bit_index) != 0;
+        for (line, row) in grid.iter_mut().enumerate() {
+            for (column, cell) in row.iter_mut().enumerate() {
+                // extra bit on top of each column
+                let bit_index = column * (height + 1) + line;
+
+                let occupied = bitboard_position.mask() & (B::ONE << bit_index) != B::ZERO;
                 if occupied {
-                    let is_current_player = bitboard_position.board() & (1 << bit_index) != 0;
+                    let is_current_player = bitboard_position.board() & (B::ONE << bit_index) != B::ZERO;
                     match is_current_player {
-                        true => grid[line][column] = player_turn,
-                        false => grid[line][column] = player_turn.swap_turn()
+                        true => *cell = player_turn,
+                        false => *cell = player_turn.swap_turn()
                     };
                 }
-
-                bit_index += 1;
             }
-            bit_index += 1; // extra bit on top of each column
         }
 
-        Self { player_turn, width, height, grid, nb_moves }
+        Self { player_turn, width, height, grid, nb_moves, win_length }
     }
 }
 
@@ -331,6 +351,50 @@ mod grid_position_tests {
             assert!(position.is_winning_move(3));
             assert_eq!(position.nb_moves, 6);
         }
+
+        #[test]
+        fn connect_three() {
+            let position = GridPosition::from_seq_with_win_length(5, 4, 3, &SequencePosition::parse("1122").unwrap()).unwrap();
+            assert!(position.is_winning_move(2));
+            assert!(!GridPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("1122").unwrap()).unwrap().is_winning_move(2));
+        }
+
+        #[test]
+        fn connect_five() {
+            let sequence = SequencePosition::parse("11223344").unwrap();
+            let position = GridPosition::from_seq_with_win_length(7, 6, 5, &sequence).unwrap();
+            assert!(position.is_winning_move(4));
+            assert!(!position.is_winning_move(5));
+            // Four discs already won the game
+            assert_eq!(GridPosition::from_seq(&sequence), Err(PositionError::MoveAfterWin { ply: 7 }));
+        }
+    }
+
+    mod winning {
+        use super::*;
+
+        #[test]
+        fn win_length() {
+            let sequence = SequencePosition::parse("11223").unwrap();
+            assert_eq!(GridPosition::from_seq_with_win_length(5, 4, 3, &sequence).unwrap().winning(), Cell::Red);
+            assert_eq!(GridPosition::from_seq_with_dims(5, 4, &sequence).unwrap().winning(), Cell::Empty);
+            // Alignments longer than the board never happen
+            assert_eq!(GridPosition::from_seq_with_win_length(3, 3, 4, &sequence).map(|position| position.winning()), Ok(Cell::Empty));
+        }
+    }
+
+    mod scores {
+        use super::*;
+
+        #[test]
+        fn score_bounds() {
+            let position = GridPosition::new(7, 6);
+            assert_eq!((position.min_score(), position.max_score()), (-18, 18));
+            let position = GridPosition::with_win_length(4, 4, 3);
+            assert_eq!((position.min_score(), position.max_score()), (-6, 6));
+            let position = GridPosition::with_win_length(9, 7, 5);
+            assert_eq!((position.min_score(), position.max_score()), (-27, 28));
+        }
     }
 
     mod from_sequence_position {
@@ -340,7 +404,7 @@ mod grid_position_tests {
         fn sequence_empty() {
             let expected_result = GridPosition::new(7, 6);
             assert_eq!(
-                GridPosition::from(&SequencePosition::from(&"".to_string())),
+                GridPosition::try_from(&SequencePosition::parse("").unwrap()).unwrap(),
                 expected_result
             )
         } 
@@ -357,10 +421,34 @@ mod grid_position_tests {
             expected_result.play(6);
 
             assert_eq!(
-                GridPosition::from(&SequencePosition::from(&"1234567".to_string())),
+                GridPosition::try_from(&SequencePosition::parse("1234567").unwrap()).unwrap(),
                 expected_result
             )
         } 
+
+        #[test]
+        fn sequence_with_dims() {
+            let mut expected_result = GridPosition::new(5, 4);
+            expected_result.play(0);
+            expected_result.play(4);
+
+            assert_eq!(
+                GridPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("15").unwrap()).unwrap(),
+                expected_result
+            );
+            assert_eq!(
+                GridPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("16").unwrap()),
+                Err(PositionError::ColumnOutOfRange { ply: 1, column: 6, width: 5 })
+            );
+        }
+
+        #[test]
+        fn sequence_column_full() {
+            assert_eq!(
+                GridPosition::try_from(&SequencePosition::parse("4444444").unwrap()),
+                Err(PositionError::ColumnFull { ply: 6, column: 4 })
+            )
+        }
     }
 
     mod from_bitboard_position {
@@ -397,4 +485,46 @@ mod grid_position_tests {
             assert_eq!(converted_grid_position, expected_grid_position);
         }
     }
+
+    mod bitboard_compatibility {
+        use super::*;
+
+        #[test]
+        fn same_key_as_bitboard() {
+            let sequence = SequencePosition::parse("4453372").unwrap();
+            assert_eq!(
+                GridPosition::from_seq(&sequence).unwrap().key(),
+                BitboardPositionWithOrdering::from_seq(&sequence).unwrap().key()
+            );
+            assert_eq!(
+                GridPosition::from_seq(&sequence).unwrap().canonical_key(),
+                BitboardPositionWithOrdering::from_seq(&sequence).unwrap().canonical_key()
+            );
+        }
+
+        #[test]
+        fn same_non_loosing_moves_as_bitboard() {
+            // Red threatens to win in column 4
+            let sequence = SequencePosition::parse("41424").unwrap();
+            let position = GridPosition::from_seq(&sequence).unwrap();
+            let bitboard_position = BitboardPositionWithOrdering::from_seq(&sequence).unwrap();
+
+            assert!(!position.can_win_next());
+            assert_eq!(position.possible_non_loosing_moves(), bitboard_position.possible_non_loosing_moves());
+        }
+
+        #[test]
+        fn play_move() {
+            let sequence = SequencePosition::parse("4453").unwrap();
+            let mut position = GridPosition::from_seq(&sequence).unwrap();
+            let mut expected_position = position.clone();
+
+            let move_bit = position.possible_non_loosing_moves() & (((1 << 6) - 1) << (2 * 7));
+            assert_eq!(position.move_score(move_bit), BitboardPositionWithOrdering::from_seq(&sequence).unwrap().move_score(move_bit));
+
+            position.play_move(move_bit);
+            expected_position.play(2);
+            assert_eq!(position, expected_position);
+        }
+    }
 }
\ No newline at end of file
@@ -1,14 +1,22 @@
-use sequence_position::SequencePosition;
+use sequence_position::{SequencePosition, PositionError};
+use bitboard::Bitboard;
 
+pub mod bitboard;
 pub mod grid_position;
 pub mod sequence_position;
 pub mod stack_position;
 pub mod bitboard_position;
 pub mod anticipating_bitboard_position;
 pub mod bitboard_position_with_ordering;
+pub mod variant_position;
+pub mod player;
 
 const FIRST_PLAYER: Cell = Cell::Red;
 
+/// Number of aligned discs needed to win in the standard game.
+pub const DEFAULT_WIN_LENGTH: usize = 4;
+
+/// Rules of the game: enough to run a Min-Max or a vanilla Alpha-Beta.
 pub trait Position {
     fn player_turn(&self) -> Cell;
     fn width(&self) -> usize;
@@ -18,19 +26,73 @@ pub trait Position {
     //fn winning(&self) -> Cell;
     fn is_winning_move(&self, column: usize) -> bool;
     fn nb_moves(&self) -> usize;
-    fn from_seq(sequence: &SequencePosition) -> Self;
+    /// Number of aligned discs needed to win.
+    fn win_length(&self) -> usize;
+    fn from_seq_with_win_length(width: usize, height: usize, win_length: usize, sequence: &SequencePosition) -> Result<Self, PositionError> where Self: Sized;
+
+    /// Builds a four-in-a-row position.
+    fn from_seq_with_dims(width: usize, height: usize, sequence: &SequencePosition) -> Result<Self, PositionError> where Self: Sized {
+        Self::from_seq_with_win_length(width, height, DEFAULT_WIN_LENGTH, sequence)
+    }
+
+    /// Builds a position on the standard 7x6 board.
+    fn from_seq(sequence: &SequencePosition) -> Result<Self, PositionError> where Self: Sized {
+        Self::from_seq_with_dims(7, 6, sequence)
+    }
+
+    /// Lowest score of the board: the second player wins with their `win_length`-th disc.
+    fn min_score(&self) -> i32 {
+        -((self.width()*self.height()) as i32)/2 + self.win_length() as i32 - 1
+    }
 
-    // AnticipatingBitboardPosition
-    fn key(&self) -> u64;
+    /// Highest score of the board: the first player wins with their `win_length`-th disc.
+    fn max_score(&self) -> i32 {
+        (self.width()*self.height()+1) as i32/2 - self.win_length() as i32 + 1
+    }
+}
+
+/// Positions that can be stored in a transposition table.
+pub trait KeyedPosition: Position {
+    /// Integer holding the keys, and the moves of the positions with bitboard capabilities.
+    type Bitboard: Bitboard;
+
+    fn key(&self) -> Self::Bitboard;
+    /// Smallest key among the position and its left-right reflection, which share their score.
+    fn canonical_key(&self) -> Self::Bitboard;
+
+    /// Maps `column` between the position and the orientation of its canonical key.
+    /// The mapping is its own inverse.
+    fn canonical_column(&self, column: usize) -> usize {
+        if self.key() == self.canonical_key() {
+            column
+        } else {
+            self.width() - 1 - column
+        }
+    }
+}
+
+/// Positions able to detect immediate threats, used to prune loosing moves.
+pub trait AnticipatingPosition: KeyedPosition {
     fn can_win_next(&self) -> bool;
-    fn possible_non_loosing_moves(&self) -> u64;
+    /// Bitmap of the playable cells that do not give the opponent an immediate win.
+    fn possible_non_loosing_moves(&self) -> Self::Bitboard;
+}
+
+/// Positions whose moves can be scored and played directly from a bitmap.
+pub trait BitboardMovePosition: AnticipatingPosition {
+    fn move_score(&self, move_bit: Self::Bitboard) -> usize;
+    fn play_move(&mut self, move_bit: Self::Bitboard);
+}
 
-    // BitboardPositionWithOrdering
-    fn move_score(&self, move_bit: u64) -> usize;
-    fn play_move(&mut self, move_bit: u64);
+/// Columns of a board of `width` columns, starting from the center ones,
+/// which take part in the most alignments.
+pub fn center_first(width: usize) -> Vec<usize> {
+    let mut columns: Vec<usize> = (0..width).collect();
+    columns.sort_by_key(|&column| (2*column as i32 - width as i32 + 1).abs());
+    columns
 }
 
-#[derive(Debug, Clone, PartialEq, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Copy)]
 pub enum Cell {
     Red,
     Yellow,
@@ -50,14 +112,130 @@ impl Cell {
     }
 }
 
-pub trait Solver{
-    fn solve(&mut self, position: &(impl Position + Clone)) -> i32;
+pub trait Solver<P: Position> {
+    fn solve(&mut self, position: &P) -> i32;
     fn explored_positions(&self) -> usize;
     fn reset_explored_positions(&mut self);
 }
 
-pub trait WeakSolver{
-    fn weak_solve(&mut self, position: &(impl Position + Clone)) -> i32;
+pub trait WeakSolver<P: Position> {
+    fn weak_solve(&mut self, position: &P) -> i32;
     fn explored_positions(&self) -> usize;
     fn reset_explored_positions(&mut self);
-}
\ No newline at end of file
+}
+
+/// Result of the analysis of a position, from the point of view of the player to move.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    /// Meaningless when the search was cancelled.
+    pub score: i32,
+    /// `None` when no move can be played, or when the search was cancelled.
+    pub best_move: Option<usize>,
+    /// Sequence of optimal moves for both players, starting with `best_move`.
+    pub principal_variation: Vec<usize>
+}
+
+/// Solvers able to tell which move leads to the score of a position.
+/// Optimal moves are found by solving the children of the position, which is
+/// cheap once the transposition table of the solver holds the parent search.
+pub trait Analyser<P: Position + Clone>: Solver<P> {
+    /// Column that is likely to be optimal in `position`, checked before the others.
+    fn hint(&self, _position: &P) -> Option<usize> {
+        None
+    }
+
+    /// Whether the last search was cancelled, leaving its score meaningless.
+    /// Solvers whose searches cannot be cancelled keep the default.
+    fn is_cancelled(&mut self) -> bool {
+        false
+    }
+
+    /// Score of the position. Immediate wins are detected here, since some
+    /// solvers expect the player to move not to have one.
+    fn score(&mut self, position: &P) -> i32 {
+        let size = position.width() * position.height();
+        if position.nb_moves() >= size {
+            return 0;
+        }
+        if (0..position.width()).any(|column| position.can_play(column) && position.is_winning_move(column)) {
+            return ((size + 1 - position.nb_moves()) as i32)/2;
+        }
+        self.solve(position)
+    }
+
+    /// Returns a legal move of `position` reaching `score`, the score of the position.
+    /// `None` when no move can be played, when `score` is not the score of the position,
+    /// or when the search is cancelled.
+    fn optimal_move(&mut self, position: &P, score: i32) -> Option<usize> {
+        let columns = self.hint(position).into_iter().chain(0..position.width());
+        for column in columns.collect::<Vec<_>>() {
+            if column < position.width() && position.can_play(column) {
+                let move_score = self.move_score(position, column);
+                if self.is_cancelled() {
+                    return None;
+                }
+                if move_score == score {
+                    return Some(column);
+                }
+            }
+        }
+        None
+    }
+
+    /// Score of playing the legal move `column` in `position`.
+    fn move_score(&mut self, position: &P, column: usize) -> i32 {
+        if position.is_winning_move(column) {
+            ((position.width() * position.height() + 1 - position.nb_moves()) as i32)/2
+        } else {
+            let mut position2 = position.clone();
+            position2.play(column);
+            -self.score(&position2)
+        }
+    }
+
+    /// Returns the score of the position along with an optimal move.
+    /// When the search is cancelled, the move is `None` and the score meaningless.
+    fn best_move(&mut self, position: &P) -> (i32, Option<usize>) {
+        let score = self.score(position);
+        if self.is_cancelled() {
+            return (score, None);
+        }
+        (score, self.optimal_move(position, score))
+    }
+
+    /// Returns the score, an optimal move, and the principal variation
+    /// played until the end of the game. A cancelled search leaves the principal variation
+    /// at the moves proven before it.
+    fn analyse(&mut self, position: &P) -> Analysis {
+        let score = self.score(position);
+        if self.is_cancelled() {
+            return Analysis { score, best_move: None, principal_variation: Vec::new() };
+        }
+        let principal_variation = self.principal_variation(position, score);
+        Analysis { score, best_move: principal_variation.first().copied(), principal_variation }
+    }
+
+    /// Optimal moves for both players from `position`, whose score is `score`, until the end
+    /// of the game. Solvers storing their best moves can walk them instead of searching each ply.
+    fn principal_variation(&mut self, position: &P, score: i32) -> Vec<usize> {
+        let mut principal_variation = Vec::new();
+        let mut position = position.clone();
+        let mut position_score = score;
+
+        while let Some(column) = self.optimal_move(&position, position_score) {
+            principal_variation.push(column);
+            if position.is_winning_move(column) {
+                break;
+            }
+            position.play(column);
+            position_score = -position_score;
+        }
+        principal_variation
+    }
+}
+
+/// Strategy choosing the moves of one side of a game.
+pub trait Player<P: Position> {
+    /// Column to play in `position`, `None` when no move can be played.
+    fn choose_move(&mut self, position: &P) -> Option<usize>;
+}
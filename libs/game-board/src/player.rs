@@ -0,0 +1,243 @@
+use crate::{Position, Player, Analyser, Cell};
+use crate::sequence_position::SequencePosition;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Xorshift64* generator: enough to vary the moves of the players, and reproducible from a seed.
+pub struct Random(u64);
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // The state must never be zero
+        Self(seed | 1)
+    }
+
+    /// Generator seeded with the current time.
+    pub fn from_time() -> Self {
+        Self::new(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64))
+    }
+
+    /// Uniform number in `[0, 1)`.
+    pub fn uniform(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545F4914F6CDD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform index in `0..length`.
+    pub fn below(&mut self, length: usize) -> usize {
+        ((self.uniform() * length as f64) as usize).min(length - 1)
+    }
+}
+
+/// Plays an optimal move found by any solver able to analyse positions.
+pub struct SolverPlayer<S> {
+    solver: S
+}
+
+impl<S> SolverPlayer<S> {
+    pub fn new(solver: S) -> Self {
+        Self { solver }
+    }
+
+    pub fn solver(&mut self) -> &mut S {
+        &mut self.solver
+    }
+}
+
+impl<P: Position + Clone, S: Analyser<P>> Player<P> for SolverPlayer<S> {
+    fn choose_move(&mut self, position: &P) -> Option<usize> {
+        if position.nb_moves() >= position.width() * position.height() {
+            return None;
+        }
+        self.solver.best_move(position).1
+    }
+}
+
+/// Plays any legal move, uniformly.
+pub struct RandomPlayer {
+    random: Random
+}
+
+impl Default for RandomPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RandomPlayer {
+    pub fn new() -> Self {
+        Self { random: Random::from_time() }
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self { random: Random::new(seed) }
+    }
+}
+
+impl<P: Position> Player<P> for RandomPlayer {
+    fn choose_move(&mut self, position: &P) -> Option<usize> {
+        let columns: Vec<usize> = (0..position.width()).filter(|&column| position.can_play(column)).collect();
+        match columns.len() {
+            0 => None,
+            length => Some(columns[self.random.below(length)])
+        }
+    }
+}
+
+/// Replays a recorded game: in a position of `n` moves, plays the `n`-th move of the record,
+/// so that both sides can share the same record.
+pub struct ScriptedPlayer {
+    moves: Vec<usize>
+}
+
+impl ScriptedPlayer {
+    /// Record of the whole game, including the moves of the starting position.
+    pub fn new(sequence: &SequencePosition) -> Self {
+        Self { moves: sequence.sequence().iter().map(|column| column - 1).collect() }
+    }
+}
+
+impl<P: Position> Player<P> for ScriptedPlayer {
+    /// `None` once the record is over.
+    fn choose_move(&mut self, position: &P) -> Option<usize> {
+        self.moves.get(position.nb_moves()).copied()
+    }
+}
+
+/// How a game between two players ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchOutcome {
+    Win(Cell),
+    Draw,
+    /// The player had no move to play while the game was not over, such as a finished script.
+    Abandoned(Cell)
+}
+
+/// Moves played during a match, starting from the given position, and how it ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchRecord {
+    pub moves: Vec<usize>,
+    pub outcome: MatchOutcome
+}
+
+/// Plays a game from `position`, which must not be won yet, until it ends. `players` are
+/// the red and the yellow players, and `observer` is called after each move with
+/// the new position and the column played.
+pub fn play_match<P: Position + Clone>(mut position: P, players: [&mut dyn Player<P>; 2], mut observer: impl FnMut(&P, usize)) -> MatchRecord {
+    let [red, yellow] = players;
+    let mut moves = Vec::new();
+
+    loop {
+        if position.nb_moves() >= position.width() * position.height() {
+            return MatchRecord { moves, outcome: MatchOutcome::Draw };
+        }
+
+        let player_turn = position.player_turn();
+        let player = if player_turn == Cell::Red { &mut *red } else { &mut *yellow };
+        let column = match player.choose_move(&position) {
+            Some(column) => column,
+            None => return MatchRecord { moves, outcome: MatchOutcome::Abandoned(player_turn) }
+        };
+        assert!(column < position.width() && position.can_play(column), "Column {} is not a legal move.", column + 1);
+
+        let winning = position.is_winning_move(column);
+        position.play(column);
+        moves.push(column);
+        observer(&position, column);
+
+        if winning {
+            return MatchRecord { moves, outcome: MatchOutcome::Win(player_turn) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod player_tests {
+    use super::*;
+    use crate::Solver;
+    use crate::bitboard_position_with_ordering::BitboardPositionWithOrdering;
+
+    /// Minimal solver telling only immediate wins apart.
+    struct ImmediateWinSolver;
+
+    impl<P: Position> Solver<P> for ImmediateWinSolver {
+        fn solve(&mut self, _position: &P) -> i32 {
+            0
+        }
+
+        fn explored_positions(&self) -> usize {
+            0
+        }
+
+        fn reset_explored_positions(&mut self) {}
+    }
+
+    impl<P: Position + Clone> Analyser<P> for ImmediateWinSolver {}
+
+    fn sequence(moves: &str) -> SequencePosition {
+        SequencePosition::parse(moves).unwrap()
+    }
+
+    #[test]
+    fn scripted_game() {
+        let mut red = ScriptedPlayer::new(&sequence("1212121"));
+        let mut yellow = ScriptedPlayer::new(&sequence("1212121"));
+        let mut observed = Vec::new();
+        let record = play_match(BitboardPositionWithOrdering::new(7, 6), [&mut red, &mut yellow], |position, column| {
+            observed.push((position.nb_moves(), column));
+        });
+
+        assert_eq!(record.moves, vec![0, 1, 0, 1, 0, 1, 0]);
+        assert_eq!(record.outcome, MatchOutcome::Win(Cell::Red));
+        assert_eq!(observed.len(), 7);
+        assert_eq!(observed[6], (7, 0));
+    }
+
+    #[test]
+    fn script_over() {
+        let mut red = ScriptedPlayer::new(&sequence("4453"));
+        let mut yellow = ScriptedPlayer::new(&sequence("4453"));
+        let start = BitboardPositionWithOrdering::from_seq(&sequence("44")).unwrap();
+        let record = play_match(start, [&mut red, &mut yellow], |_, _| ());
+
+        assert_eq!(record.moves, vec![4, 2]);
+        assert_eq!(record.outcome, MatchOutcome::Abandoned(Cell::Red));
+    }
+
+    #[test]
+    fn random_games_end() {
+        for seed in 0..20 {
+            let (mut red, mut yellow) = (RandomPlayer::with_seed(seed), RandomPlayer::with_seed(seed + 100));
+            let record = play_match(BitboardPositionWithOrdering::new(4, 4), [&mut red, &mut yellow], |_, _| ());
+
+            let position = BitboardPositionWithOrdering::from_seq_with_dims(4, 4, &sequence(
+                &record.moves.iter().map(|column| (column + 1).to_string()).collect::<String>()
+            ));
+            assert!(position.is_ok());
+            assert!(matches!(record.outcome, MatchOutcome::Win(_) | MatchOutcome::Draw));
+            assert!(record.outcome != MatchOutcome::Draw || record.moves.len() == 16);
+        }
+    }
+
+    #[test]
+    fn solver_takes_wins() {
+        let mut red = SolverPlayer::new(ImmediateWinSolver);
+        // Only the yellow moves of the record are played
+        let mut yellow = ScriptedPlayer::new(&sequence("1717171"));
+        let record = play_match(BitboardPositionWithOrdering::new(7, 6), [&mut red, &mut yellow], |_, _| ());
+
+        // Red sees nothing but immediate wins, and otherwise plays the first column not loosing at once
+        assert_eq!(record.outcome, MatchOutcome::Win(Cell::Red));
+        assert_eq!(record.moves, vec![0, 6, 0, 6, 0, 6, 0]);
+    }
+
+    #[test]
+    fn same_seed_same_moves() {
+        let position = BitboardPositionWithOrdering::new(7, 6);
+        let (mut first, mut second) = (RandomPlayer::with_seed(9), RandomPlayer::with_seed(9));
+        for _ in 0..10 {
+            assert_eq!(first.choose_move(&position), second.choose_move(&position));
+        }
+    }
+}
@@ -1,23 +1,163 @@
-#[derive(Debug, PartialEq)]
+use std::fmt;
+use crate::Position;
+
+/// Reasons why a move sequence cannot be turned into a position.
+/// Columns are reported 1-indexed, as written in the sequence.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PositionError {
+    InvalidCharacter { index: usize, character: char },
+    ColumnOutOfRange { ply: usize, column: usize, width: usize },
+    ColumnFull { ply: usize, column: usize },
+    MoveAfterWin { ply: usize }
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use PositionError::*;
+        match self {
+            InvalidCharacter { index, character } =>
+                write!(f, "invalid character '{}' at index {}", character, index),
+            ColumnOutOfRange { ply, column, width } =>
+                write!(f, "column {} at ply {} is out of range for a board of width {}", column, ply, width),
+            ColumnFull { ply, column } =>
+                write!(f, "column {} is already full at ply {}", column, ply),
+            MoveAfterWin { ply } =>
+                write!(f, "move played at ply {} while the game is already won", ply)
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct SequencePosition {
     sequence: Vec<usize>
 }
 
 impl SequencePosition {
+    /// Parses a sequence of 1-indexed column digits, such as `"4453"`.
+    /// Only the characters are checked here: board-dependent errors are
+    /// reported when the sequence is played on a position.
+    pub fn parse(value: &str) -> Result<Self, PositionError> {
+        let sequence = value
+            .chars()
+            .enumerate()
+            .map(|(index, character)| match character.to_digit(10) {
+                Some(digit) => Ok(digit as usize),
+                None => Err(PositionError::InvalidCharacter { index, character })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { sequence })
+    }
+
     pub fn sequence(&self) -> &Vec<usize> {
         &self.sequence
     }
+
+    /// Plays every move of the sequence on `position`, checking that each
+    /// column exists, is not full, and that the game was not already won.
+    pub fn play_on(&self, position: &mut impl Position) -> Result<(), PositionError> {
+        let mut won = false;
+
+        for (ply, &column) in self.sequence.iter().enumerate() {
+            if won {
+                return Err(PositionError::MoveAfterWin { ply });
+            }
+            if column == 0 || column > position.width() {
+                return Err(PositionError::ColumnOutOfRange { ply, column, width: position.width() });
+            }
+            if !position.can_play(column-1) {
+                return Err(PositionError::ColumnFull { ply, column });
+            }
+
+            won = position.is_winning_move(column-1);
+            position.play(column-1);
+        }
+
+        Ok(())
+    }
 }
 
-impl From<&String> for SequencePosition {
-    fn from(value: &String) -> Self {
-        let sequence = value
-            .chars()
-            .map(|c| c.to_digit(10).unwrap() as usize)
-            .collect();
+impl TryFrom<&str> for SequencePosition {
+    type Error = PositionError;
 
-        Self {
-            sequence
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::parse(value)
+    }
+}
+
+#[cfg(test)]
+mod sequence_position_tests {
+    use super::*;
+    use crate::grid_position::GridPosition;
+
+    mod parse {
+        use super::*;
+
+        #[test]
+        fn valid_sequence() {
+            assert_eq!(
+                SequencePosition::parse("4453").unwrap().sequence(),
+                &vec![4, 4, 5, 3]
+            );
+        }
+
+        #[test]
+        fn invalid_character() {
+            assert_eq!(
+                SequencePosition::parse("44a3"),
+                Err(PositionError::InvalidCharacter { index: 2, character: 'a' })
+            );
+        }
+    }
+
+    mod play_on {
+        use super::*;
+
+        #[test]
+        fn column_out_of_range() {
+            let mut position = GridPosition::new(7, 6);
+            assert_eq!(
+                SequencePosition::parse("448").unwrap().play_on(&mut position),
+                Err(PositionError::ColumnOutOfRange { ply: 2, column: 8, width: 7 })
+            );
+        }
+
+        #[test]
+        fn column_zero() {
+            let mut position = GridPosition::new(7, 6);
+            assert_eq!(
+                SequencePosition::parse("0").unwrap().play_on(&mut position),
+                Err(PositionError::ColumnOutOfRange { ply: 0, column: 0, width: 7 })
+            );
+        }
+
+        #[test]
+        fn column_full() {
+            let mut position = GridPosition::new(7, 6);
+            assert_eq!(
+                SequencePosition::parse("1111111").unwrap().play_on(&mut position),
+                Err(PositionError::ColumnFull { ply: 6, column: 1 })
+            );
+        }
+
+        #[test]
+        fn move_after_win() {
+            let mut position = GridPosition::new(7, 6);
+            assert_eq!(
+                SequencePosition::parse("12121213").unwrap().play_on(&mut position),
+                Err(PositionError::MoveAfterWin { ply: 7 })
+            );
+        }
+
+        #[test]
+        fn winning_move_last() {
+            let mut position = GridPosition::new(7, 6);
+            assert_eq!(
+                SequencePosition::parse("1212121").unwrap().play_on(&mut position),
+                Ok(())
+            );
         }
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,4 @@
-use crate::{*, sequence_position::SequencePosition};
+use crate::{*, sequence_position::{SequencePosition, PositionError}, bitboard_position_with_ordering::BitboardPositionWithOrdering};
 
 const FIRST_PLAYER: Cell = Cell::Red;
 
@@ -8,14 +8,24 @@ pub struct StackPosition {
     width: usize,
     height: usize,
     stacks: Vec<Vec<Cell>>,
-    nb_moves: usize
+    nb_moves: usize,
+    win_length: usize
 }
 
 impl StackPosition {
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_win_length(width, height, DEFAULT_WIN_LENGTH)
+    }
+
+    pub fn with_win_length(width: usize, height: usize, win_length: usize) -> Self {
+        assert!(win_length >= 2, "At least two aligned discs are needed to win.");
         let stacks = (0..width).map(|_| vec![]).collect();
 
-        Self { player_turn: FIRST_PLAYER, width, height, stacks, nb_moves: 0 }
+        Self { player_turn: FIRST_PLAYER, width, height, stacks, nb_moves: 0, win_length }
+    }
+
+    pub fn stacks(&self) -> &Vec<Vec<Cell>> {
+        &self.stacks
     }
 }
 
@@ -52,11 +62,9 @@ impl Position for StackPosition {
     fn is_winning_move(&self, column: usize) -> bool {
         let line = self.stacks[column].len();
 
-        // Vertical align: check if the 3 cells below are of the player's color
-        if line >= 3
-            && self.stacks[column][line-3] == self.player_turn
-            && self.stacks[column][line-2] == self.player_turn
-            && self.stacks[column][line-1] == self.player_turn {
+        // Vertical align: check if the cells below are of the player's color
+        if line >= self.win_length - 1
+            && self.stacks[column][line+1-self.win_length..].iter().all(|&cell| cell == self.player_turn) {
                 return true;
         }
 
@@ -77,7 +85,7 @@ impl Position for StackPosition {
                  }
             }
 
-            if nb_nearby >= 3 {
+            if nb_nearby >= self.win_length - 1 {
                 return true;
             }
         }
@@ -89,42 +97,56 @@ impl Position for StackPosition {
         self.nb_moves
     }
 
-    fn from_seq(sequence: &SequencePosition) -> Self {
-        Self::from(sequence)
+    fn win_length(&self) -> usize {
+        self.win_length
+    }
+
+    fn from_seq_with_win_length(width: usize, height: usize, win_length: usize, sequence: &SequencePosition) -> Result<Self, PositionError> {
+        let mut position = Self::with_win_length(width, height, win_length);
+        sequence.play_on(&mut position)?;
+
+        Ok(position)
     }
+}
+
+impl KeyedPosition for StackPosition {
+    type Bitboard = u64;
 
     fn key(&self) -> u64 {
-        todo!("key not implemented for StackPosition");
+        BitboardPositionWithOrdering::from(self).key()
+    }
+
+    fn canonical_key(&self) -> u64 {
+        BitboardPositionWithOrdering::from(self).canonical_key()
     }
+}
 
+impl AnticipatingPosition for StackPosition {
     fn can_win_next(&self) -> bool {
-        todo!("can_win_next not implemented for StackPosition");
+        (0..self.width).any(|column| self.can_play(column) && self.is_winning_move(column))
     }
 
     fn possible_non_loosing_moves(&self) -> u64 {
-        todo!("possible_non_loosing_moves not implemented for StackPosition");
+        BitboardPositionWithOrdering::from(self).possible_non_loosing_moves()
     }
+}
 
-    fn move_score(&self, _move_bit: u64) -> usize {
-        todo!("move_score not implemented for StackPosition")
+impl BitboardMovePosition for StackPosition {
+    fn move_score(&self, move_bit: u64) -> usize {
+        BitboardPositionWithOrdering::from(self).move_score(move_bit)
     }
 
-    fn play_move(&mut self, _move_bit: u64) {
-        todo!("play_move not implemented for StackPosition")
+    fn play_move(&mut self, move_bit: u64) {
+        let column = move_bit.trailing_zeros() as usize / (self.height + 1);
+        self.play(column);
     }
 }
 
-impl From<&SequencePosition> for StackPosition {
-    fn from(sequence_position: &SequencePosition) -> Self {
-        let mut grid_position = StackPosition::new(7, 6);        
-        let mut player = FIRST_PLAYER;
-
-        for column in sequence_position.sequence() {
-            grid_position.play(column-1);
-            player = player.swap_turn();
-        }
+impl TryFrom<&SequencePosition> for StackPosition {
+    type Error = PositionError;
 
-        grid_position
+    fn try_from(sequence_position: &SequencePosition) -> Result<Self, Self::Error> {
+        StackPosition::from_seq(sequence_position)
     }
 }
 
@@ -222,7 +244,7 @@ mod tests {
         fn sequence_empty() {
             let expected_result = StackPosition::new(7, 6);
             assert_eq!(
-                StackPosition::from(&SequencePosition::from(&"".to_string())),
+                StackPosition::try_from(&SequencePosition::parse("").unwrap()).unwrap(),
                 expected_result
             )
         } 
@@ -239,9 +261,63 @@ mod tests {
             expected_result.play(6);
 
             assert_eq!(
-                StackPosition::from(&SequencePosition::from(&"1234567".to_string())),
+                StackPosition::try_from(&SequencePosition::parse("1234567").unwrap()).unwrap(),
                 expected_result
             )
         } 
+
+        #[test]
+        fn sequence_with_dims() {
+            let mut expected_result = StackPosition::new(5, 4);
+            expected_result.play(0);
+            expected_result.play(4);
+
+            assert_eq!(
+                StackPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("15").unwrap()).unwrap(),
+                expected_result
+            );
+            assert_eq!(
+                StackPosition::from_seq_with_dims(5, 4, &SequencePosition::parse("16").unwrap()),
+                Err(PositionError::ColumnOutOfRange { ply: 1, column: 6, width: 5 })
+            );
+        }
+    }
+
+    mod bitboard_compatibility {
+        use super::*;
+
+        #[test]
+        fn same_key_as_bitboard() {
+            let sequence = SequencePosition::parse("4453372").unwrap();
+            assert_eq!(
+                StackPosition::from_seq(&sequence).unwrap().key(),
+                BitboardPositionWithOrdering::from_seq(&sequence).unwrap().key()
+            );
+        }
+
+        #[test]
+        fn same_non_loosing_moves_as_bitboard() {
+            // Red threatens to win in column 4
+            let sequence = SequencePosition::parse("41424").unwrap();
+            let position = StackPosition::from_seq(&sequence).unwrap();
+            let bitboard_position = BitboardPositionWithOrdering::from_seq(&sequence).unwrap();
+
+            assert!(!position.can_win_next());
+            assert_eq!(position.possible_non_loosing_moves(), bitboard_position.possible_non_loosing_moves());
+        }
+
+        #[test]
+        fn play_move() {
+            let sequence = SequencePosition::parse("4453").unwrap();
+            let mut position = StackPosition::from_seq(&sequence).unwrap();
+            let mut expected_position = position.clone();
+
+            let move_bit = position.possible_non_loosing_moves() & (((1 << 6) - 1) << (2 * 7));
+            assert_eq!(position.move_score(move_bit), BitboardPositionWithOrdering::from_seq(&sequence).unwrap().move_score(move_bit));
+
+            position.play_move(move_bit);
+            expected_position.play(2);
+            assert_eq!(position, expected_position);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,491 @@
+use std::fmt;
+use crate::{Cell, FIRST_PLAYER, DEFAULT_WIN_LENGTH, center_first};
+
+/// Rule sets beyond dropping discs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Variant {
+    /// Discs are only dropped, as in the standard game.
+    Standard,
+    /// Players may also pop one of their discs out of the bottom of a column,
+    /// which moves the column down. A pop aligning discs of both players wins for
+    /// the player who popped. A full board only ends the game, in a draw, when the
+    /// player to move has no disc to pop.
+    PopOut,
+    /// Both edge columns start full, with alternating colors.
+    FiveInARow,
+    /// Players first fill the board row by row, without any win. They then pop their
+    /// own discs: a disc part of an alignment of its color is kept and its owner plays
+    /// again, any other disc has to be dropped back on the board. The first player
+    /// keeping `target` discs wins. A player without any disc at the bottom passes.
+    PopTen { target: usize }
+}
+
+/// Board and rules of a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rules {
+    pub variant: Variant,
+    pub width: usize,
+    pub height: usize,
+    pub win_length: usize
+}
+
+impl Rules {
+    pub fn standard(width: usize, height: usize) -> Self {
+        Self { variant: Variant::Standard, width, height, win_length: DEFAULT_WIN_LENGTH }
+    }
+
+    pub fn pop_out(width: usize, height: usize) -> Self {
+        Self { variant: Variant::PopOut, width, height, win_length: DEFAULT_WIN_LENGTH }
+    }
+
+    /// `width` includes the two edge columns: the commercial game is played on 9x6.
+    pub fn five_in_a_row(width: usize, height: usize) -> Self {
+        Self { variant: Variant::FiveInARow, width, height, win_length: 5 }
+    }
+
+    /// The commercial game is played on 7x6, with a target of 10 discs.
+    pub fn pop_ten(width: usize, height: usize, target: usize) -> Self {
+        Self { variant: Variant::PopTen { target }, width, height, win_length: DEFAULT_WIN_LENGTH }
+    }
+}
+
+/// Moves of the variants, columns being 0-indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Move {
+    Drop(usize),
+    Pop(usize),
+    /// Only played when no other move is available, in Pop Ten.
+    Pass
+}
+
+impl fmt::Display for Move {
+    /// Columns are written 1-indexed, pops being prefixed with `p`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Move::Drop(column) => write!(f, "{}", column + 1),
+            Move::Pop(column) => write!(f, "p{}", column + 1),
+            Move::Pass => write!(f, "pass")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Outcome {
+    Win(Cell),
+    Draw
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Phase {
+    /// Pop Ten: the board is being filled.
+    Setup,
+    Play,
+    /// Pop Ten: the player to move drops back the disc they popped.
+    Replace
+}
+
+/// Everything the rest of the game depends on: two positions with the same key
+/// are a repetition, whatever the number of moves played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VariantKey {
+    discs: [u64; 2],
+    player: u8,
+    phase: Phase,
+    kept: [u8; 2]
+}
+
+/// Bitboard position playing any `Variant`. Unlike the other positions, the discs
+/// of each player are stored, since Pop Ten lets a player move several times in a row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantPosition {
+    rules: Rules,
+    /// Discs of the red and the yellow player, one column after the other.
+    discs: [u64; 2],
+    player: usize,
+    phase: Phase,
+    /// Discs kept by each player in Pop Ten.
+    kept: [usize; 2],
+    nb_moves: usize,
+    outcome: Option<Outcome>
+}
+
+impl VariantPosition {
+    pub fn new(rules: Rules) -> Self {
+        assert!(rules.width*(rules.height+1) <= 64, "The board does not fit inside a 64bits bitboard.");
+        assert!(rules.win_length >= 2, "At least two aligned discs are needed to win.");
+
+        let mut position = Self {
+            rules,
+            discs: [0, 0],
+            player: Self::index(FIRST_PLAYER),
+            phase: Phase::Play,
+            kept: [0, 0],
+            nb_moves: 0,
+            outcome: None
+        };
+
+        match rules.variant {
+            Variant::FiveInARow => {
+                assert!(rules.width >= 3, "Five-in-a-row needs columns between its edge columns.");
+                for line in 0..rules.height {
+                    position.discs[line % 2] |= position.cell_bit(0, line);
+                    position.discs[(line + 1) % 2] |= position.cell_bit(rules.width - 1, line);
+                }
+            },
+            Variant::PopTen { target } => {
+                assert!(target > 0, "Pop Ten needs at least one disc to keep.");
+                position.phase = Phase::Setup;
+            },
+            Variant::Standard | Variant::PopOut => ()
+        }
+
+        position
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn width(&self) -> usize {
+        self.rules.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.rules.height
+    }
+
+    pub fn player_turn(&self) -> Cell {
+        [Cell::Red, Cell::Yellow][self.player]
+    }
+
+    pub fn nb_moves(&self) -> usize {
+        self.nb_moves
+    }
+
+    /// `None` while the game goes on.
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    /// Discs kept by `player` in Pop Ten.
+    pub fn kept(&self, player: Cell) -> usize {
+        self.kept[Self::index(player)]
+    }
+
+    pub fn cell(&self, column: usize, line: usize) -> Cell {
+        let bit = self.cell_bit(column, line);
+        if self.discs[0] & bit != 0 {
+            Cell::Red
+        } else if self.discs[1] & bit != 0 {
+            Cell::Yellow
+        } else {
+            Cell::Empty
+        }
+    }
+
+    pub fn key(&self) -> VariantKey {
+        VariantKey {
+            discs: self.discs,
+            player: self.player as u8,
+            phase: self.phase,
+            kept: [self.kept[0] as u8, self.kept[1] as u8]
+        }
+    }
+
+    /// Legal moves, drops first and from the center columns.
+    pub fn moves(&self) -> Vec<Move> {
+        if self.outcome.is_some() {
+            return Vec::new();
+        }
+
+        let columns = center_first(self.width());
+        let drops = columns.iter().copied().filter(|&column| self.column_height(column) < self.height());
+        let pops = columns.iter().copied()
+            .filter(|&column| self.discs[self.player] & self.cell_bit(column, 0) != 0)
+            .map(Move::Pop);
+
+        match (self.rules.variant, self.phase) {
+            (Variant::PopTen { .. }, Phase::Setup) => {
+                // The board is filled row by row
+                let lowest = (0..self.width()).map(|column| self.column_height(column)).min().unwrap_or(0);
+                drops.filter(|&column| self.column_height(column) == lowest).map(Move::Drop).collect()
+            },
+            (Variant::PopTen { .. }, Phase::Play) => {
+                let pops: Vec<Move> = pops.collect();
+                if pops.is_empty() { vec![Move::Pass] } else { pops }
+            },
+            (Variant::PopOut, _) => drops.map(Move::Drop).chain(pops).collect(),
+            _ => drops.map(Move::Drop).collect()
+        }
+    }
+
+    pub fn can_play(&self, played: Move) -> bool {
+        self.moves().contains(&played)
+    }
+
+    pub fn play(&mut self, played: Move) {
+        assert!(self.can_play(played), "{} is not a legal move.", played);
+
+        let player = self.player;
+        let mut next_player = 1 - player;
+        self.nb_moves += 1;
+
+        match played {
+            Move::Drop(column) => {
+                self.discs[player] |= self.cell_bit(column, self.column_height(column));
+
+                match self.phase {
+                    Phase::Setup => if self.is_full() {
+                        self.phase = Phase::Play;
+                    },
+                    Phase::Replace => self.phase = Phase::Play,
+                    Phase::Play => if self.alignments(self.discs[player]) != 0 {
+                        self.outcome = Some(Outcome::Win(self.player_turn()));
+                    }
+                }
+            },
+            Move::Pop(column) => {
+                let kept = self.alignments(self.discs[player]) & self.cell_bit(column, 0) != 0;
+                let column_mask = self.column_mask(column);
+                for discs in self.discs.iter_mut() {
+                    *discs = (*discs & !column_mask) | ((*discs & column_mask) >> 1 & column_mask);
+                }
+
+                if let Variant::PopTen { target } = self.rules.variant {
+                    if kept {
+                        self.kept[player] += 1;
+                        if self.kept[player] >= target {
+                            self.outcome = Some(Outcome::Win(self.player_turn()));
+                        }
+                    } else {
+                        self.phase = Phase::Replace;
+                    }
+                    // Either the player plays again, or they drop their disc back
+                    next_player = player;
+                } else if self.alignments(self.discs[player]) != 0 {
+                    self.outcome = Some(Outcome::Win(self.player_turn()));
+                } else if self.alignments(self.discs[1 - player]) != 0 {
+                    self.outcome = Some(Outcome::Win(self.player_turn().swap_turn()));
+                }
+            },
+            Move::Pass => ()
+        }
+
+        self.player = next_player;
+        if self.outcome.is_none() && self.moves().is_empty() {
+            self.outcome = Some(Outcome::Draw);
+        }
+    }
+
+    fn index(player: Cell) -> usize {
+        match player {
+            Cell::Red => 0,
+            Cell::Yellow => 1,
+            Cell::Empty => panic!("Empty cells do not belong to a player.")
+        }
+    }
+
+    fn cell_bit(&self, column: usize, line: usize) -> u64 {
+        1 << (column * (self.height() + 1) + line)
+    }
+
+    fn column_mask(&self, column: usize) -> u64 {
+        ((1 << self.height()) - 1) << (column * (self.height() + 1))
+    }
+
+    fn column_height(&self, column: usize) -> usize {
+        ((self.discs[0] | self.discs[1]) & self.column_mask(column)).count_ones() as usize
+    }
+
+    fn is_full(&self) -> bool {
+        (0..self.width()).all(|column| self.column_height(column) == self.height())
+    }
+
+    /// Cells of `discs` belonging to at least one alignment.
+    fn alignments(&self, discs: u64) -> u64 {
+        let (win_length, height) = (self.rules.win_length, self.height());
+        // Alignments longer than the board are skipped, which also keeps the shifts inside the bitboard.
+        let (fits_horizontally, fits_vertically) = (win_length <= self.width(), win_length <= height);
+        let directions = [
+            (height + 1, fits_horizontally),
+            (1, fits_vertically),
+            (height, fits_horizontally && fits_vertically),
+            (height + 2, fits_horizontally && fits_vertically)
+        ];
+
+        // The empty top bit of the columns stops alignments from wrapping around.
+        directions.into_iter()
+            .filter(|&(_, fits)| fits)
+            .fold(0, |cells, (direction, _)| {
+                let starts = (1..win_length).fold(discs, |m, t| m & (discs >> (t * direction)));
+                (0..win_length).fold(cells, |cells, t| cells | starts << (t * direction))
+            })
+    }
+}
+
+#[cfg(test)]
+mod variant_position_tests {
+    use super::*;
+
+    fn play_all(position: &mut VariantPosition, moves: &[Move]) {
+        for &played in moves {
+            position.play(played);
+        }
+    }
+
+    mod standard {
+        use super::*;
+        use crate::{Position, grid_position::GridPosition};
+
+        #[test]
+        fn same_as_grid() {
+            let mut position = VariantPosition::new(Rules::standard(7, 6));
+            let mut grid = GridPosition::new(7, 6);
+            for column in [3, 3, 2, 2, 1, 1, 0] {
+                assert_eq!(position.outcome(), None);
+                assert_eq!(position.player_turn(), grid.player_turn());
+                let winning = grid.is_winning_move(column);
+                position.play(Move::Drop(column));
+                grid.play(column);
+                assert_eq!(position.outcome().is_some(), winning);
+            }
+            assert_eq!(position.outcome(), Some(Outcome::Win(Cell::Red)));
+            assert_eq!(position.moves(), vec![]);
+        }
+
+        #[test]
+        fn full_board_draw() {
+            let mut position = VariantPosition::new(Rules { win_length: 3, ..Rules::standard(2, 2) });
+            play_all(&mut position, &[Move::Drop(0), Move::Drop(1), Move::Drop(0), Move::Drop(1)]);
+            assert_eq!(position.outcome(), Some(Outcome::Draw));
+        }
+
+        #[test]
+        fn only_drops() {
+            let mut position = VariantPosition::new(Rules::standard(3, 3));
+            position.play(Move::Drop(1));
+            assert_eq!(position.moves(), vec![Move::Drop(1), Move::Drop(0), Move::Drop(2)]);
+        }
+
+        #[test]
+        #[should_panic(expected = "p1 is not a legal move.")]
+        fn pop_not_allowed() {
+            let mut position = VariantPosition::new(Rules::standard(3, 3));
+            position.play(Move::Drop(0));
+            position.play(Move::Drop(1));
+            position.play(Move::Pop(0));
+        }
+    }
+
+    mod pop_out {
+        use super::*;
+
+        #[test]
+        fn pop_own_discs() {
+            let mut position = VariantPosition::new(Rules::pop_out(4, 4));
+            play_all(&mut position, &[Move::Drop(0), Move::Drop(1), Move::Drop(0)]);
+            // Yellow can only pop their disc of column 2
+            assert!(position.can_play(Move::Pop(1)));
+            assert!(!position.can_play(Move::Pop(0)));
+
+            position.play(Move::Pop(1));
+            assert_eq!(position.cell(1, 0), Cell::Empty);
+            assert!(position.can_play(Move::Pop(0)));
+
+            // Popping moves the column down
+            position.play(Move::Pop(0));
+            assert_eq!((position.cell(0, 0), position.cell(0, 1)), (Cell::Red, Cell::Empty));
+        }
+
+        #[test]
+        fn full_board_goes_on() {
+            let mut position = VariantPosition::new(Rules { win_length: 3, ..Rules::pop_out(2, 2) });
+            play_all(&mut position, &[Move::Drop(0), Move::Drop(1), Move::Drop(0), Move::Drop(1)]);
+            assert_eq!(position.outcome(), None);
+            assert_eq!(position.moves(), vec![Move::Pop(0)]);
+        }
+
+        #[test]
+        fn pop_aligning_both_players() {
+            // Red pops the bottom of column 3: red aligns on the second line, yellow on the first one
+            let mut position = VariantPosition::new(Rules { win_length: 3, ..Rules::pop_out(3, 3) });
+            play_all(&mut position, &[2, 0, 0, 1, 1, 2, 2, 0].map(Move::Drop));
+            assert_eq!(position.outcome(), None);
+            position.play(Move::Pop(2));
+            assert_eq!(position.outcome(), Some(Outcome::Win(Cell::Red)));
+        }
+
+        #[test]
+        fn pop_aligning_opponent() {
+            let mut position = VariantPosition::new(Rules { win_length: 3, ..Rules::pop_out(3, 3) });
+            // Red pops the bottom of column 3, completing the first line of yellow
+            play_all(&mut position, &[2, 0, 0, 1, 1, 2].map(Move::Drop));
+            position.play(Move::Pop(2));
+            assert_eq!(position.outcome(), Some(Outcome::Win(Cell::Yellow)));
+        }
+    }
+
+    mod five_in_a_row {
+        use super::*;
+
+        #[test]
+        fn edge_columns() {
+            let position = VariantPosition::new(Rules::five_in_a_row(9, 6));
+            assert_eq!((position.cell(0, 0), position.cell(0, 1)), (Cell::Red, Cell::Yellow));
+            assert_eq!((position.cell(8, 0), position.cell(8, 5)), (Cell::Yellow, Cell::Red));
+            assert_eq!(position.moves().len(), 7);
+            assert!(!position.can_play(Move::Drop(0)));
+        }
+
+        #[test]
+        fn five_discs_needed() {
+            let mut position = VariantPosition::new(Rules::five_in_a_row(9, 6));
+            // Yellow aligns four discs with the edge column on the second line
+            play_all(&mut position, &[1, 1, 2, 2, 3, 3].map(Move::Drop));
+            assert_eq!(position.outcome(), None);
+            position.play(Move::Drop(4));
+            assert_eq!(position.outcome(), Some(Outcome::Win(Cell::Red)));
+        }
+    }
+
+    mod pop_ten {
+        use super::*;
+
+        fn filled_board(target: usize) -> VariantPosition {
+            let mut position = VariantPosition::new(Rules { win_length: 2, ..Rules::pop_ten(3, 2, target) });
+            position.play(Move::Drop(1));
+            // The first line is filled before the second one
+            assert_eq!(position.moves(), vec![Move::Drop(0), Move::Drop(2)]);
+            // Alignments do not count while filling the board
+            play_all(&mut position, &[0, 2, 1, 0, 2].map(Move::Drop));
+            assert_eq!(position.outcome(), None);
+            position
+        }
+
+        #[test]
+        fn pop_and_replace() {
+            let mut position = filled_board(2);
+            assert_eq!(position.moves(), vec![Move::Pop(1), Move::Pop(2)]);
+
+            // Aligned with the disc of column 3: kept, and red plays again
+            position.play(Move::Pop(1));
+            assert_eq!((position.kept(Cell::Red), position.player_turn()), (1, Cell::Red));
+
+            // Alone now: red has to drop it back
+            position.play(Move::Pop(2));
+            assert_eq!((position.kept(Cell::Red), position.player_turn()), (1, Cell::Red));
+            assert_eq!(position.moves(), vec![Move::Drop(1), Move::Drop(2)]);
+
+            position.play(Move::Drop(1));
+            assert_eq!(position.player_turn(), Cell::Yellow);
+            assert_eq!(position.moves(), vec![Move::Pop(1), Move::Pop(0), Move::Pop(2)]);
+        }
+
+        #[test]
+        fn target_reached() {
+            let mut position = filled_board(1);
+            position.play(Move::Pop(2));
+            assert_eq!(position.outcome(), Some(Outcome::Win(Cell::Red)));
+        }
+    }
+}
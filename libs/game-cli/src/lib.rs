@@ -1,13 +1,55 @@
-use lib_game_board::{bitboard_position_with_ordering::BitboardPositionWithOrdering, grid_position::GridPosition, Position, sequence_position::SequencePosition, Cell};
+use lib_game_board::{bitboard_position_with_ordering::BitboardPositionWithOrdering, grid_position::GridPosition, Position, sequence_position::{SequencePosition, PositionError}, Cell, Player, center_first};
 use lib_alpha_beta_solver::final_alpha_beta::FinalAlphaBeta;
+use lib_alpha_beta_solver::ai_player::{AiPlayer, Difficulty};
+use lib_alpha_beta_solver::search_limit::SearchLimit;
+use std::time::Duration;
+
+/// Time given to the search of each column when displaying the hints.
+const HINT_LIMIT: SearchLimit = SearchLimit::Time(Duration::from_millis(200));
+
+/// Who chooses the moves of one colour.
+pub enum Side {
+    /// Someone typing the moves, who can also ask for hints and take moves back.
+    Human,
+    Computer(Box<dyn Player<BitboardPositionWithOrdering>>)
+}
 
 pub struct GameCLI {
-    position: BitboardPositionWithOrdering
+    position: BitboardPositionWithOrdering,
+    /// Red and yellow sides, red moving first.
+    sides: [Side; 2],
+    show_hints: bool,
+    /// Columns played since the start of the game, by both players.
+    history: Vec<usize>,
+    /// Columns taken back, the next one to replay being the last.
+    undone: Vec<usize>
+}
+
+/// Actions that the human player can type.
+enum Command {
+    Play(usize),
+    ToggleHints,
+    Undo,
+    Redo
 }
 
 impl GameCLI {
-    pub fn new(width: usize, height: usize) -> Self {
-        Self { position: BitboardPositionWithOrdering::new(width, height) }
+    pub fn new(width: usize, height: usize, red: Side, yellow: Side) -> Self {
+        Self { position: BitboardPositionWithOrdering::new(width, height), sides: [red, yellow], show_hints: false, history: Vec::new(), undone: Vec::new() }
+    }
+
+    /// Starts the game from the position reached by `sequence`, which cannot be taken back.
+    pub fn from_seq(width: usize, height: usize, red: Side, yellow: Side, sequence: &SequencePosition) -> Result<Self, PositionError> {
+        Ok(Self { position: Position::from_seq_with_dims(width, height, sequence)?, ..Self::new(width, height, red, yellow) })
+    }
+
+    /// AI of the game, using the small opening book of the board when it has been generated.
+    pub fn ai_player(width: usize, height: usize, difficulty: Difficulty) -> AiPlayer {
+        let mut solver = FinalAlphaBeta::new(width, height, center_first(width));
+        if let Err(error) = solver.load_opening_book(&Self::book_path(width, height)) {
+            println!("[WARNING] {}: the AI will play without opening book.", error);
+        }
+        AiPlayer::new(solver, difficulty)
     }
 
     pub fn run_game(&mut self) {
@@ -18,51 +60,93 @@ impl GameCLI {
         println!("\n\n");
 
         println!("Loading game files...");
-        let mut solver = FinalAlphaBeta::new(7, 6, vec![3, 4, 2, 5, 1, 6, 0]);
-        solver.load_opening_book("libs/alpha-beta-solver/opening-books/7x6_small.book");
-
-        self.position = Position::from_seq(
-            &SequencePosition::from(&
-                "37313333717124171162542"
-                .to_string())
-        );
-
-        while self.position.nb_moves() < 42 {
-            loop {
-                let column = match self.position.nb_moves() % 2 {
-                    // Human's turn
-                    0 => { 
-                        self.display_board(); 
-                        let column = Self::ask_position();
-                        println!("You played in column {}.", column + 1);
-                        column
-                    }, 
-                    // AI's turn
-                    1 => 
-                    {
-                        let (_score, column) = solver.solve(&self.position);
-                        println!("AI played in column {}.", column + 1);
-                        column
+        let (width, height) = (self.position.width(), self.position.height());
+        let mut solver = FinalAlphaBeta::new(width, height, center_first(width));
+        // Hints are only less precise without book
+        if let Err(error) = solver.load_opening_book(&Self::book_path(width, height)) {
+            println!("[WARNING] {}: the hints will be searched without opening book.", error);
+        }
+        let spectating = self.sides.iter().all(|side| matches!(side, Side::Computer(_)));
+
+        while !self.is_over() {
+            let player = self.position.player_turn();
+            if self.is_human_turn() {
+                self.display_board();
+                if self.show_hints {
+                    self.display_hints(&mut solver);
+                }
+                match Self::ask_position(width) {
+                    Command::Play(column) => match self.play(column) {
+                        Ok(()) => println!("{} played in column {}.", self.name(player), column + 1),
+                        Err(()) => println!("You cannot play in the column {}.", column + 1)
+                    },
+                    Command::ToggleHints => self.show_hints = !self.show_hints,
+                    Command::Undo => if self.undo().is_err() {
+                        println!("There is no move to take back.");
                     },
-                    _ => panic!()
+                    Command::Redo => if self.redo().is_err() {
+                        println!("There is no move to replay.");
+                    }
+                }
+            } else {
+                if spectating {
+                    self.display_board();
+                    Self::wait_for_next_move();
+                }
+                let Side::Computer(computer) = &mut self.sides[Self::side_index(player)] else {
+                    unreachable!()
                 };
-
-                match self.play(column) {
-                    Ok(()) => break,
-                    Err(()) => println!("You cannot play in the column {}.", column + 1)
+                let Some(column) = computer.choose_move(&self.position) else {
+                    println!("{} has no move to play: the game stops here.", self.name(player));
+                    return;
                 };
+                self.play(column).expect("The computer played an illegal move.");
+                println!("{} played in column {}.", self.name(player), column + 1);
             }
+        }
+    }
+
+    fn book_path(width: usize, height: usize) -> String {
+        format!("libs/alpha-beta-solver/opening-books/{}x{}_small.book", width, height)
+    }
+
+    fn side_index(player: Cell) -> usize {
+        match player {
+            Cell::Red => 0,
+            Cell::Yellow => 1,
+            Cell::Empty => panic!("Empty cells have no side.")
+        }
+    }
+
+    /// "You" and "AI" against a single human, the colours otherwise.
+    fn name(&self, player: Cell) -> &'static str {
+        match (&self.sides, player) {
+            ([Side::Human, Side::Computer(_)], Cell::Red) | ([Side::Computer(_), Side::Human], Cell::Yellow) => "You",
+            ([Side::Human, Side::Computer(_)], _) | ([Side::Computer(_), Side::Human], _) => "AI",
+            (_, Cell::Red) => "Red",
+            _ => "Yellow"
+        }
+    }
 
-            let winner = GridPosition::from(&self.position).winning();
-            if winner == Cell::Red {
+    /// Announces the end of the game, the starting position being possibly already won.
+    fn is_over(&self) -> bool {
+        match GridPosition::from(&self.position).winning() {
+            Cell::Red => {
                 self.display_board();
                 println!("\n\n{}\x1b[31;1mRED PLAYER WINS!\x1b[0m", Self::left_shift(29));
-                return;
-            } else if winner == Cell::Yellow {
+                true
+            },
+            Cell::Yellow => {
                 self.display_board();
                 println!("\n\n{}\x1b[93;1m ===\x1b[0m YELLOW PLAYER WINS! \x1b[93;1m=== \x1b[0m", Self::left_shift(29));
-                return;
-            }
+                true
+            },
+            Cell::Empty if self.position.nb_moves() == self.position.width() * self.position.height() => {
+                self.display_board();
+                println!("\n\n{}DRAW!", Self::left_shift(5));
+                true
+            },
+            Cell::Empty => false
         }
     }
 
@@ -95,27 +179,95 @@ impl GameCLI {
             } else {
                 print!("{}", "-".repeat(self.position.width()*4+1));
             }
-            println!("");
+            println!();
         }
 
         print!("{}", left_shift);
         for column in 0..self.position.width() {
             print!("  {} ", column+1);
         }
-        println!("");
+        println!();
+    }
+
+    /// Displays the score of each column under the board: positive scores
+    /// are winning for the player to move, negative ones are loosing.
+    /// The scores not found within `HINT_LIMIT` are estimated, and marked with a `~`.
+    fn display_hints(&self, solver: &mut FinalAlphaBeta) {
+        let left_shift = Self::left_shift(self.position.width()*4+1);
+
+        print!("{}", left_shift);
+        for result in solver.analyze_all_moves_with_limit(&self.position, HINT_LIMIT) {
+            let Some(result) = result else {
+                print!("    ");
+                continue;
+            };
+            let score = if result.is_exact() { result.min.to_string() } else { format!("~{}", result.estimate()) };
+            match result {
+                result if result.min > 0 => print!("\x1b[32m{:>3}\x1b[0m ", score),
+                result if result.max < 0 => print!("\x1b[31m{:>3}\x1b[0m ", score),
+                _ => print!("{:>3} ", score)
+            }
+        }
+        println!();
     }
 
+    fn is_human_turn(&self) -> bool {
+        matches!(self.sides[Self::side_index(self.position.player_turn())], Side::Human)
+    }
+
+    /// Plays a new move, which forgets the moves that were taken back.
     fn play(&mut self, column: usize) -> Result<(), ()> {
         match self.position.can_play(column) {
-            true => Ok(self.position.play(column)),
+            true => {
+                self.position.play(column);
+                self.history.push(column);
+                self.undone.clear();
+                Ok(())
+            },
             false => Err(())
         }
     }
 
-    fn ask_position() -> usize {
+    /// Takes back moves until the human player's last move is undone.
+    fn undo(&mut self) -> Result<(), ()> {
+        let mut taken_back = 0;
+        while let Some(column) = self.history.pop() {
+            self.position.undo(column);
+            self.undone.push(column);
+            taken_back += 1;
+            if self.is_human_turn() {
+                return Ok(());
+            }
+        }
+
+        // Only computers played since the start: their moves are kept
+        for _ in 0..taken_back {
+            let column = self.undone.pop().unwrap();
+            self.position.play(column);
+            self.history.push(column);
+        }
+        Err(())
+    }
+
+    /// Replays the moves taken back until the human player is to move again.
+    fn redo(&mut self) -> Result<(), ()> {
+        if self.undone.is_empty() {
+            return Err(());
+        }
+        while let Some(column) = self.undone.pop() {
+            self.position.play(column);
+            self.history.push(column);
+            if self.is_human_turn() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn ask_position(width: usize) -> Command {
         let mut column: usize;
         loop {
-            println!("\n\nChoose a column to play (between 1 and 7):");
+            println!("\n\nChoose a column to play (between 1 and {}), 'h' to toggle hints, 'u' to take back your last move or 'r' to replay it:", width);
             let mut input = String::new();
             match std::io::stdin().read_line(&mut input) {
                 Ok(_) => (),
@@ -124,7 +276,14 @@ impl GameCLI {
                     continue
                 }
             }
-            match input.replace("\n", "").parse() {
+            let input = input.trim();
+            match input {
+                "h" => return Command::ToggleHints,
+                "u" => return Command::Undo,
+                "r" => return Command::Redo,
+                _ => ()
+            }
+            match input.parse() {
                 Ok(value) => column = value,
                 Err(_) => {
                     println!("Please enter a number.");
@@ -132,19 +291,123 @@ impl GameCLI {
                 }
             }
     
-            if 1 <= column && column <= 7 { 
+            if (1..=width).contains(&column) { 
                 break; 
             } else {
-                println!("Please enter a column number that is between 1 and 7.\n");
+                println!("Please enter a column number that is between 1 and {}.\n", width);
             }
         }
     
-        column - 1 // start indexing the array at 0
+        Command::Play(column - 1) // start indexing the array at 0
     }
     
+    fn wait_for_next_move() {
+        println!("\n\nPress Enter to see the next move.");
+        let mut input = String::new();
+        let _ = std::io::stdin().read_line(&mut input);
+    }
+
     fn left_shift(element_width: usize) -> String {
         let terminal_columns = termsize::get().unwrap().cols;
         let number_spaces_before = (terminal_columns as usize - element_width)/2;
         " ".repeat(number_spaces_before)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod game_cli_tests {
+    use super::*;
+    use lib_game_board::player::{RandomPlayer, ScriptedPlayer};
+
+    fn computer() -> Side {
+        Side::Computer(Box::new(RandomPlayer::with_seed(0)))
+    }
+
+    fn game(columns: &[usize]) -> GameCLI {
+        let mut game = GameCLI::new(7, 6, Side::Human, computer());
+        for &column in columns {
+            game.play(column).unwrap();
+        }
+        game
+    }
+
+    #[test]
+    fn undo_until_human_turn() {
+        let mut game = game(&[3, 3, 2, 4]);
+        assert!(game.undo().is_ok());
+        assert_eq!(game.position, self::game(&[3, 3]).position);
+        assert!(game.undo().is_ok());
+        assert_eq!(game.position, BitboardPositionWithOrdering::new(7, 6));
+        assert!(game.undo().is_err());
+    }
+
+    #[test]
+    fn redo_undone_moves() {
+        let mut game = game(&[3, 3, 2, 4]);
+        game.undo().unwrap();
+        game.undo().unwrap();
+        assert!(game.redo().is_ok());
+        assert_eq!(game.position, self::game(&[3, 3]).position);
+        assert!(game.redo().is_ok());
+        assert_eq!(game.position, self::game(&[3, 3, 2, 4]).position);
+        assert!(game.redo().is_err());
+    }
+
+    #[test]
+    fn new_move_forgets_undone_moves() {
+        let mut game = game(&[3, 3]);
+        game.undo().unwrap();
+        game.play(0).unwrap();
+        assert!(game.redo().is_err());
+        assert_eq!(game.history, vec![0]);
+    }
+
+    #[test]
+    fn ai_moving_first() {
+        let mut game = GameCLI::new(7, 6, computer(), Side::Human);
+        assert!(!game.is_human_turn());
+        game.play(3).unwrap();
+        assert!(game.is_human_turn());
+        // Taking back the human's move also takes back the AI's answer
+        game.play(3).unwrap();
+        game.play(2).unwrap();
+        game.undo().unwrap();
+        assert_eq!(game.position, self::game(&[3]).position);
+        // There is nothing left to take back for the human
+        assert!(game.undo().is_err());
+        assert_eq!(game.history, vec![3]);
+    }
+
+    #[test]
+    fn starting_sequence() {
+        let sequence = SequencePosition::parse("4453").unwrap();
+        let mut game = GameCLI::from_seq(5, 4, Side::Human, computer(), &sequence).unwrap();
+        assert_eq!(game.position.nb_moves(), 4);
+        assert!(game.is_human_turn());
+        // Moves of the starting sequence cannot be taken back
+        assert!(game.undo().is_err());
+
+        let sequence = SequencePosition::parse("6").unwrap();
+        assert!(GameCLI::from_seq(5, 4, Side::Human, computer(), &sequence).is_err());
+    }
+
+    #[test]
+    fn hot_seat() {
+        let mut game = GameCLI::new(7, 6, Side::Human, Side::Human);
+        game.play(3).unwrap();
+        game.play(2).unwrap();
+        // Each human takes back their own move
+        game.undo().unwrap();
+        assert_eq!(game.history, vec![3]);
+        assert_eq!(game.name(Cell::Red), "Red");
+    }
+
+    #[test]
+    fn names() {
+        let game = GameCLI::new(7, 6, computer(), Side::Human);
+        assert_eq!((game.name(Cell::Red), game.name(Cell::Yellow)), ("AI", "You"));
+        let sequence = SequencePosition::parse("44").unwrap();
+        let game = GameCLI::new(7, 6, computer(), Side::Computer(Box::new(ScriptedPlayer::new(&sequence))));
+        assert_eq!((game.name(Cell::Red), game.name(Cell::Yellow)), ("Red", "Yellow"));
+    }
+}
@@ -1,18 +1,24 @@
-use lib_game_board::Solver;
+use lib_game_board::{Position, Solver, Analyser};
 
 pub struct MinMaxSolver {
     explored_positions: usize
 }
 
+impl Default for MinMaxSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MinMaxSolver {
     pub fn new() -> Self {
         Self { explored_positions: 0 }
     }
 }
 
-impl Solver for MinMaxSolver {
+impl<P: Position + Clone> Solver<P> for MinMaxSolver {
     /// Uses negamax to solve the position.
-    fn solve(&mut self, position: &(impl lib_game_board::Position + Clone)) -> i32 {
+    fn solve(&mut self, position: &P) -> i32 {
         self.explored_positions += 1;
         // Draw
         if position.nb_moves() == position.width() * position.height() {
@@ -35,7 +41,7 @@ impl Solver for MinMaxSolver {
                 let mut position2 = position.clone();
                 position2.play(column);
 
-                let score = - self.solve(&mut position2);
+                let score = - self.solve(&position2);
                 if score > best_score {
                     best_score = score;
                 }
@@ -54,6 +60,8 @@ impl Solver for MinMaxSolver {
     }
 }
 
+impl<P: Position + Clone> Analyser<P> for MinMaxSolver {}
+
 #[cfg(test)]
 mod min_max_tests {
     use super::*;
@@ -64,10 +72,10 @@ mod min_max_tests {
         let mut minmax_solver = MinMaxSolver::new();
 
         assert_eq!(minmax_solver.solve(
-        &mut GridPosition::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
+        &GridPosition::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
 
@@ -76,10 +84,10 @@ mod min_max_tests {
         let mut minmax_solver = MinMaxSolver::new();
 
         assert_eq!(minmax_solver.solve(
-        &mut StackPosition::from(
-                    &SequencePosition::from(
-                        &"2252576253462244111563365343671351441".to_string()
-                    ))),
+        &StackPosition::try_from(
+                    &SequencePosition::parse(
+                        "2252576253462244111563365343671351441"
+                    ).unwrap()).unwrap()),
                 -1);
     }
 }
\ No newline at end of file
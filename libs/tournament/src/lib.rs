@@ -0,0 +1,111 @@
+use lib_game_board::{center_first, Player};
+use lib_game_board::player::{RandomPlayer, SolverPlayer};
+use lib_game_board::bitboard_position_with_ordering::BitboardPositionWithOrdering;
+use lib_alpha_beta_solver::{alpha_beta_solver::AlphaBetaSolver, final_alpha_beta::FinalAlphaBeta};
+use lib_alpha_beta_solver::ai_player::{AiPlayer, Difficulty};
+use lib_alpha_beta_solver::opening_book::OpeningBook;
+use lib_min_max_solver::MinMaxSolver;
+use crate::{tournament::{Tournament, all_openings}, standings::Standings};
+
+use progress_bar::*;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+pub mod tournament;
+pub mod rating;
+pub mod standings;
+
+/// Names of the players that can enter a tournament.
+pub const PLAYER_NAMES: [&str; 7] = ["random", "easy", "medium", "hard", "perfect", "alpha_beta", "min_max"];
+
+/// Book of the AI players, unless given another one.
+pub fn default_book_path(width: usize, height: usize) -> String {
+    format!("libs/alpha-beta-solver/opening-books/{}x{}_small.book", width, height)
+}
+
+/// Largest boards, in cells, where the players searching without any limit still find
+/// their first moves within seconds. The perfect AI is only limited without a book.
+fn max_cells(name: &str, has_book: bool) -> Option<usize> {
+    match name {
+        "min_max" => Some(16),
+        "alpha_beta" => Some(20),
+        "perfect" if !has_book => Some(30),
+        _ => None
+    }
+}
+
+/// Player named `name`, among `PLAYER_NAMES`. Its random choices depend on `seed`.
+fn player(name: &str, width: usize, height: usize, book: Option<&Arc<OpeningBook>>, seed: u64) -> Box<dyn Player<BitboardPositionWithOrdering>> {
+    let ai_player = |difficulty| {
+        let mut solver = FinalAlphaBeta::new(width, height, center_first(width));
+        if let Some(book) = book {
+            solver.set_opening_book(Arc::clone(book)).expect("The book was checked against the board.");
+        }
+        Box::new(AiPlayer::with_seed(solver, difficulty, seed))
+    };
+    match name {
+        "random" => Box::new(RandomPlayer::with_seed(seed)),
+        "easy" => ai_player(Difficulty::Easy),
+        "medium" => ai_player(Difficulty::Medium),
+        "hard" => ai_player(Difficulty::Hard),
+        "perfect" => ai_player(Difficulty::Perfect),
+        "alpha_beta" => Box::new(SolverPlayer::new(AlphaBetaSolver::new(center_first(width)))),
+        "min_max" => Box::new(SolverPlayer::new(MinMaxSolver::new())),
+        _ => panic!("Unknown player name.")
+    }
+}
+
+/// Plays a round-robin between the players named `names` on a `width`x`height` board,
+/// from every opening of `depth` moves, prints the standings and writes them to `csv_path`.
+/// The AI players share the book stored in `book_path`, and play without it if it cannot be loaded.
+/// Players searching without limit are refused on the boards where their games would never end.
+pub fn run_tournament(width: usize, height: usize, depth: usize, csv_path: &str, book_path: &str, names: &[String]) {
+    let book = match OpeningBook::open(book_path) {
+        Ok(book) if (book.width(), book.height()) == (width, height) => Some(Arc::new(book)),
+        Ok(book) => {
+            println!("[WARNING] The opening book {} is for a {}x{} board: the AI plays without it.", book_path, book.width(), book.height());
+            None
+        },
+        Err(error) => {
+            println!("[WARNING] Unable to load the opening book: {}. The AI plays without it.", error);
+            None
+        }
+    };
+    for name in names {
+        if let Some(max_cells) = max_cells(name, book.is_some()).filter(|&max_cells| width * height > max_cells) {
+            return println!("\n\n{} searches without limit: it only plays on boards of up to {} cells{}.",
+                name, max_cells, if name == "perfect" { " without an opening book" } else { "" });
+        }
+    }
+
+    let openings = all_openings::<BitboardPositionWithOrdering>(width, height, depth);
+    let mut tournament = Tournament::<BitboardPositionWithOrdering>::new(width, height, openings).unwrap();
+
+    println!("\n\nSelected arguments:");
+    println!("\t- Board: {}x{}", width, height);
+    println!("\t- Openings: {} of {} moves", tournament.openings().len(), depth);
+    println!("\t- Players: {}", names.join(", "));
+    println!("\t- Opening book: {}", if book.is_some() { book_path } else { "none" });
+    println!();
+
+    println!("Loading players...");
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+    for (index, name) in names.iter().enumerate() {
+        tournament.add_player(name, player(name, width, height, book.as_ref(), seed.wrapping_add(index as u64)));
+    }
+
+    let now = Instant::now();
+    init_progress_bar(tournament.nb_games());
+    set_progress_bar_action("Playing", Color::LightBlue, Style::Normal);
+    tournament.run(|_| inc_progress_bar());
+    finalize_progress_bar();
+    println!("{} games played in {:.2?}.\n", tournament.results().len(), now.elapsed());
+
+    let standings = Standings::new(tournament.names(), tournament.results());
+    println!("{}", standings);
+
+    match std::fs::write(csv_path, standings.to_csv()) {
+        Ok(()) => println!("\nStandings written to {}.", csv_path),
+        Err(error) => println!("\n[ERROR] Could not write {}: {}.", csv_path, error)
+    }
+}
@@ -0,0 +1,309 @@
+use crate::tournament::GameResult;
+
+/// Logistic scale of the Elo points: a player rated `d` points above their
+/// opponent is expected to score `1/(1 + 10^(-d/400))`.
+const SCALE: f64 = std::f64::consts::LN_10 / 400.;
+
+/// Quantile of the normal distribution giving 95% confidence intervals.
+const CONFIDENCE: f64 = 1.96;
+
+/// Virtual draws of each player against a player rated 0, which keep the ratings
+/// finite when a player wins or loses all their games. As many virtual draws between
+/// even players keep the advantage of BayesElo finite when red always wins.
+const PRIOR_DRAWS: f64 = 2.;
+
+/// Rating of a player, centered on the average player.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rating {
+    pub elo: f64,
+    /// Half-width of the 95% confidence interval.
+    pub margin: f64
+}
+
+/// Ratings of the BayesElo model, along with the advantage of moving first and the
+/// tendency to draw it estimated from the games.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BayesElo {
+    pub ratings: Vec<Rating>,
+    /// Elo points given to the red player.
+    pub advantage: f64,
+    /// The larger it is, the more draws between players of similar strength.
+    pub draw_elo: f64
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1. / (1. + (-x).exp())
+}
+
+/// Logarithm of the sigmoid, which does not overflow for large negative `x`.
+fn log_sigmoid(x: f64) -> f64 {
+    if x < 0. {
+        x - x.exp().ln_1p()
+    } else {
+        -(-x).exp().ln_1p()
+    }
+}
+
+/// Elo ratings of `nb_players` players: the maximum likelihood of the logistic model,
+/// a draw counting as half a win. Colours are ignored.
+pub fn elo(nb_players: usize, games: &[GameResult]) -> Vec<Rating> {
+    let log_likelihood = |difference: f64, score: f64| {
+        score * log_sigmoid(SCALE * difference) + (1. - score) * log_sigmoid(-SCALE * difference)
+    };
+    let log_posterior = |ratings: &[f64]| {
+        games.iter()
+            .map(|game| log_likelihood(ratings[game.red] - ratings[game.yellow], game.red_score()))
+            .sum::<f64>()
+        + ratings.iter().map(|&rating| PRIOR_DRAWS * log_likelihood(rating, 0.5)).sum::<f64>()
+    };
+
+    let mut ratings = vec![0.; nb_players];
+    let covariances = maximize(&mut ratings, &vec![f64::NEG_INFINITY; nb_players], log_posterior);
+    centered(ratings, &covariances)
+}
+
+/// Log-likelihood of a result scored `score` by red in the BayesElo model, where
+/// `difference` includes the advantage of red.
+fn bayes_log_likelihood(difference: f64, draw_elo: f64, score: f64) -> f64 {
+    let (win, loss) = (SCALE * (difference - draw_elo), SCALE * (-difference - draw_elo));
+    match score {
+        1. => log_sigmoid(win),
+        0. => log_sigmoid(loss),
+        _ => (1. - sigmoid(win) - sigmoid(loss)).max(f64::MIN_POSITIVE).ln()
+    }
+}
+
+/// Ratings of Rémi Coulom's BayesElo: a draw is a result of its own, likelier between
+/// close players, and red gets a bonus for moving first. Ratings, advantage and draw elo
+/// are those of maximum a posteriori, with a prior of virtual draws.
+pub fn bayes_elo(nb_players: usize, games: &[GameResult]) -> BayesElo {
+    // Parameters are the ratings, followed by the advantage and the draw elo
+    let (advantage, draw_elo) = (nb_players, nb_players + 1);
+    let log_posterior = |parameters: &[f64]| {
+        let draws = |difference| PRIOR_DRAWS * bayes_log_likelihood(difference, parameters[draw_elo], 0.5);
+        games.iter()
+            .map(|game| bayes_log_likelihood(
+                parameters[game.red] + parameters[advantage] - parameters[game.yellow],
+                parameters[draw_elo],
+                game.red_score()
+            ))
+            .sum::<f64>()
+        + parameters[..nb_players].iter().map(|&rating| draws(rating)).sum::<f64>()
+        + draws(parameters[advantage])
+    };
+
+    let mut parameters = vec![0.; nb_players + 2];
+    parameters[draw_elo] = 100.;
+    // Draws would have a negative probability below a draw elo of 0
+    let mut minimums = vec![f64::NEG_INFINITY; nb_players + 2];
+    minimums[draw_elo] = 0.;
+    let covariances = maximize(&mut parameters, &minimums, log_posterior);
+
+    BayesElo {
+        ratings: centered(parameters[..nb_players].to_vec(), &covariances),
+        advantage: parameters[advantage],
+        draw_elo: parameters[draw_elo]
+    }
+}
+
+/// Maximises a smooth concave function with Newton's method, keeping the parameters above
+/// their `minimums`. Derivatives are estimated by finite differences. Returns the covariances
+/// of the parameters, given by the inverse of the curvature at the maximum.
+fn maximize(parameters: &mut [f64], minimums: &[f64], function: impl Fn(&[f64]) -> f64) -> Vec<Vec<f64>> {
+    for _ in 0..100 {
+        let (gradient, hessian) = derivatives(parameters, &function);
+        let mut step: Vec<f64> = match inverse(&hessian) {
+            Some(inverse) => inverse.iter().map(|row| -row.iter().zip(&gradient).map(|(a, b)| a * b).sum::<f64>()).collect(),
+            None => gradient.clone()
+        };
+        // Away from the maximum, the Newton step may not go uphill
+        if step.iter().zip(&gradient).map(|(a, b)| a * b).sum::<f64>() <= 0. {
+            step = gradient.clone();
+        }
+
+        // Halves the step until the function increases
+        let current = function(parameters);
+        let mut moved = false;
+        for _ in 0..50 {
+            let candidate: Vec<f64> = parameters.iter().zip(&step).zip(minimums)
+                .map(|((parameter, step), minimum)| (parameter + step).max(*minimum))
+                .collect();
+            if function(&candidate) >= current {
+                let largest_step = candidate.iter().zip(parameters.iter()).map(|(a, b)| (a - b).abs()).fold(0., f64::max);
+                parameters.copy_from_slice(&candidate);
+                moved = largest_step > 1e-6;
+                break;
+            }
+            step.iter_mut().for_each(|step| *step /= 2.);
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    let (_, hessian) = derivatives(parameters, &function);
+    match inverse(&hessian) {
+        Some(inverse) => inverse.iter().map(|row| row.iter().map(|value| -value).collect()).collect(),
+        None => vec![vec![f64::INFINITY; parameters.len()]; parameters.len()]
+    }
+}
+
+/// Gradient and Hessian of `function` at `parameters`, by central differences.
+fn derivatives(parameters: &[f64], function: impl Fn(&[f64]) -> f64) -> (Vec<f64>, Vec<Vec<f64>>) {
+    // Elo points are coarse enough for a large step
+    let step = 0.1;
+    let at = |shifts: &[(usize, f64)]| {
+        let mut shifted = parameters.to_vec();
+        for &(index, shift) in shifts {
+            shifted[index] += shift;
+        }
+        function(&shifted)
+    };
+
+    let size = parameters.len();
+    let center = function(parameters);
+    let gradient = (0..size).map(|i| (at(&[(i, step)]) - at(&[(i, -step)])) / (2. * step)).collect();
+    let second = |i: usize, j: usize| if i == j {
+        (at(&[(i, step)]) - 2. * center + at(&[(i, -step)])) / (step * step)
+    } else {
+        (at(&[(i, step), (j, step)]) - at(&[(i, step), (j, -step)])
+            - at(&[(i, -step), (j, step)]) + at(&[(i, -step), (j, -step)])) / (4. * step * step)
+    };
+    let hessian = (0..size).map(|i| (0..size).map(|j| second(i, j)).collect()).collect();
+    (gradient, hessian)
+}
+
+/// Inverse of a square matrix by Gauss-Jordan elimination, `None` when it is singular.
+fn inverse(matrix: &[Vec<f64>]) -> Option<Vec<Vec<f64>>> {
+    let size = matrix.len();
+    let mut left = matrix.to_vec();
+    let mut right: Vec<Vec<f64>> = (0..size).map(|i| (0..size).map(|j| if i == j { 1. } else { 0. }).collect()).collect();
+
+    for column in 0..size {
+        let pivot = (column..size).max_by(|&a, &b| left[a][column].abs().total_cmp(&left[b][column].abs()))?;
+        if left[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        left.swap(column, pivot);
+        right.swap(column, pivot);
+
+        let factor = left[column][column];
+        left[column].iter_mut().for_each(|value| *value /= factor);
+        right[column].iter_mut().for_each(|value| *value /= factor);
+        for row in 0..size {
+            if row != column {
+                let factor = left[row][column];
+                for index in 0..size {
+                    left[row][index] -= factor * left[column][index];
+                    right[row][index] -= factor * right[column][index];
+                }
+            }
+        }
+    }
+    Some(right)
+}
+
+/// Shifts the ratings so that the average player is rated 0, with margins given by their covariances.
+fn centered(ratings: Vec<f64>, covariances: &[Vec<f64>]) -> Vec<Rating> {
+    let size = ratings.len().max(1) as f64;
+    let mean = ratings.iter().sum::<f64>() / size;
+    // Only the differences between players are known precisely: the margins are those of
+    // the distances to the mean, which do not suffer from the uncertain overall level
+    let row_means: Vec<f64> = (0..ratings.len()).map(|i| covariances[i][..ratings.len()].iter().sum::<f64>() / size).collect();
+    let total_mean = row_means.iter().sum::<f64>() / size;
+    ratings.iter().enumerate()
+        .map(|(i, elo)| {
+            let variance = covariances[i][i] - 2. * row_means[i] + total_mean;
+            Rating { elo: elo - mean, margin: CONFIDENCE * variance.max(0.).sqrt() }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod rating_tests {
+    use super::*;
+    use lib_game_board::Cell;
+    use lib_game_board::player::MatchOutcome;
+
+    fn game(red: usize, yellow: usize, outcome: MatchOutcome) -> GameResult {
+        GameResult { red, yellow, opening: 0, moves: vec![], outcome }
+    }
+
+    /// `wins`, `draws` and `losses` of player 0 against player 1, half of them with each colour.
+    fn duel(wins: usize, draws: usize, losses: usize) -> Vec<GameResult> {
+        let mut games = Vec::new();
+        for (count, winner) in [(wins, Some(0)), (draws, None), (losses, Some(1))] {
+            for index in 0..count {
+                let (red, yellow) = if index % 2 == 0 { (0, 1) } else { (1, 0) };
+                let outcome = match winner {
+                    Some(winner) if winner == red => MatchOutcome::Win(Cell::Red),
+                    Some(_) => MatchOutcome::Win(Cell::Yellow),
+                    None => MatchOutcome::Draw
+                };
+                games.push(game(red, yellow, outcome));
+            }
+        }
+        games
+    }
+
+    #[test]
+    fn even_players() {
+        let ratings = elo(2, &duel(10, 10, 10));
+        assert!(ratings[0].elo.abs() < 1e-6 && ratings[1].elo.abs() < 1e-6);
+        assert!(ratings[0].margin > 0. && (ratings[0].margin - ratings[1].margin).abs() < 1e-6);
+
+        let bayes = bayes_elo(2, &duel(10, 10, 10));
+        assert!(bayes.ratings[0].elo.abs() < 1e-3);
+        assert!(bayes.advantage.abs() < 1e-3);
+    }
+
+    #[test]
+    fn stronger_player() {
+        // 75% score: about 190 points above in the logistic model, lowered by the prior
+        let ratings = elo(2, &duel(300, 0, 100));
+        let difference = ratings[0].elo - ratings[1].elo;
+        assert!((difference - 190.8).abs() < 2., "{}", difference);
+
+        // More games, narrower intervals
+        assert!(elo(2, &duel(30, 0, 10))[0].margin > ratings[0].margin);
+    }
+
+    #[test]
+    fn perfect_score_stays_finite() {
+        let ratings = elo(2, &duel(20, 0, 0));
+        assert!(ratings[0].elo.is_finite() && ratings[0].elo > 200.);
+        let bayes = bayes_elo(2, &duel(20, 0, 0));
+        assert!(bayes.ratings[0].elo.is_finite() && bayes.ratings[0].elo > 200.);
+    }
+
+    #[test]
+    fn first_move_advantage() {
+        // Red wins every game: both players are even, but moving first is worth a lot
+        let mut games = Vec::new();
+        for _ in 0..10 {
+            games.push(game(0, 1, MatchOutcome::Win(Cell::Red)));
+            games.push(game(1, 0, MatchOutcome::Win(Cell::Red)));
+        }
+        let bayes = bayes_elo(2, &games);
+        assert!(bayes.ratings[0].elo.abs() < 1e-3);
+        assert!(bayes.advantage > 200.);
+    }
+
+    #[test]
+    fn draws() {
+        let bayes = bayes_elo(2, &duel(5, 30, 5));
+        let fewer_draws = bayes_elo(2, &duel(15, 10, 15));
+        assert!(bayes.draw_elo > fewer_draws.draw_elo);
+    }
+
+    #[test]
+    fn transitivity() {
+        let mut games = duel(30, 0, 10);
+        games.extend(duel(30, 0, 10).into_iter().map(|game| GameResult { red: game.red + 1, yellow: game.yellow + 1, ..game }));
+        let ratings = elo(3, &games);
+        assert!(ratings[0].elo > ratings[1].elo && ratings[1].elo > ratings[2].elo);
+        assert!((ratings.iter().map(|rating| rating.elo).sum::<f64>()).abs() < 1e-6);
+        let bayes = bayes_elo(3, &games);
+        assert!(bayes.ratings[0].elo > bayes.ratings[1].elo && bayes.ratings[1].elo > bayes.ratings[2].elo);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::rating::{Rating, elo, bayes_elo};
+use crate::tournament::GameResult;
+use std::fmt;
+
+/// Results and ratings of one player.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standing {
+    pub name: String,
+    pub wins: usize,
+    pub draws: usize,
+    pub losses: usize,
+    pub elo: Rating,
+    pub bayes_elo: Rating
+}
+
+impl Standing {
+    pub fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Share of the points won, a draw being worth half a win.
+    pub fn score(&self) -> f64 {
+        (self.wins as f64 + self.draws as f64 / 2.) / self.games().max(1) as f64
+    }
+}
+
+/// Players of a tournament, ranked by BayesElo.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standings {
+    pub players: Vec<Standing>,
+    /// Elo points given to the red player by BayesElo.
+    pub advantage: f64,
+    pub draw_elo: f64
+}
+
+impl Standings {
+    /// Standings of the players named `names`, whose games are `results`.
+    pub fn new(names: &[String], results: &[GameResult]) -> Self {
+        let elo = elo(names.len(), results);
+        let bayes_elo = bayes_elo(names.len(), results);
+
+        let mut players: Vec<Standing> = names.iter().enumerate().map(|(player, name)| {
+            let scores: Vec<f64> = results.iter()
+                .filter(|result| result.red == player || result.yellow == player)
+                .map(|result| result.score(player))
+                .collect();
+            Standing {
+                name: name.clone(),
+                wins: scores.iter().filter(|&&score| score == 1.).count(),
+                draws: scores.iter().filter(|&&score| score == 0.5).count(),
+                losses: scores.iter().filter(|&&score| score == 0.).count(),
+                elo: elo[player],
+                bayes_elo: bayes_elo.ratings[player]
+            }
+        }).collect();
+        players.sort_by(|a, b| b.bayes_elo.elo.total_cmp(&a.bayes_elo.elo));
+
+        Self { players, advantage: bayes_elo.advantage, draw_elo: bayes_elo.draw_elo }
+    }
+
+    /// One line per player, ranked, with a header.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("rank,player,games,wins,draws,losses,score,elo,elo_margin,bayes_elo,bayes_elo_margin\n");
+        for (rank, player) in self.players.iter().enumerate() {
+            csv += &format!("{},{},{},{},{},{},{:.4},{:.1},{:.1},{:.1},{:.1}\n",
+                rank + 1, Self::csv_field(&player.name), player.games(), player.wins, player.draws, player.losses,
+                player.score(), player.elo.elo, player.elo.margin, player.bayes_elo.elo, player.bayes_elo.margin
+            );
+        }
+        csv
+    }
+
+    /// Quotes the fields that would break the CSV line.
+    fn csv_field(value: &str) -> String {
+        if value.contains([',', '"', '\n']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+}
+
+impl fmt::Display for Standings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name_width = self.players.iter().map(|player| player.name.len()).max().unwrap_or(0).max(6);
+        writeln!(f, "Rank  {:<name_width$}  Games   Wins  Draws Losses  Score          Elo     BayesElo", "Player")?;
+        for (rank, player) in self.players.iter().enumerate() {
+            writeln!(f, "{:>4}  {:<name_width$}  {:>5}  {:>5}  {:>5}  {:>5}  {:>4.1}%  {:>+5.0} ± {:<4.0}  {:>+5.0} ± {:<4.0}",
+                rank + 1, player.name, player.games(), player.wins, player.draws, player.losses, player.score() * 100.,
+                player.elo.elo, player.elo.margin, player.bayes_elo.elo, player.bayes_elo.margin
+            )?;
+        }
+        write!(f, "Advantage of the first player: {:+.0} Elo; draw elo: {:.0}", self.advantage, self.draw_elo)
+    }
+}
+
+#[cfg(test)]
+mod standings_tests {
+    use super::*;
+    use lib_game_board::Cell;
+    use lib_game_board::player::MatchOutcome;
+
+    fn standings() -> Standings {
+        let names = ["weak".to_string(), "strong, really".to_string()];
+        let game = |red, yellow, outcome| GameResult { red, yellow, opening: 0, moves: vec![], outcome };
+        let results = vec![
+            game(0, 1, MatchOutcome::Win(Cell::Yellow)),
+            game(1, 0, MatchOutcome::Win(Cell::Red)),
+            game(0, 1, MatchOutcome::Draw),
+            game(1, 0, MatchOutcome::Abandoned(Cell::Yellow))
+        ];
+        Standings::new(&names, &results)
+    }
+
+    #[test]
+    fn ranking() {
+        let standings = standings();
+        assert_eq!(standings.players[0].name, "strong, really");
+        assert_eq!((standings.players[0].wins, standings.players[0].draws, standings.players[0].losses), (3, 1, 0));
+        assert_eq!(standings.players[1].score(), 0.125);
+        assert!(standings.players[0].elo.elo > 0. && standings.players[1].bayes_elo.elo < 0.);
+    }
+
+    #[test]
+    fn csv() {
+        let csv = standings().to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("1,\"strong, really\",4,3,1,0,0.8750,"));
+        assert!(lines[2].starts_with("2,weak,4,0,1,3,0.1250,"));
+        assert!(lines.iter().all(|line| line.matches(',').count() >= 10));
+    }
+
+    #[test]
+    fn table() {
+        let table = standings().to_string();
+        assert_eq!(table.lines().count(), 4);
+        assert!(table.lines().nth(1).unwrap().contains("strong, really"));
+    }
+}
@@ -0,0 +1,201 @@
+use lib_game_board::{Position, Player, Cell};
+use lib_game_board::player::{play_match, MatchOutcome};
+use lib_game_board::sequence_position::{SequencePosition, PositionError};
+
+/// Game played during a tournament, between the players of the given indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameResult {
+    pub red: usize,
+    pub yellow: usize,
+    /// Index of the opening the game started from.
+    pub opening: usize,
+    pub moves: Vec<usize>,
+    pub outcome: MatchOutcome
+}
+
+impl GameResult {
+    /// Points of the red player: 1 for a win, 0.5 for a draw. Abandoning loses the game.
+    pub fn red_score(&self) -> f64 {
+        match self.outcome {
+            MatchOutcome::Win(Cell::Red) | MatchOutcome::Abandoned(Cell::Yellow) => 1.,
+            MatchOutcome::Draw => 0.5,
+            _ => 0.
+        }
+    }
+
+    /// Points of `player`, who must have played the game.
+    pub fn score(&self, player: usize) -> f64 {
+        if player == self.red {
+            self.red_score()
+        } else {
+            assert_eq!(player, self.yellow, "Player {} did not play this game.", player);
+            1. - self.red_score()
+        }
+    }
+}
+
+/// Round-robin tournament: every pair of players meets once from each opening with each colour.
+pub struct Tournament<P: Position + Clone> {
+    names: Vec<String>,
+    players: Vec<Box<dyn Player<P>>>,
+    openings: Vec<(SequencePosition, P)>,
+    results: Vec<GameResult>
+}
+
+impl<P: Position + Clone> Tournament<P> {
+    /// Tournament on a `width`x`height` board. The openings must not be already won.
+    pub fn new(width: usize, height: usize, openings: Vec<SequencePosition>) -> Result<Self, PositionError> {
+        let openings = openings.into_iter()
+            .map(|opening| P::from_seq_with_dims(width, height, &opening).map(|position| (opening, position)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { names: Vec::new(), players: Vec::new(), openings, results: Vec::new() })
+    }
+
+    pub fn add_player(&mut self, name: &str, player: Box<dyn Player<P>>) {
+        self.names.push(name.to_string());
+        self.players.push(player);
+    }
+
+    pub fn names(&self) -> &Vec<String> {
+        &self.names
+    }
+
+    pub fn openings(&self) -> Vec<&SequencePosition> {
+        self.openings.iter().map(|(opening, _)| opening).collect()
+    }
+
+    /// Results of the games played so far.
+    pub fn results(&self) -> &Vec<GameResult> {
+        &self.results
+    }
+
+    /// Number of games of a full round-robin.
+    pub fn nb_games(&self) -> usize {
+        self.players.len() * (self.players.len().saturating_sub(1)) * self.openings.len()
+    }
+
+    /// Plays every game of the round-robin, calling `on_game` after each of them.
+    pub fn run(&mut self, mut on_game: impl FnMut(&GameResult)) {
+        for first in 0..self.players.len() {
+            for second in first+1..self.players.len() {
+                for opening in 0..self.openings.len() {
+                    for (red, yellow) in [(first, second), (second, first)] {
+                        let result = self.play(red, yellow, opening);
+                        on_game(&result);
+                        self.results.push(result);
+                    }
+                }
+            }
+        }
+    }
+
+    fn play(&mut self, red: usize, yellow: usize, opening: usize) -> GameResult {
+        let (first, second) = self.players.split_at_mut(red.max(yellow));
+        let (low, high) = (&mut first[red.min(yellow)], &mut second[0]);
+        let players: [&mut dyn Player<P>; 2] = if red < yellow {
+            [low.as_mut(), high.as_mut()]
+        } else {
+            [high.as_mut(), low.as_mut()]
+        };
+
+        let record = play_match(self.openings[opening].1.clone(), players, |_, _| ());
+        GameResult { red, yellow, opening, moves: record.moves, outcome: record.outcome }
+    }
+}
+
+/// Every sequence of `depth` moves on a `width`x`height` board that does not end the game.
+pub fn all_openings<P: Position + Clone>(width: usize, height: usize, depth: usize) -> Vec<SequencePosition> {
+    let mut openings = Vec::new();
+    let mut to_visit = vec![(String::new(), P::from_seq_with_dims(width, height, &SequencePosition::parse("").unwrap()).unwrap())];
+
+    while let Some((sequence, position)) = to_visit.pop() {
+        if sequence.len() == depth {
+            openings.push(SequencePosition::parse(&sequence).unwrap());
+            continue;
+        }
+        for column in (0..width).rev() {
+            if position.can_play(column) && !position.is_winning_move(column) && position.nb_moves() + 1 < width * height {
+                let mut position2 = position.clone();
+                position2.play(column);
+                to_visit.push((format!("{}{}", sequence, column + 1), position2));
+            }
+        }
+    }
+
+    openings
+}
+
+#[cfg(test)]
+mod tournament_tests {
+    use super::*;
+    use lib_game_board::bitboard_position_with_ordering::BitboardPositionWithOrdering;
+    use lib_game_board::player::{RandomPlayer, ScriptedPlayer};
+
+    fn sequence(moves: &str) -> SequencePosition {
+        SequencePosition::parse(moves).unwrap()
+    }
+
+    #[test]
+    fn round_robin() {
+        let mut tournament = Tournament::<BitboardPositionWithOrdering>::new(5, 4, vec![sequence("3"), sequence("23")]).unwrap();
+        for seed in 0..3 {
+            tournament.add_player(&format!("random {}", seed), Box::new(RandomPlayer::with_seed(seed)));
+        }
+        assert_eq!(tournament.nb_games(), 12);
+
+        let mut played = 0;
+        tournament.run(|_| played += 1);
+        assert_eq!(played, 12);
+
+        let results = tournament.results();
+        for player in 0..3 {
+            // Two games against each opponent from each opening, half of them with red
+            assert_eq!(results.iter().filter(|result| result.red == player).count(), 4);
+            assert_eq!(results.iter().filter(|result| result.yellow == player).count(), 4);
+        }
+        for result in results {
+            assert_ne!(result.red, result.yellow);
+            assert!(matches!(result.outcome, MatchOutcome::Win(_) | MatchOutcome::Draw));
+        }
+    }
+
+    #[test]
+    fn scores() {
+        let mut tournament = Tournament::<BitboardPositionWithOrdering>::new(7, 6, vec![sequence("")]).unwrap();
+        // Red always wins with the first column, the scripts being shared by both colours
+        tournament.add_player("first", Box::new(ScriptedPlayer::new(&sequence("1212121"))));
+        tournament.add_player("second", Box::new(ScriptedPlayer::new(&sequence("1212121"))));
+        tournament.run(|_| ());
+
+        let results = tournament.results();
+        assert_eq!(results.len(), 2);
+        assert_eq!((results[0].red, results[0].score(0), results[0].score(1)), (0, 1., 0.));
+        assert_eq!((results[1].red, results[1].score(0), results[1].score(1)), (1, 0., 1.));
+    }
+
+    #[test]
+    fn abandoning_loses() {
+        let result = GameResult { red: 0, yellow: 1, opening: 0, moves: vec![], outcome: MatchOutcome::Abandoned(Cell::Red) };
+        assert_eq!(result.score(1), 1.);
+    }
+
+    #[test]
+    fn invalid_opening() {
+        assert!(Tournament::<BitboardPositionWithOrdering>::new(5, 4, vec![sequence("7")]).is_err());
+    }
+
+    #[test]
+    fn openings() {
+        assert_eq!(all_openings::<BitboardPositionWithOrdering>(7, 6, 2).len(), 49);
+        assert_eq!(all_openings::<BitboardPositionWithOrdering>(3, 1, 2).len(), 6);
+        // No opening ends the game
+        for opening in all_openings::<BitboardPositionWithOrdering>(4, 4, 7) {
+            let mut position = BitboardPositionWithOrdering::new(4, 4);
+            for &column in opening.sequence() {
+                assert!(!position.is_winning_move(column - 1));
+                position.play(column - 1);
+            }
+        }
+        assert_eq!(all_openings::<BitboardPositionWithOrdering>(2, 1, 2).len(), 0);
+    }
+}